@@ -0,0 +1,138 @@
+use std::collections::HashMap;
+
+pub type BddRef = usize;
+
+pub const FALSE: BddRef = 0;
+pub const TRUE: BddRef = 1;
+
+// terminals sort after every variable
+const TERMINAL: usize = usize::MAX;
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+enum Op {
+    And,
+    Or,
+    Xor,
+}
+
+// reduced ordered binary decision diagram, equal functions over the same variable order get
+// the same BddRef
+pub struct Bdd {
+    // (variable, low, high)
+    nodes: Vec<(usize, BddRef, BddRef)>,
+    unique: HashMap<(usize, BddRef, BddRef), BddRef>,
+    cache: HashMap<(Op, BddRef, BddRef), BddRef>,
+}
+
+impl Default for Bdd {
+    fn default() -> Self {
+        Bdd::new()
+    }
+}
+
+impl Bdd {
+    pub fn new() -> Bdd {
+        Bdd {
+            nodes: vec![(TERMINAL, FALSE, FALSE), (TERMINAL, TRUE, TRUE)],
+            unique: HashMap::new(),
+            cache: HashMap::new(),
+        }
+    }
+
+    pub fn constant(value: bool) -> BddRef {
+        match value {
+            true => TRUE,
+            false => FALSE,
+        }
+    }
+
+    pub fn var(&mut self, variable: usize) -> BddRef {
+        self.make(variable, FALSE, TRUE)
+    }
+
+    fn make(&mut self, variable: usize, low: BddRef, high: BddRef) -> BddRef {
+        if low == high {
+            return low;
+        }
+        if let Some(node) = self.unique.get(&(variable, low, high)) {
+            return *node;
+        }
+        self.nodes.push((variable, low, high));
+        let node = self.nodes.len() - 1;
+        self.unique.insert((variable, low, high), node);
+        node
+    }
+
+    pub fn negate(&mut self, a: BddRef) -> BddRef {
+        self.xor(a, TRUE)
+    }
+
+    pub fn and(&mut self, a: BddRef, b: BddRef) -> BddRef {
+        self.apply(Op::And, a, b)
+    }
+
+    pub fn or(&mut self, a: BddRef, b: BddRef) -> BddRef {
+        self.apply(Op::Or, a, b)
+    }
+
+    pub fn xor(&mut self, a: BddRef, b: BddRef) -> BddRef {
+        self.apply(Op::Xor, a, b)
+    }
+
+    fn apply(&mut self, op: Op, a: BddRef, b: BddRef) -> BddRef {
+        if a <= TRUE && b <= TRUE {
+            let (a, b) = (a == TRUE, b == TRUE);
+            return Bdd::constant(match op {
+                Op::And => a & b,
+                Op::Or => a | b,
+                Op::Xor => a ^ b,
+            });
+        }
+        if let Some(result) = self.cache.get(&(op, a, b)) {
+            return *result;
+        }
+
+        let (a_variable, a_low, a_high) = self.nodes[a];
+        let (b_variable, b_low, b_high) = self.nodes[b];
+        let variable = a_variable.min(b_variable);
+        let (a_low, a_high) = if a_variable == variable { (a_low, a_high) } else { (a, a) };
+        let (b_low, b_high) = if b_variable == variable { (b_low, b_high) } else { (b, b) };
+
+        let low = self.apply(op, a_low, b_low);
+        let high = self.apply(op, a_high, b_high);
+        let result = self.make(variable, low, high);
+        self.cache.insert((op, a, b), result);
+        result
+    }
+
+    pub fn node_count(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn eval(&self, mut node: BddRef, values: &[bool]) -> bool {
+        while node > TRUE {
+            let (variable, low, high) = self.nodes[node];
+            node = if values[variable] { high } else { low };
+        }
+        node == TRUE
+    }
+
+    // one assignment making the function true, variables it doesn't depend on are false
+    pub fn any_sat(&self, mut node: BddRef, variable_count: usize) -> Option<Vec<bool>> {
+        if node == FALSE {
+            return None;
+        }
+        let mut values = vec![false; variable_count];
+        while node > TRUE {
+            let (variable, low, high) = self.nodes[node];
+            // only FALSE is unsatisfiable, every other reduced node has a path to TRUE
+            if low != FALSE {
+                node = low;
+            } else {
+                values[variable] = true;
+                node = high;
+            }
+        }
+        Some(values)
+    }
+}
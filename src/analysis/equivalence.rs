@@ -0,0 +1,374 @@
+use crate::analysis::bdd::*;
+use crate::formats;
+use crate::types::circuit::*;
+use crate::types::gate_type::*;
+use crate::types::keys::*;
+use std::collections::{HashMap, HashSet};
+
+// a few hundred megabytes of nodes, multipliers and the like blow up past any sensible size
+pub const MAX_BDD_NODES: usize = 1 << 22;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Matching {
+    // IN / OUT gates with the same name (label, or in0 / out0 ... when unlabeled)
+    Names,
+    // the n-th IN / OUT gate of both circuits, sorted top-down then left-right
+    Positions,
+}
+
+impl Matching {
+    // names when both circuits use the same ones, positions otherwise
+    pub fn guess(a: &Circuit, b: &Circuit) -> Matching {
+        let names = |circuit: &Circuit, gate_type: GateType| -> HashSet<String> {
+            circuit.io_names(gate_type).into_iter().map(|(_, name)| name).collect()
+        };
+        if names(a, GateType::IN) == names(b, GateType::IN) && names(a, GateType::OUT) == names(b, GateType::OUT) {
+            Matching::Names
+        } else {
+            Matching::Positions
+        }
+    }
+}
+
+pub struct Counterexample {
+    pub inputs: Vec<(String, bool)>,
+    // (name, value in the first circuit, value in the second)
+    pub outputs: Vec<(String, bool, bool)>,
+}
+
+pub enum Equivalence {
+    Equivalent,
+    Different(Counterexample),
+}
+
+impl Equivalence {
+    pub fn to_text(&self) -> String {
+        match self {
+            Equivalence::Equivalent => "equivalent\n".to_string(),
+            Equivalence::Different(counterexample) => {
+                let mut text = "not equivalent, counterexample:\n".to_string();
+                for (name, value) in &counterexample.inputs {
+                    text += &format!("  {} = {}\n", name, *value as u8);
+                }
+                for (name, a, b) in &counterexample.outputs {
+                    let marker = if a != b { "  <-- differs" } else { "" };
+                    text += &format!("  {}: {} vs {}{}\n", name, *a as u8, *b as u8, marker);
+                }
+                text
+            }
+        }
+    }
+}
+
+// pairs up the IN / OUT gates of both circuits, names come from the first circuit
+fn pair_up(
+    a: &Circuit,
+    b: &Circuit,
+    gate_type: GateType,
+    matching: Matching,
+) -> Result<Vec<(String, GateKey, GateKey)>, String> {
+    let a_gates = a.io_names(gate_type.clone());
+    let b_gates = b.io_names(gate_type.clone());
+    if a_gates.len() != b_gates.len() {
+        return Err(format!(
+            "the circuits have {} and {} {} gates",
+            a_gates.len(),
+            b_gates.len(),
+            gate_type.text()
+        ));
+    }
+
+    match matching {
+        Matching::Positions => Ok(a_gates
+            .into_iter()
+            .zip(b_gates)
+            .map(|((a_key, name), (b_key, _))| (name, a_key, b_key))
+            .collect()),
+        Matching::Names => {
+            // a name used twice would pair two gates with the same counterpart
+            for (gates, which) in [(&a_gates, "first"), (&b_gates, "second")] {
+                let mut seen = HashSet::new();
+                if let Some((_, name)) = gates.iter().find(|(_, name)| !seen.insert(name)) {
+                    return Err(format!("{} gate name {} is used twice in the {} circuit", gate_type.text(), name, which));
+                }
+            }
+            a_gates
+                .into_iter()
+                .map(|(a_key, name)| {
+                    let (b_key, _) = b_gates
+                        .iter()
+                        .find(|(_, b_name)| *b_name == name)
+                        .ok_or(format!("{} gate {} is missing from the second circuit", gate_type.text(), name))?;
+                    Ok((name, a_key, *b_key))
+                })
+                .collect()
+        }
+    }
+}
+
+// proves both circuits compute the same OUT values for every IN combination, or finds one
+// where they don't. works on the combinational logic only, feedback loops are rejected
+pub fn check(a: &Circuit, b: &Circuit, matching: Matching) -> Result<Equivalence, String> {
+    let inputs = pair_up(a, b, GateType::IN, matching)?;
+    let outputs = pair_up(a, b, GateType::OUT, matching)?;
+
+    let mut bdd = Bdd::new();
+    let mut a_builder = BddBuilder::new(a);
+    let mut b_builder = BddBuilder::new(b);
+    for (index, (_, a_key, b_key)) in inputs.iter().enumerate() {
+        let variable = bdd.var(index);
+        a_builder.bind(*a_key, variable);
+        b_builder.bind(*b_key, variable);
+    }
+
+    let mut functions = vec![];
+    let mut miter = FALSE;
+    for (_, a_key, b_key) in &outputs {
+        let a_function = a_builder.out_function(&mut bdd, *a_key)?;
+        let b_function = b_builder.out_function(&mut bdd, *b_key)?;
+        let difference = bdd.xor(a_function, b_function);
+        miter = bdd.or(miter, difference);
+        functions.push((a_function, b_function));
+    }
+
+    match bdd.any_sat(miter, inputs.len()) {
+        None => Ok(Equivalence::Equivalent),
+        Some(values) => Ok(Equivalence::Different(Counterexample {
+            inputs: inputs.iter().map(|(name, _, _)| name.clone()).zip(values.iter().copied()).collect(),
+            outputs: outputs
+                .iter()
+                .zip(functions)
+                .map(|((name, _, _), (a_function, b_function))| {
+                    (name.clone(), bdd.eval(a_function, &values), bdd.eval(b_function, &values))
+                })
+                .collect(),
+        })),
+    }
+}
+
+pub fn check_files(a_path: &str, b_path: &str) -> Result<Equivalence, String> {
+    let a = formats::load(a_path).map_err(|e| format!("{}: {}", a_path, e))?;
+    let b = formats::load(b_path).map_err(|e| format!("{}: {}", b_path, e))?;
+    check(&a, &b, Matching::guess(&a, &b))
+}
+
+// the bdd of every wire in a fan-in cone, one gate at a time so shared logic stays shared
+struct BddBuilder<'a> {
+    circuit: &'a Circuit,
+    inputs: HashMap<GateKey, BddRef>,
+    visiting: HashSet<GateKey>,
+    done: HashMap<GateKey, BddRef>,
+}
+
+impl<'a> BddBuilder<'a> {
+    fn new(circuit: &'a Circuit) -> BddBuilder<'a> {
+        BddBuilder {
+            circuit,
+            inputs: HashMap::new(),
+            visiting: HashSet::new(),
+            done: HashMap::new(),
+        }
+    }
+
+    fn bind(&mut self, key: GateKey, variable: BddRef) {
+        self.inputs.insert(key, variable);
+    }
+
+    fn out_function(&mut self, bdd: &mut Bdd, out_key: GateKey) -> Result<BddRef, String> {
+        match self.circuit.gates[out_key].input[0].wire_index {
+            Some(wire_key) => self.wire_function(bdd, wire_key),
+            None => Ok(FALSE),
+        }
+    }
+
+    fn wire_function(&mut self, bdd: &mut Bdd, wire_key: WireKey) -> Result<BddRef, String> {
+        let source = self.source(wire_key)?;
+        self.gate_function(bdd, source)
+    }
+
+    // depth first with its own stack so long chains from imported netlists can't overflow the
+    // thread's, a gate is expanded first and built once all its sources are done
+    fn gate_function(&mut self, bdd: &mut Bdd, root: GateKey) -> Result<BddRef, String> {
+        let mut stack = vec![(root, false)];
+        while let Some((key, expanded)) = stack.pop() {
+            if self.done.contains_key(&key) || self.inputs.contains_key(&key) {
+                continue;
+            }
+            let gate = &self.circuit.gates[key];
+            if !expanded {
+                // expanded but not done means it's further up this path
+                if !self.visiting.insert(key) {
+                    return Err("a circuit has a feedback loop, only combinational logic can be checked".to_string());
+                }
+                stack.push((key, true));
+                for pin in &gate.input {
+                    if let Some(wire_key) = pin.wire_index {
+                        stack.push((self.source(wire_key)?, false));
+                    }
+                }
+                continue;
+            }
+
+            let mut pins = vec![];
+            for pin in &gate.input {
+                pins.push(match pin.wire_index {
+                    Some(wire_key) => self.function(self.source(wire_key)?),
+                    None => FALSE,
+                });
+            }
+
+            let function = match &gate.gate_type {
+                GateType::NOT => bdd.negate(pins[0]),
+                GateType::OR => bdd.or(pins[0], pins[1]),
+                GateType::XOR => bdd.xor(pins[0], pins[1]),
+                GateType::XNOR => {
+                    let xor = bdd.xor(pins[0], pins[1]);
+                    bdd.negate(xor)
+                }
+                GateType::NOR => {
+                    let or = bdd.or(pins[0], pins[1]);
+                    bdd.negate(or)
+                }
+                GateType::AND => bdd.and(pins[0], pins[1]),
+                GateType::NAND => {
+                    let and = bdd.and(pins[0], pins[1]);
+                    bdd.negate(and)
+                }
+                other => return Err(format!("{} gates can't be checked", other.text())),
+            };
+            if bdd.node_count() > MAX_BDD_NODES {
+                return Err(format!("the diagrams grow past {} nodes, the circuits are too big to check", MAX_BDD_NODES));
+            }
+
+            self.visiting.remove(&key);
+            self.done.insert(key, function);
+        }
+        Ok(self.function(root))
+    }
+
+    fn source(&self, wire_key: WireKey) -> Result<GateKey, String> {
+        let wire = self
+            .circuit
+            .wires
+            .get(wire_key)
+            .ok_or(format!("missing wire {:?}", wire_key))?;
+        Ok(wire.source.gate_index)
+    }
+
+    // only for gates that are done or bound to a variable
+    fn function(&self, key: GateKey) -> BddRef {
+        self.done.get(&key).or(self.inputs.get(&key)).copied().unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::gate::*;
+    use crate::types::pin_type::*;
+    use macroquad::prelude::Rect;
+
+    // IN into 'length' NOT gates, then an OUT
+    fn chain(length: usize) -> Circuit {
+        let mut circuit = Circuit::new();
+        let mut previous = circuit.gates.insert(Gate::new(Rect::new(0.0, 0.0, 64.0, 64.0), Rotation::Up, GateType::IN));
+        for x in 1..=length + 1 {
+            let gate_type = if x > length { GateType::OUT } else { GateType::NOT };
+            let gate = circuit.gates.insert(Gate::new(Rect::new(x as f32 * 64.0, 0.0, 64.0, 64.0), Rotation::Up, gate_type));
+            circuit.connect_wire(previous, gate, 0, PinType::Output, 0, PinType::Input);
+            previous = gate;
+        }
+        circuit
+    }
+
+    #[test]
+    fn deep_chains_dont_overflow_the_stack() {
+        let result = check(&chain(100_000), &chain(2), Matching::Names).unwrap();
+        assert!(matches!(result, Equivalence::Equivalent));
+        let result = check(&chain(100_001), &chain(2), Matching::Names).unwrap();
+        assert!(matches!(result, Equivalence::Different(_)));
+    }
+
+    #[test]
+    fn duplicate_names_are_refused() {
+        let mut a = chain(1);
+        let mut b = chain(1);
+        for circuit in [&mut a, &mut b] {
+            let mut x = 0.0;
+            for _ in 0..2 {
+                let mut gate = Gate::new(Rect::new(x, 128.0, 64.0, 64.0), Rotation::Up, GateType::IN);
+                gate.label = Some("twice".to_string());
+                circuit.gates.insert(gate);
+                x += 64.0;
+            }
+        }
+        assert!(matches!(Matching::guess(&a, &b), Matching::Names));
+        let error = check(&a, &b, Matching::Names).err().unwrap();
+        assert!(error.contains("used twice"), "{}", error);
+    }
+
+    // IN a and b into 'gate_type', optionally through a NOT each, then OUT y
+    fn two_inputs(gate_type: GateType, inverted: bool) -> Circuit {
+        let mut circuit = Circuit::new();
+        let add = |circuit: &mut Circuit, gate_type: GateType, x: f32, y: f32, label: Option<&str>| {
+            let mut gate = Gate::new(Rect::new(x * 64.0, y * 64.0, 64.0, 64.0), Rotation::Up, gate_type);
+            gate.label = label.map(str::to_string);
+            circuit.gates.insert(gate)
+        };
+        let a = add(&mut circuit, GateType::IN, 0.0, 0.0, Some("a"));
+        let b = add(&mut circuit, GateType::IN, 0.0, 1.0, Some("b"));
+        let gate = add(&mut circuit, gate_type, 2.0, 0.0, None);
+        let y = add(&mut circuit, GateType::OUT, 3.0, 0.0, Some("y"));
+        for (pin, input) in [a, b].into_iter().enumerate() {
+            match inverted {
+                true => {
+                    let not = add(&mut circuit, GateType::NOT, 1.0, pin as f32, None);
+                    circuit.connect_wire(input, not, 0, PinType::Output, 0, PinType::Input);
+                    circuit.connect_wire(not, gate, 0, PinType::Output, pin, PinType::Input);
+                }
+                false => circuit.connect_wire(input, gate, 0, PinType::Output, pin, PinType::Input),
+            }
+        }
+        circuit.connect_wire(gate, y, 0, PinType::Output, 0, PinType::Input);
+        circuit
+    }
+
+    #[test]
+    fn de_morgan_pairs_are_equivalent() {
+        let nand = two_inputs(GateType::NAND, false);
+        let or = two_inputs(GateType::OR, true);
+        assert!(matches!(check(&nand, &or, Matching::Names), Ok(Equivalence::Equivalent)));
+        let nor = two_inputs(GateType::NOR, false);
+        let and = two_inputs(GateType::AND, true);
+        assert!(matches!(check(&nor, &and, Matching::Names), Ok(Equivalence::Equivalent)));
+    }
+
+    #[test]
+    fn counterexamples_are_inputs_the_circuits_disagree_on() {
+        let and = two_inputs(GateType::AND, false);
+        let or = two_inputs(GateType::OR, false);
+        let Ok(Equivalence::Different(counterexample)) = check(&and, &or, Matching::Names) else {
+            panic!("and and or are different");
+        };
+        let values: HashMap<&str, bool> = counterexample.inputs.iter().map(|(name, value)| (name.as_str(), *value)).collect();
+        assert_eq!(values.len(), 2);
+        assert_ne!(values["a"], values["b"]);
+
+        // the reported outputs are what simulating both gives
+        let simulate = |circuit: &Circuit| {
+            let mut circuit = circuit.clone();
+            for (key, name) in circuit.io_names(GateType::IN) {
+                circuit.gates[key].active = values[name.as_str()];
+            }
+            circuit.settle(100);
+            let (key, _) = circuit.io_names(GateType::OUT)[0];
+            circuit.read_input(&circuit.gates[key], 0)
+        };
+        let [(name, a, b)] = &counterexample.outputs[..] else {
+            panic!("one output");
+        };
+        assert_eq!(name, "y");
+        assert_eq!((*a, *b), (simulate(&and), simulate(&or)));
+        assert_ne!(a, b);
+    }
+}
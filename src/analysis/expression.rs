@@ -0,0 +1,484 @@
+use crate::analysis::minimize::*;
+use crate::types::circuit::*;
+use crate::types::gate_type::*;
+use crate::types::keys::*;
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+// quine-mccluskey gets slow past this
+pub const MAX_SIMPLIFY_VARIABLES: usize = 12;
+// nodes in one expression. shared fan-in is copied into every gate using it, so reconvergent
+// circuits grow exponentially with depth and would run out of memory long before printing
+pub const MAX_EXPR_SIZE: usize = 10_000;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Expr {
+    Const(bool),
+    Var(usize),
+    Not(Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Xor(Box<Expr>, Box<Expr>),
+}
+
+impl Expr {
+    pub fn negate(expr: Expr) -> Expr {
+        Expr::Not(Box::new(expr))
+    }
+
+    pub fn and(a: Expr, b: Expr) -> Expr {
+        Expr::And(Box::new(a), Box::new(b))
+    }
+
+    pub fn or(a: Expr, b: Expr) -> Expr {
+        Expr::Or(Box::new(a), Box::new(b))
+    }
+
+    pub fn xor(a: Expr, b: Expr) -> Expr {
+        Expr::Xor(Box::new(a), Box::new(b))
+    }
+
+    // values are indexed by variable
+    pub fn eval(&self, values: &[bool]) -> bool {
+        match self {
+            Expr::Const(value) => *value,
+            Expr::Var(index) => values[*index],
+            Expr::Not(a) => !a.eval(values),
+            Expr::And(a, b) => a.eval(values) & b.eval(values),
+            Expr::Or(a, b) => a.eval(values) | b.eval(values),
+            Expr::Xor(a, b) => a.eval(values) ^ b.eval(values),
+        }
+    }
+
+    pub fn variables(&self) -> BTreeSet<usize> {
+        let mut variables = BTreeSet::new();
+        self.collect_variables(&mut variables);
+        variables
+    }
+
+    fn collect_variables(&self, variables: &mut BTreeSet<usize>) {
+        match self {
+            Expr::Const(_) => {}
+            Expr::Var(index) => {
+                variables.insert(*index);
+            }
+            Expr::Not(a) => a.collect_variables(variables),
+            Expr::And(a, b) | Expr::Or(a, b) | Expr::Xor(a, b) => {
+                a.collect_variables(variables);
+                b.collect_variables(variables);
+            }
+        }
+    }
+
+    // binds tighter as it grows: | then ^ then & then !
+    fn precedence(&self) -> u8 {
+        match self {
+            Expr::Or(..) => 1,
+            Expr::Xor(..) => 2,
+            Expr::And(..) => 3,
+            Expr::Not(_) => 4,
+            Expr::Const(_) | Expr::Var(_) => 5,
+        }
+    }
+
+    pub fn format(&self, names: &[String]) -> String {
+        let wrap = |child: &Expr| -> String {
+            match child.precedence() < self.precedence() {
+                true => format!("({})", child.format(names)),
+                false => child.format(names),
+            }
+        };
+
+        match self {
+            Expr::Const(value) => (*value as u8).to_string(),
+            Expr::Var(index) => names[*index].clone(),
+            Expr::Not(a) => format!("!{}", wrap(a)),
+            Expr::And(a, b) => format!("{} & {}", wrap(a), wrap(b)),
+            Expr::Or(a, b) => format!("{} | {}", wrap(a), wrap(b)),
+            Expr::Xor(a, b) => format!("{} ^ {}", wrap(a), wrap(b)),
+        }
+    }
+
+    // implicant literal j refers to variables[j]
+    pub fn from_implicants(implicants: &[Implicant], variables: &[usize]) -> Expr {
+        let products = implicants.iter().map(|implicant| {
+            implicant
+                .literals(variables.len())
+                .into_iter()
+                .map(|(literal, positive)| match positive {
+                    true => Expr::Var(variables[literal]),
+                    false => Expr::negate(Expr::Var(variables[literal])),
+                })
+                .reduce(Expr::and)
+                .unwrap_or(Expr::Const(true))
+        });
+        products.reduce(Expr::or).unwrap_or(Expr::Const(false))
+    }
+
+    // minterms over the variables the expression actually uses, variable order is kept
+    pub fn minterms(&self) -> Result<(Vec<usize>, Vec<u32>), String> {
+        let variables: Vec<usize> = self.variables().into_iter().collect();
+        if variables.len() > MAX_SIMPLIFY_VARIABLES {
+            return Err(format!(
+                "{} variables is too many to simplify (max {})",
+                variables.len(),
+                MAX_SIMPLIFY_VARIABLES
+            ));
+        }
+
+        let mut values = vec![false; variables.last().map_or(0, |last| last + 1)];
+        let mut minterms = vec![];
+        for minterm in 0..(1u32 << variables.len()) {
+            for (position, variable) in variables.iter().enumerate() {
+                values[*variable] = minterm >> (variables.len() - 1 - position) & 1 == 1;
+            }
+            if self.eval(&values) {
+                minterms.push(minterm);
+            }
+        }
+        Ok((variables, minterms))
+    }
+
+    // '!' / '~', '&', '^' and '|' (tightest first), parentheses, 0 / 1 and identifiers.
+    // returns the identifiers sorted, Var indexes point into them
+    pub fn parse(text: &str) -> Result<(Expr, Vec<String>), String> {
+        let tokens = tokenize(text)?;
+        let mut names: Vec<String> = tokens
+            .iter()
+            .filter(|token| token.chars().next().unwrap().is_alphabetic() || token.starts_with('_'))
+            .cloned()
+            .collect();
+        names.sort();
+        names.dedup();
+
+        let mut parser = Parser { tokens: &tokens, position: 0, names: &names };
+        let expr = parser.or()?;
+        if let Some(token) = tokens.get(parser.position) {
+            return Err(format!("unexpected '{}'", token));
+        }
+        Ok((expr, names))
+    }
+
+    // minimal sum of products
+    pub fn simplify(&self) -> Result<Expr, String> {
+        let (variables, minterms) = self.minterms()?;
+        Ok(Expr::from_implicants(&minimize(&minterms, &[]), &variables))
+    }
+}
+
+fn tokenize(text: &str) -> Result<Vec<String>, String> {
+    let mut tokens = vec![];
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c.is_whitespace() {
+            continue;
+        } else if c.is_alphanumeric() || c == '_' {
+            let mut token = c.to_string();
+            while let Some(next) = chars.peek().filter(|next| next.is_alphanumeric() || **next == '_') {
+                token.push(*next);
+                chars.next();
+            }
+            tokens.push(token);
+        } else if "!~&|^()".contains(c) {
+            tokens.push(c.to_string());
+        } else {
+            return Err(format!("unexpected character '{}'", c));
+        }
+    }
+    Ok(tokens)
+}
+
+struct Parser<'a> {
+    tokens: &'a [String],
+    position: usize,
+    names: &'a [String],
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<&str> {
+        self.tokens.get(self.position).map(|token| token.as_str())
+    }
+
+    fn binary(
+        &mut self,
+        operator: &str,
+        operand: fn(&mut Self) -> Result<Expr, String>,
+        combine: fn(Expr, Expr) -> Expr,
+    ) -> Result<Expr, String> {
+        let mut expr = operand(self)?;
+        while self.peek() == Some(operator) {
+            self.position += 1;
+            expr = combine(expr, operand(self)?);
+        }
+        Ok(expr)
+    }
+
+    fn or(&mut self) -> Result<Expr, String> {
+        self.binary("|", Self::xor, Expr::or)
+    }
+
+    fn xor(&mut self) -> Result<Expr, String> {
+        self.binary("^", Self::and, Expr::xor)
+    }
+
+    fn and(&mut self) -> Result<Expr, String> {
+        self.binary("&", Self::unary, Expr::and)
+    }
+
+    fn unary(&mut self) -> Result<Expr, String> {
+        let token = self.peek().ok_or("unexpected end of expression")?.to_string();
+        self.position += 1;
+        match token.as_str() {
+            "!" | "~" => Ok(Expr::negate(self.unary()?)),
+            "(" => {
+                let expr = self.or()?;
+                match self.peek() {
+                    Some(")") => {
+                        self.position += 1;
+                        Ok(expr)
+                    }
+                    _ => Err("missing ')'".to_string()),
+                }
+            }
+            "0" => Ok(Expr::Const(false)),
+            "1" => Ok(Expr::Const(true)),
+            _ => match self.names.iter().position(|name| *name == token) {
+                Some(index) => Ok(Expr::Var(index)),
+                None => Err(format!("unexpected '{}'", token)),
+            },
+        }
+    }
+}
+
+pub struct Expressions {
+    pub inputs: Vec<String>,
+    // name, expression and its sum of products or why there is none. simplifying can take a
+    // while so it's done once here and not every time the outputs are shown
+    pub outputs: Vec<(String, Expr, Result<Expr, String>)>,
+}
+
+impl Expressions {
+    // one expression per OUT gate in terms of the IN gates
+    pub fn extract(circuit: &Circuit) -> Result<Expressions, String> {
+        let inputs = circuit.io_names(GateType::IN);
+        let mut variables = HashMap::new();
+        for (index, (key, _)) in inputs.iter().enumerate() {
+            if let Some(wire_key) = circuit.gates[*key].output[0].wire_index {
+                variables.insert(wire_key, index);
+            }
+        }
+
+        let mut walker = ConeWalker::new(circuit, variables);
+        let mut outputs = vec![];
+        for (key, name) in circuit.io_names(GateType::OUT) {
+            let expr = match circuit.gates[key].input[0].wire_index {
+                Some(wire_key) => walker.wire_expr(wire_key)?,
+                None => Expr::Const(false),
+            };
+            let simplified = expr.simplify();
+            outputs.push((name, expr, simplified));
+        }
+
+        Ok(Expressions {
+            inputs: inputs.into_iter().map(|(_, name)| name).collect(),
+            outputs,
+        })
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut text = String::new();
+        for (name, expr, simplified) in &self.outputs {
+            text += &format!("{} = {}\n", name, expr.format(&self.inputs));
+            match simplified {
+                Ok(simplified) => text += &format!("  sop: {}\n", simplified.format(&self.inputs)),
+                Err(e) => text += &format!("  sop: {}\n", e),
+            }
+        }
+        text
+    }
+}
+
+// walks the fan-in cone of a wire back through Wire.source, stopping at the variable wires
+pub struct ConeWalker<'a> {
+    circuit: &'a Circuit,
+    variables: HashMap<WireKey, usize>,
+    visiting: HashSet<GateKey>,
+    // expression and its node count
+    done: HashMap<GateKey, (Expr, usize)>,
+}
+
+impl<'a> ConeWalker<'a> {
+    pub fn new(circuit: &'a Circuit, variables: HashMap<WireKey, usize>) -> ConeWalker<'a> {
+        ConeWalker {
+            circuit,
+            variables,
+            visiting: HashSet::new(),
+            done: HashMap::new(),
+        }
+    }
+
+    pub fn wire_expr(&mut self, wire_key: WireKey) -> Result<Expr, String> {
+        self.sized_wire_expr(wire_key).map(|(expr, _)| expr)
+    }
+
+    fn sized_wire_expr(&mut self, wire_key: WireKey) -> Result<(Expr, usize), String> {
+        if let Some(index) = self.variables.get(&wire_key) {
+            return Ok((Expr::Var(*index), 1));
+        }
+        let wire = self
+            .circuit
+            .wires
+            .get(wire_key)
+            .ok_or(format!("missing wire {:?}", wire_key))?;
+        self.gate_expr(wire.source.gate_index)
+    }
+
+    // depth first with its own stack, imported netlists can be deeper than the thread's. a gate
+    // is expanded first and built once everything it reads is done
+    fn gate_expr(&mut self, root: GateKey) -> Result<(Expr, usize), String> {
+        let mut stack = vec![(root, false)];
+        while let Some((gate_key, expanded)) = stack.pop() {
+            if self.done.contains_key(&gate_key) {
+                continue;
+            }
+            let gate = &self.circuit.gates[gate_key];
+            if !expanded {
+                // expanded but not done means it's further up this path
+                if !self.visiting.insert(gate_key) {
+                    return Err("the circuit has a feedback loop, it has no boolean expression".to_string());
+                }
+                stack.push((gate_key, true));
+                for pin in &gate.input {
+                    if let Some(source) = self.source(pin.wire_index)? {
+                        stack.push((source, false));
+                    }
+                }
+                continue;
+            }
+
+            let mut pins = vec![];
+            let mut size = 0;
+            for pin in &gate.input {
+                let (expr, pin_size) = match (pin.wire_index, self.source(pin.wire_index)?) {
+                    (_, Some(source)) => self.done[&source].clone(),
+                    (Some(wire_key), None) => (Expr::Var(self.variables[&wire_key]), 1),
+                    (None, None) => (Expr::Const(false), 1),
+                };
+                pins.push(expr);
+                size += pin_size;
+            }
+            // the gate adds at most two nodes, a NAND is a NOT over an AND
+            size += 2;
+            if size > MAX_EXPR_SIZE {
+                return Err(format!(
+                    "the expression grows past {} terms, the circuit reuses too many signals to write it out",
+                    MAX_EXPR_SIZE
+                ));
+            }
+
+            let pin = |index: usize| pins[index].clone();
+            let expr = match &gate.gate_type {
+                GateType::NOT => Expr::negate(pin(0)),
+                GateType::OR => Expr::or(pin(0), pin(1)),
+                GateType::XOR => Expr::xor(pin(0), pin(1)),
+                GateType::XNOR => Expr::negate(Expr::xor(pin(0), pin(1))),
+                GateType::NOR => Expr::negate(Expr::or(pin(0), pin(1))),
+                GateType::AND => Expr::and(pin(0), pin(1)),
+                GateType::NAND => Expr::negate(Expr::and(pin(0), pin(1))),
+                GateType::IN => Expr::Const(gate.active),
+                other => return Err(format!("{} gates have no boolean expression", other.text())),
+            };
+
+            self.visiting.remove(&gate_key);
+            self.done.insert(gate_key, (expr, size));
+        }
+        Ok(self.done[&root].clone())
+    }
+
+    // the gate driving a pin's wire, None for unconnected pins and the variable wires
+    fn source(&self, wire_index: Option<WireKey>) -> Result<Option<GateKey>, String> {
+        let Some(wire_key) = wire_index.filter(|wire_key| !self.variables.contains_key(wire_key)) else {
+            return Ok(None);
+        };
+        let wire = self
+            .circuit
+            .wires
+            .get(wire_key)
+            .ok_or(format!("missing wire {:?}", wire_key))?;
+        Ok(Some(wire.source.gate_index))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::gate::*;
+    use crate::types::pin_type::*;
+    use macroquad::prelude::Rect;
+
+    // IN into 'depth' XOR gates that each take the previous one on both pins, then an OUT
+    fn reconvergent(depth: usize) -> Circuit {
+        let mut circuit = Circuit::new();
+        let mut add = |gate_type: GateType, x: usize| {
+            circuit.gates.insert(Gate::new(Rect::new(x as f32 * 64.0, 0.0, 64.0, 64.0), Rotation::Up, gate_type))
+        };
+        let mut gates = vec![add(GateType::IN, 0)];
+        for x in 1..=depth {
+            gates.push(add(GateType::XOR, x));
+        }
+        gates.push(add(GateType::OUT, depth + 1));
+        for pair in gates.windows(2) {
+            circuit.connect_wire(pair[0], pair[1], 0, PinType::Output, 0, PinType::Input);
+            if pair[1] != *gates.last().unwrap() {
+                circuit.connect_wire(pair[0], pair[1], 0, PinType::Output, 1, PinType::Input);
+            }
+        }
+        circuit
+    }
+
+    // 'start' into a chain of NOT gates, then an OUT
+    fn chain(start: GateType, length: usize) -> Circuit {
+        let mut circuit = Circuit::new();
+        let mut previous = circuit.gates.insert(Gate::new(Rect::new(0.0, 0.0, 64.0, 64.0), Rotation::Up, start));
+        for x in 1..=length + 1 {
+            let gate_type = if x > length { GateType::OUT } else { GateType::NOT };
+            let gate = circuit.gates.insert(Gate::new(Rect::new(x as f32 * 64.0, 0.0, 64.0, 64.0), Rotation::Up, gate_type));
+            circuit.connect_wire(previous, gate, 0, PinType::Output, 0, PinType::Input);
+            previous = gate;
+        }
+        circuit
+    }
+
+    #[test]
+    fn deep_chains_dont_overflow_the_stack() {
+        let expressions = Expressions::extract(&chain(GateType::IN, 1001)).unwrap();
+        let (_, expr, _) = &expressions.outputs[0];
+        assert!(expr.eval(&[false]) && !expr.eval(&[true]));
+
+        // the walk has to get all the way down before it finds the flip-flop
+        let error = Expressions::extract(&chain(GateType::DFF, 100_000)).err().unwrap();
+        assert!(error.contains("no boolean expression"), "{}", error);
+    }
+
+    #[test]
+    fn feedback_loops_are_refused() {
+        let mut circuit = chain(GateType::IN, 3);
+        let nots: Vec<GateKey> = circuit.gates.iter().filter(|(_, gate)| gate.gate_type == GateType::NOT).map(|(key, _)| key).collect();
+        circuit.connect_wire(nots[2], nots[0], 0, PinType::Output, 0, PinType::Input);
+        let error = Expressions::extract(&circuit).err().unwrap();
+        assert!(error.contains("feedback loop"), "{}", error);
+    }
+
+    #[test]
+    fn shared_fan_in_is_written_out() {
+        let expressions = Expressions::extract(&reconvergent(3)).unwrap();
+        let (_, expr, _) = &expressions.outputs[0];
+        assert_eq!(expr.variables().len(), 1);
+        assert!(!expr.eval(&[false]) && !expr.eval(&[true]));
+    }
+
+    #[test]
+    fn exponential_expressions_are_refused() {
+        let error = Expressions::extract(&reconvergent(64)).err().unwrap();
+        assert!(error.contains("grows past"), "{}", error);
+    }
+}
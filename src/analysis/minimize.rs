@@ -0,0 +1,142 @@
+use std::collections::BTreeSet;
+
+// a product term over variable_count variables, variable 0 is the msb of a minterm.
+// bits set in 'mask' are don't cares, 'value' holds the remaining literals
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Implicant {
+    pub value: u32,
+    pub mask: u32,
+}
+
+impl Implicant {
+    pub fn covers(&self, minterm: u32) -> bool {
+        minterm & !self.mask == self.value
+    }
+
+    // (variable index, positive) for every literal of the term
+    pub fn literals(&self, variable_count: usize) -> Vec<(usize, bool)> {
+        (0..variable_count)
+            .filter_map(|variable| {
+                let bit = 1 << (variable_count - 1 - variable);
+                match self.mask & bit {
+                    0 => Some((variable, self.value & bit != 0)),
+                    _ => None,
+                }
+            })
+            .collect()
+    }
+}
+
+// Quine–McCluskey: find the prime implicants, take the essential ones and cover the rest greedily
+pub fn minimize(minterms: &[u32], dont_cares: &[u32]) -> Vec<Implicant> {
+    if minterms.is_empty() {
+        return vec![];
+    }
+
+    let mut current: BTreeSet<Implicant> = minterms
+        .iter()
+        .chain(dont_cares)
+        .map(|minterm| Implicant { value: *minterm, mask: 0 })
+        .collect();
+    let mut primes: BTreeSet<Implicant> = BTreeSet::new();
+
+    while !current.is_empty() {
+        let terms: Vec<Implicant> = current.iter().copied().collect();
+        let mut combined = vec![false; terms.len()];
+        let mut next = BTreeSet::new();
+
+        for i in 0..terms.len() {
+            for j in (i + 1)..terms.len() {
+                let (a, b) = (terms[i], terms[j]);
+                let difference = a.value ^ b.value;
+                if a.mask == b.mask && difference.count_ones() == 1 {
+                    next.insert(Implicant {
+                        value: a.value & !difference,
+                        mask: a.mask | difference,
+                    });
+                    combined[i] = true;
+                    combined[j] = true;
+                }
+            }
+        }
+
+        for (term, was_combined) in terms.iter().zip(combined) {
+            if !was_combined {
+                primes.insert(*term);
+            }
+        }
+        current = next;
+    }
+
+    let primes: Vec<Implicant> = primes.into_iter().collect();
+    let mut uncovered: BTreeSet<u32> = minterms.iter().copied().collect();
+    let mut cover = vec![];
+
+    // essential primes are the only ones covering some minterm
+    for minterm in minterms {
+        let covering: Vec<&Implicant> = primes.iter().filter(|p| p.covers(*minterm)).collect();
+        if covering.len() == 1 && !cover.contains(covering[0]) {
+            cover.push(*covering[0]);
+        }
+    }
+    uncovered.retain(|minterm| !cover.iter().any(|p| p.covers(*minterm)));
+
+    // then the prime covering the most leftovers, fewer literals wins ties
+    while !uncovered.is_empty() {
+        let best = primes
+            .iter()
+            .max_by_key(|p| {
+                let count = uncovered.iter().filter(|m| p.covers(**m)).count();
+                (count, p.mask.count_ones())
+            })
+            .copied()
+            .unwrap();
+        uncovered.retain(|minterm| !best.covers(*minterm));
+        cover.push(best);
+    }
+
+    cover.sort_by_key(|p| (p.value, p.mask));
+    cover
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the cover gives 1 on every minterm and 0 off the minterms and don't cares
+    fn check_cover(cover: &[Implicant], minterms: &[u32], dont_cares: &[u32], variable_count: usize) {
+        for minterm in 0..1u32 << variable_count {
+            let covered = cover.iter().any(|implicant| implicant.covers(minterm));
+            if !dont_cares.contains(&minterm) {
+                assert_eq!(covered, minterms.contains(&minterm), "minterm {:b} in {:?}", minterm, cover);
+            }
+        }
+    }
+
+    #[test]
+    fn majority_needs_three_pairs() {
+        let minterms = [0b011, 0b101, 0b110, 0b111];
+        let cover = minimize(&minterms, &[]);
+        check_cover(&cover, &minterms, &[], 3);
+        assert_eq!(cover.len(), 3);
+        assert!(cover.iter().all(|implicant| implicant.mask.count_ones() == 1), "{:?}", cover);
+    }
+
+    #[test]
+    fn xor_doesnt_merge() {
+        let minterms = [0b001, 0b010, 0b100, 0b111];
+        let cover = minimize(&minterms, &[]);
+        check_cover(&cover, &minterms, &[], 3);
+        assert_eq!(cover.len(), 4);
+    }
+
+    #[test]
+    fn dont_cares_widen_the_terms() {
+        // with the a = 1 rows free, c alone covers b c = 01 and 11
+        let (minterms, dont_cares) = ([0b001, 0b011], [0b101, 0b111]);
+        let cover = minimize(&minterms, &dont_cares);
+        check_cover(&cover, &minterms, &dont_cares, 3);
+        assert_eq!(cover, [Implicant { value: 0b001, mask: 0b110 }]);
+        assert_eq!(cover[0].literals(3), [(2, true)]);
+    }
+}
@@ -0,0 +1,8 @@
+pub mod bdd;
+pub mod equivalence;
+pub mod expression;
+pub mod minimize;
+pub mod subcircuit;
+pub mod synthesis;
+pub mod test_bench;
+pub mod truth_table;
@@ -0,0 +1,97 @@
+use crate::analysis::expression::*;
+use crate::analysis::synthesis::*;
+use crate::types::circuit::*;
+use crate::types::gate_type::*;
+use crate::types::keys::*;
+use std::collections::{HashMap, HashSet};
+
+// wires crossing the edge of a group of gates, sorted by the position of their source pin
+pub struct Boundary {
+    // driven from outside, read inside
+    pub inputs: Vec<WireKey>,
+    // driven inside, read outside
+    pub outputs: Vec<WireKey>,
+}
+
+impl Boundary {
+    pub fn of(circuit: &Circuit, gates: &HashSet<GateKey>) -> Boundary {
+        let mut inputs = vec![];
+        let mut outputs = vec![];
+        for (wire_key, wire) in &circuit.wires {
+            let source_inside = gates.contains(&wire.source.gate_index);
+            let reads_inside = wire.connections.iter().any(|c| gates.contains(&c.gate_index));
+            let reads_outside = wire.connections.iter().any(|c| !gates.contains(&c.gate_index));
+            if !source_inside && reads_inside {
+                inputs.push(wire_key);
+            } else if source_inside && reads_outside {
+                outputs.push(wire_key);
+            }
+        }
+
+        let position = |wire_key: &WireKey| {
+            let source = &circuit.wires[*wire_key].source;
+            let rect = circuit.gates.get(source.gate_index).map(|gate| gate.rect).unwrap_or_default();
+            (rect.y, rect.x, source.pin_index)
+        };
+        inputs.sort_by(|a, b| position(a).partial_cmp(&position(b)).unwrap());
+        outputs.sort_by(|a, b| position(a).partial_cmp(&position(b)).unwrap());
+        Boundary { inputs, outputs }
+    }
+}
+
+// the boolean function of the boundary outputs over the boundary inputs,
+// inputs are named i0, i1, ... and outputs o0, o1, ... in boundary order
+pub fn group_function(circuit: &Circuit, gates: &HashSet<GateKey>) -> Result<(Boundary, FunctionSpec), String> {
+    for key in gates {
+        match circuit.gates[*key].gate_type {
+            GateType::IN | GateType::OUT | GateType::CUSTOM { .. } | GateType::DFF => {
+                return Err(format!("can't minimize a selection with {} gates", circuit.gates[*key].gate_type.text()));
+            }
+            _ => {}
+        }
+    }
+
+    let boundary = Boundary::of(circuit, gates);
+    if boundary.inputs.len() > MAX_SIMPLIFY_VARIABLES {
+        return Err(format!(
+            "{} wires enter the selection, at most {} can be minimized",
+            boundary.inputs.len(),
+            MAX_SIMPLIFY_VARIABLES
+        ));
+    }
+
+    let variables: HashMap<WireKey, usize> = boundary
+        .inputs
+        .iter()
+        .enumerate()
+        .map(|(index, wire_key)| (*wire_key, index))
+        .collect();
+    let mut walker = ConeWalker::new(circuit, variables);
+
+    let input_count = boundary.inputs.len();
+    let mut outputs = vec![];
+    for (index, wire_key) in boundary.outputs.iter().enumerate() {
+        let expr = walker.wire_expr(*wire_key)?;
+        let mut values = vec![false; input_count];
+        let mut minterms = vec![];
+        for minterm in 0..(1u32 << input_count) {
+            for (variable, value) in values.iter_mut().enumerate() {
+                *value = minterm >> (input_count - 1 - variable) & 1 == 1;
+            }
+            if expr.eval(&values) {
+                minterms.push(minterm);
+            }
+        }
+        outputs.push(OutputSpec {
+            name: format!("o{}", index),
+            minterms,
+            dont_cares: vec![],
+        });
+    }
+
+    let spec = FunctionSpec {
+        inputs: (0..input_count).map(|index| format!("i{}", index)).collect(),
+        outputs,
+    };
+    Ok((boundary, spec))
+}
@@ -0,0 +1,237 @@
+use crate::analysis::expression::*;
+use crate::analysis::minimize::*;
+use crate::types::gate_type::*;
+use crate::types::netlist::*;
+use std::collections::HashMap;
+
+pub struct OutputSpec {
+    pub name: String,
+    pub minterms: Vec<u32>,
+    pub dont_cares: Vec<u32>,
+}
+
+// a boolean function of named inputs, minterm bits follow the input order with the first as msb
+pub struct FunctionSpec {
+    pub inputs: Vec<String>,
+    pub outputs: Vec<OutputSpec>,
+}
+
+impl FunctionSpec {
+    // "(a & b) | !c" or "f = (a & b) | !c", the output is called f when not named
+    pub fn from_expression(text: &str) -> Result<FunctionSpec, String> {
+        let (name, body) = match text.split_once('=') {
+            Some((name, body)) if is_identifier(name.trim()) => (name.trim(), body),
+            Some(_) => return Err("expected 'name = expression'".to_string()),
+            None => ("f", text),
+        };
+
+        let (expr, inputs) = Expr::parse(body)?;
+        if inputs.iter().any(|input| input == name) {
+            return Err(format!("{} is both an input and the output", name));
+        }
+        let (_, minterms) = expr.minterms()?;
+
+        Ok(FunctionSpec {
+            inputs,
+            outputs: vec![OutputSpec {
+                name: name.to_string(),
+                minterms,
+                dont_cares: vec![],
+            }],
+        })
+    }
+
+    // a header naming the inputs and outputs split by '|', then one row per line:
+    //
+    //   a b c | f
+    //   0 0 1 | 1
+    //   1 - 0 | x
+    //
+    // cells are separated by spaces or commas, '-' / 'x' is a don't care (an input don't care
+    // stands for both values) and rows that aren't listed are 0
+    pub fn from_truth_table(text: &str) -> Result<FunctionSpec, String> {
+        let mut lines = text
+            .lines()
+            .map(|line| line.split('#').next().unwrap().trim())
+            .filter(|line| !line.is_empty());
+
+        let (inputs, outputs) = split_row(lines.next().ok_or("empty truth table")?)?;
+        if inputs.len() > MAX_SIMPLIFY_VARIABLES {
+            return Err(format!("at most {} inputs", MAX_SIMPLIFY_VARIABLES));
+        }
+        if outputs.is_empty() {
+            return Err("truth table has no outputs".to_string());
+        }
+        if let Some(name) = inputs.iter().chain(&outputs).find(|name| !is_identifier(name)) {
+            return Err(format!("'{}' isn't a valid name", name));
+        }
+
+        let mut values: Vec<HashMap<u32, Option<bool>>> = vec![HashMap::new(); outputs.len()];
+        for line in lines {
+            let (input_cells, output_cells) = split_row(line)?;
+            if input_cells.len() != inputs.len() || output_cells.len() != outputs.len() {
+                return Err(format!("wrong number of cells in '{}'", line));
+            }
+
+            let mut row_minterms = vec![0u32];
+            for cell in &input_cells {
+                row_minterms = match cell_value(cell)? {
+                    Some(bit) => row_minterms.iter().map(|m| m << 1 | bit as u32).collect(),
+                    None => row_minterms.iter().flat_map(|m| [m << 1, m << 1 | 1]).collect(),
+                };
+            }
+
+            for (index, cell) in output_cells.iter().enumerate() {
+                let value = cell_value(cell)?;
+                for minterm in &row_minterms {
+                    if let Some(previous) = values[index].insert(*minterm, value)
+                        && previous != value
+                    {
+                        return Err(format!("conflicting rows for {}", outputs[index]));
+                    }
+                }
+            }
+        }
+
+        let outputs = outputs
+            .into_iter()
+            .zip(values)
+            .map(|(name, values)| {
+                let mut minterms: Vec<u32> = values.iter().filter(|(_, v)| **v == Some(true)).map(|(m, _)| *m).collect();
+                let mut dont_cares: Vec<u32> = values.iter().filter(|(_, v)| v.is_none()).map(|(m, _)| *m).collect();
+                minterms.sort();
+                dont_cares.sort();
+                OutputSpec { name, minterms, dont_cares }
+            })
+            .collect();
+
+        Ok(FunctionSpec { inputs, outputs })
+    }
+
+    // minimized two level and / or logic, inverted inputs are shared between the outputs
+    pub fn synthesize(&self) -> Netlist {
+        let mut builder = NetlistBuilder::new();
+        let inputs: Vec<NetId> = self.inputs.iter().map(|name| builder.input(name)).collect();
+        let mut inverted: HashMap<usize, NetId> = HashMap::new();
+
+        for output in &self.outputs {
+            let mut products = vec![];
+            for implicant in minimize(&output.minterms, &output.dont_cares) {
+                let literals: Vec<NetId> = implicant
+                    .literals(self.inputs.len())
+                    .into_iter()
+                    .map(|(variable, positive)| match positive {
+                        true => inputs[variable],
+                        false => *inverted
+                            .entry(variable)
+                            .or_insert_with(|| builder.unary(GateType::NOT, inputs[variable])),
+                    })
+                    .collect();
+                products.push(builder.reduce(GateType::AND, &literals));
+            }
+            let sum = builder.reduce(GateType::OR, &products);
+            builder.output(&output.name, sum);
+        }
+
+        builder.build()
+    }
+}
+
+// cells left and right of the '|', separated by spaces or commas. test benches use the same rows
+pub fn split_row(line: &str) -> Result<(Vec<String>, Vec<String>), String> {
+    let (inputs, outputs) = line
+        .split_once('|')
+        .ok_or(format!("expected 'inputs | outputs', got '{}'", line))?;
+    let cells = |part: &str| -> Vec<String> {
+        part.split(|c: char| c.is_whitespace() || c == ',')
+            .filter(|cell| !cell.is_empty())
+            .map(String::from)
+            .collect()
+    };
+    Ok((cells(inputs), cells(outputs)))
+}
+
+pub fn cell_value(cell: &str) -> Result<Option<bool>, String> {
+    match cell {
+        "0" => Ok(Some(false)),
+        "1" => Ok(Some(true)),
+        "-" | "x" | "X" => Ok(None),
+        _ => Err(format!("bad cell '{}', expected 0, 1 or -", cell)),
+    }
+}
+
+fn is_identifier(text: &str) -> bool {
+    let mut chars = text.chars();
+    matches!(chars.next(), Some(c) if c.is_alphabetic() || c == '_')
+        && chars.all(|c| c.is_alphanumeric() || c == '_')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::truth_table::TruthTable;
+    use crate::layout;
+
+    // the synthesized circuit, rows keyed by input values in the spec's order
+    fn simulate(spec: &FunctionSpec) -> Vec<(Vec<bool>, Vec<bool>)> {
+        let table = TruthTable::generate(&layout::build(&spec.synthesize())).unwrap();
+        let inputs: Vec<usize> = spec.inputs.iter().map(|name| table.inputs.iter().position(|n| n == name).unwrap()).collect();
+        let outputs: Vec<usize> =
+            spec.outputs.iter().map(|output| table.outputs.iter().position(|n| *n == output.name).unwrap()).collect();
+        table
+            .rows
+            .iter()
+            .map(|row| {
+                assert!(row.settled);
+                (
+                    inputs.iter().map(|index| row.inputs[*index]).collect(),
+                    outputs.iter().map(|index| row.outputs[*index]).collect(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn expressions_synthesize_to_their_function() {
+        let text = "(a & b) | (!a & c) | (b ^ d)";
+        let (expr, names) = Expr::parse(text).unwrap();
+        let spec = FunctionSpec::from_expression(text).unwrap();
+        assert_eq!(spec.inputs, names);
+        let rows = simulate(&spec);
+        assert_eq!(rows.len(), 16);
+        for (inputs, outputs) in rows {
+            assert_eq!(outputs, [expr.eval(&inputs)], "{:?}", inputs);
+        }
+    }
+
+    #[test]
+    fn truth_tables_synthesize_to_their_rows() {
+        // a full adder, and an output that only matters when a = 1
+        let spec = FunctionSpec::from_truth_table(
+            "a b cin | sum cout half
+             0 0 0   | 0   0    -
+             0 0 1   | 1   0    -
+             0 1 0   | 1   0    -
+             0 1 1   | 0   1    -
+             1 0 0   | 1   0    0
+             1 0 1   | 0   1    1
+             1 1 0   | 0   1    1
+             1 1 1   | 1   1    1",
+        )
+        .unwrap();
+        for (inputs, outputs) in simulate(&spec) {
+            let total = inputs.iter().filter(|value| **value).count();
+            assert_eq!(outputs[0], total % 2 == 1, "{:?}", inputs);
+            assert_eq!(outputs[1], total >= 2, "{:?}", inputs);
+            if inputs[0] {
+                assert_eq!(outputs[2], inputs[1] || inputs[2], "{:?}", inputs);
+            }
+        }
+
+        // an input don't care stands for both values, rows left out are 0
+        let spec = FunctionSpec::from_truth_table("a b | y\n1 - | 1").unwrap();
+        for (inputs, outputs) in simulate(&spec) {
+            assert_eq!(outputs, [inputs[0]], "{:?}", inputs);
+        }
+    }
+}
@@ -0,0 +1,275 @@
+use crate::analysis::synthesis::{cell_value, split_row};
+use crate::analysis::truth_table::SETTLE_TICKS;
+use crate::types::circuit::*;
+use crate::types::gate_type::*;
+use crate::types::keys::*;
+
+// one row of a test bench, None is a '-' cell
+pub struct TestStep {
+    pub line: usize,
+    // None keeps the value from the previous step
+    pub inputs: Vec<Option<bool>>,
+    // None isn't checked
+    pub expected: Vec<Option<bool>>,
+    // run exactly this many ticks before checking, None settles the circuit first
+    pub ticks: Option<usize>,
+}
+
+pub enum TestItem {
+    Step(TestStep),
+    // ticks to run between two steps without checking anything
+    Ticks(usize),
+}
+
+// a header naming IN and OUT gates split by '|', then steps setting the inputs and listing the
+// expected outputs:
+//
+//   a b clk | q
+//   1 0 0   | -
+//   - - 1   | 1     # '-' keeps an input as it was and skips an output
+//   - - 0   | 1 @ 2 # check after exactly 2 ticks instead of waiting for the circuit to settle
+//   tick 10         # run 10 ticks without checking
+//
+// '#' starts a comment
+pub struct TestBench {
+    pub inputs: Vec<String>,
+    pub outputs: Vec<String>,
+    pub items: Vec<TestItem>,
+}
+
+pub struct StepFailure {
+    pub line: usize,
+    pub ticks: usize,
+    pub settled: bool,
+    // (output, expected, actual)
+    pub mismatches: Vec<(String, bool, bool)>,
+}
+
+pub struct TestReport {
+    pub steps: usize,
+    pub failures: Vec<StepFailure>,
+}
+
+impl TestReport {
+    pub fn passed(&self) -> bool {
+        self.failures.is_empty()
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut text = String::new();
+        for failure in &self.failures {
+            text += &format!("line {}: failed after {} ticks", failure.line, failure.ticks);
+            if !failure.settled {
+                text += " (did not settle)";
+            }
+            text += "\n";
+            for (name, expected, actual) in &failure.mismatches {
+                text += &format!("  {}: expected {}, got {}\n", name, *expected as u8, *actual as u8);
+            }
+        }
+        text += &format!(
+            "{} of {} steps passed\n",
+            self.steps - self.failures.len(),
+            self.steps
+        );
+        text
+    }
+}
+
+impl TestBench {
+    pub fn parse(text: &str) -> Result<TestBench, String> {
+        let mut lines = text
+            .lines()
+            .enumerate()
+            .map(|(index, line)| (index + 1, line.split('#').next().unwrap().trim()))
+            .filter(|(_, line)| !line.is_empty());
+
+        let (_, header) = lines.next().ok_or("empty test bench")?;
+        let (inputs, outputs) = split_row(header)?;
+        if outputs.is_empty() {
+            return Err("test bench has no outputs".to_string());
+        }
+
+        let mut items = vec![];
+        for (line_number, line) in lines {
+            let error = |message: String| format!("line {}: {}", line_number, message);
+
+            if let Some(count) = line.strip_prefix("tick ") {
+                let count = count.trim().parse().map_err(|_| error(format!("bad tick count '{}'", count)))?;
+                items.push(TestItem::Ticks(count));
+                continue;
+            }
+
+            let (row, ticks) = match line.split_once('@') {
+                Some((row, ticks)) => {
+                    let ticks = ticks.trim().parse().map_err(|_| error(format!("bad tick count '{}'", ticks)))?;
+                    (row, Some(ticks))
+                }
+                None => (line, None),
+            };
+            let (input_cells, output_cells) = split_row(row).map_err(error)?;
+            if input_cells.len() != inputs.len() || output_cells.len() != outputs.len() {
+                return Err(error("wrong number of cells".to_string()));
+            }
+
+            let values = |cells: Vec<String>| -> Result<Vec<Option<bool>>, String> {
+                cells.iter().map(|cell| cell_value(cell).map_err(error)).collect()
+            };
+            items.push(TestItem::Step(TestStep {
+                line: line_number,
+                inputs: values(input_cells)?,
+                expected: values(output_cells)?,
+                ticks,
+            }));
+        }
+
+        Ok(TestBench { inputs, outputs, items })
+    }
+
+    // runs the steps on a copy of the circuit starting from cleared wires, IN gates that
+    // aren't in the header keep their current value
+    pub fn run(&self, circuit: &Circuit) -> Result<TestReport, String> {
+        let inputs = find_gates(circuit, GateType::IN, &self.inputs)?;
+        let outputs = find_gates(circuit, GateType::OUT, &self.outputs)?;
+
+        let mut scratch = circuit.clone();
+        scratch.reset_wires();
+
+        let mut report = TestReport { steps: 0, failures: vec![] };
+        for item in &self.items {
+            let step = match item {
+                TestItem::Ticks(count) => {
+                    for _ in 0..*count {
+                        scratch.tick();
+                    }
+                    continue;
+                }
+                TestItem::Step(step) => step,
+            };
+
+            for (key, value) in inputs.iter().zip(&step.inputs) {
+                if let Some(value) = value {
+                    scratch.gates[*key].active = *value;
+                }
+            }
+
+            let (ticks, settled) = match step.ticks {
+                Some(count) => {
+                    for _ in 0..count {
+                        scratch.tick();
+                    }
+                    (count, true)
+                }
+                None => (scratch.settle(SETTLE_TICKS), scratch.emulation_done),
+            };
+
+            let mismatches: Vec<(String, bool, bool)> = outputs
+                .iter()
+                .zip(&self.outputs)
+                .zip(&step.expected)
+                .filter_map(|((key, name), expected)| {
+                    let actual = scratch.read_input(&scratch.gates[*key], 0);
+                    match expected {
+                        Some(expected) if *expected != actual => Some((name.clone(), *expected, actual)),
+                        _ => None,
+                    }
+                })
+                .collect();
+
+            report.steps += 1;
+            if !mismatches.is_empty() || !settled {
+                report.failures.push(StepFailure {
+                    line: step.line,
+                    ticks,
+                    settled,
+                    mismatches,
+                });
+            }
+        }
+
+        Ok(report)
+    }
+}
+
+fn find_gates(circuit: &Circuit, gate_type: GateType, names: &[String]) -> Result<Vec<GateKey>, String> {
+    let gates = circuit.io_names(gate_type.clone());
+    names
+        .iter()
+        .map(|name| {
+            gates
+                .iter()
+                .find(|(_, gate_name)| gate_name == name)
+                .map(|(key, _)| *key)
+                .ok_or(format!("no {} gate named {}", gate_type.text(), name))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::gate::*;
+    use crate::types::pin_type::*;
+    use macroquad::prelude::Rect;
+
+    // a flip-flop with d and clk inputs and its q as the output
+    fn register() -> Circuit {
+        let mut circuit = Circuit::new();
+        let add = |circuit: &mut Circuit, gate_type: GateType, x: f32, y: f32, label: &str| {
+            let mut gate = Gate::new(Rect::new(x * 64.0, y * 64.0, 64.0, 64.0), Rotation::Up, gate_type);
+            gate.label = Some(label.to_string());
+            circuit.gates.insert(gate)
+        };
+        let d = add(&mut circuit, GateType::IN, 0.0, 0.0, "d");
+        let clk = add(&mut circuit, GateType::IN, 0.0, 1.0, "clk");
+        let dff = add(&mut circuit, GateType::DFF, 1.0, 0.0, "r");
+        let q = add(&mut circuit, GateType::OUT, 2.0, 0.0, "q");
+        circuit.connect_wire(d, dff, 0, PinType::Output, 0, PinType::Input);
+        circuit.connect_wire(clk, dff, 0, PinType::Output, 1, PinType::Input);
+        circuit.connect_wire(dff, q, 0, PinType::Output, 0, PinType::Input);
+        circuit
+    }
+
+    #[test]
+    fn a_register_passes_its_bench() {
+        let bench = TestBench::parse(
+            "clk d | q
+             0 1   | 0
+             1 -   | 1   # stored on the rising edge
+             0 0   | 1   # and held after it
+             tick 5
+             1 -   | 0",
+        )
+        .unwrap();
+        let report = bench.run(&register()).unwrap();
+        assert!(report.passed(), "{}", report.to_text());
+        assert_eq!(report.steps, 4);
+    }
+
+    #[test]
+    fn failures_name_the_line_and_the_output() {
+        let bench = TestBench::parse(
+            "clk d | q
+             0 1   | 0
+             0 0   | 1   # no edge, q is still 0
+             1 -   | 0",
+        )
+        .unwrap();
+        let report = bench.run(&register()).unwrap();
+        assert!(!report.passed());
+        let [failure] = &report.failures[..] else {
+            panic!("{}", report.to_text());
+        };
+        assert_eq!(failure.line, 3);
+        assert!(failure.settled);
+        assert_eq!(failure.mismatches, [("q".to_string(), true, false)]);
+        assert!(report.to_text().contains("2 of 3 steps passed"), "{}", report.to_text());
+    }
+
+    #[test]
+    fn unknown_names_are_errors() {
+        let bench = TestBench::parse("clk e | q\n0 1 | 0").unwrap();
+        let error = bench.run(&register()).err().unwrap();
+        assert_eq!(error, "no in gate named e");
+    }
+}
@@ -0,0 +1,100 @@
+use crate::types::circuit::*;
+use crate::types::gate_type::*;
+
+// 2^16 rows is already more than anyone will read
+pub const MAX_INPUTS: usize = 16;
+pub const SETTLE_TICKS: usize = 1000;
+
+pub struct TruthRow {
+    pub inputs: Vec<bool>,
+    pub outputs: Vec<bool>,
+    // false if the outputs were still changing after SETTLE_TICKS (oscillating / sequential circuit)
+    pub settled: bool,
+}
+
+pub struct TruthTable {
+    pub inputs: Vec<String>,
+    pub outputs: Vec<String>,
+    pub rows: Vec<TruthRow>,
+}
+
+impl TruthTable {
+    // runs every combination of the IN gates on a copy of the circuit, first input is the msb
+    pub fn generate(circuit: &Circuit) -> Result<TruthTable, String> {
+        let inputs = circuit.io_names(GateType::IN);
+        let outputs = circuit.io_names(GateType::OUT);
+
+        if outputs.is_empty() {
+            return Err("circuit has no OUT gates".to_string());
+        }
+        if inputs.len() > MAX_INPUTS {
+            return Err(format!(
+                "{} inputs is too many for a truth table (max {})",
+                inputs.len(),
+                MAX_INPUTS
+            ));
+        }
+
+        let mut scratch = circuit.clone();
+        let mut rows = vec![];
+
+        for combination in 0..(1usize << inputs.len()) {
+            let values: Vec<bool> = (0..inputs.len())
+                .map(|index| combination >> (inputs.len() - 1 - index) & 1 == 1)
+                .collect();
+
+            for ((key, _), value) in inputs.iter().zip(&values) {
+                scratch.gates[*key].active = *value;
+            }
+            scratch.reset_wires();
+            scratch.settle(SETTLE_TICKS);
+
+            rows.push(TruthRow {
+                inputs: values,
+                outputs: outputs
+                    .iter()
+                    .map(|(key, _)| scratch.read_input(&scratch.gates[*key], 0))
+                    .collect(),
+                settled: scratch.emulation_done,
+            });
+        }
+
+        Ok(TruthTable {
+            inputs: inputs.into_iter().map(|(_, name)| name).collect(),
+            outputs: outputs.into_iter().map(|(_, name)| name).collect(),
+            rows,
+        })
+    }
+
+    pub fn header(&self) -> Vec<String> {
+        self.inputs.iter().chain(self.outputs.iter()).cloned().collect()
+    }
+
+    // unsettled outputs are written as 'x'
+    pub fn cells(&self, row: &TruthRow) -> Vec<String> {
+        let inputs = row.inputs.iter().map(|value| (*value as u8).to_string());
+        let outputs = row.outputs.iter().map(|value| match row.settled {
+            true => (*value as u8).to_string(),
+            false => "x".to_string(),
+        });
+        inputs.chain(outputs).collect()
+    }
+
+    pub fn to_csv(&self) -> String {
+        let mut csv = self.header().join(",") + "\n";
+        for row in &self.rows {
+            csv += &(self.cells(row).join(",") + "\n");
+        }
+        csv
+    }
+
+    pub fn to_markdown(&self) -> String {
+        let header = self.header();
+        let mut markdown = format!("| {} |\n", header.join(" | "));
+        markdown += &format!("|{}\n", "---|".repeat(header.len()));
+        for row in &self.rows {
+            markdown += &format!("| {} |\n", self.cells(row).join(" | "));
+        }
+        markdown
+    }
+}
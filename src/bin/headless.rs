@@ -0,0 +1,225 @@
+// runs a save file without opening a window, meant for CI scripts
+//
+// usage: headless <file.save> [--set NAME=0|1]... [--stimulus FILE] [--max-ticks N] [--json]
+//        headless <file.save> --equiv <other.save>
+//        headless <file.save> --bench <file.bench>
+//
+// a .v, .blif, yosys .json or logisim .circ file instead of a save is imported first.
+// IN / OUT gates go by their label, unlabeled ones by type and position sorted top-down then
+// left-right: in0, in1, ... / out0, out1, .... --set, --stimulus, --max-ticks and --json only
+// apply to a plain run, --bench takes --set and --stimulus as the starting inputs
+// exit status: 0 the circuit settled, 1 bad arguments or unreadable file, 2 tick limit reached,
+// 3 the circuits given to --equiv aren't equivalent or a --bench step failed
+
+use playground::analysis::equivalence::{Equivalence, check_files};
+use playground::analysis::test_bench::TestBench;
+use playground::formats;
+use playground::types::circuit::Circuit;
+use playground::types::gate_type::GateType;
+use serde::Serialize;
+use std::fs;
+use std::process::ExitCode;
+
+const DEFAULT_MAX_TICKS: usize = 1000;
+
+const USAGE: &str = "usage: headless <file.save> [--set NAME=0|1]... [--stimulus FILE] [--max-ticks N] [--json]
+       headless <file.save> --equiv <other.save>
+       headless <file.save> --bench <file.bench>";
+
+struct Args {
+    file_path: String,
+    assignments: Vec<(String, bool)>,
+    max_ticks: Option<usize>,
+    json: bool,
+    equiv: Option<String>,
+    bench: Option<String>,
+}
+
+#[derive(Serialize)]
+struct OutputValue {
+    name: String,
+    value: bool,
+}
+
+#[derive(Serialize)]
+struct Report {
+    settled: bool,
+    ticks: usize,
+    outputs: Vec<OutputValue>,
+}
+
+fn parse_assignment(text: &str) -> Result<(String, bool), String> {
+    let (name, value) = text
+        .split_once('=')
+        .ok_or(format!("expected NAME=VALUE, got '{}'", text))?;
+    let value = match value.trim() {
+        "0" | "false" => false,
+        "1" | "true" => true,
+        other => return Err(format!("bad value '{}' for {}, expected 0 or 1", other, name.trim())),
+    };
+    Ok((name.trim().to_string(), value))
+}
+
+// one NAME=VALUE per line, '#' starts a comment
+fn parse_stimulus(text: &str) -> Result<Vec<(String, bool)>, String> {
+    text.lines()
+        .map(|line| line.split('#').next().unwrap().trim())
+        .filter(|line| !line.is_empty())
+        .map(parse_assignment)
+        .collect()
+}
+
+fn parse_args() -> Result<Args, String> {
+    let mut args = Args {
+        file_path: String::new(),
+        assignments: vec![],
+        max_ticks: None,
+        json: false,
+        equiv: None,
+        bench: None,
+    };
+
+    let mut iter = std::env::args().skip(1);
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--set" => {
+                let value = iter.next().ok_or("--set needs NAME=VALUE")?;
+                args.assignments.push(parse_assignment(&value)?);
+            }
+            "--stimulus" => {
+                let path = iter.next().ok_or("--stimulus needs a file")?;
+                let text = fs::read_to_string(&path).map_err(|e| format!("{}: {}", path, e))?;
+                args.assignments.extend(parse_stimulus(&text)?);
+            }
+            "--max-ticks" => {
+                let value = iter.next().ok_or("--max-ticks needs a number")?;
+                args.max_ticks = Some(value.parse().map_err(|_| format!("bad tick limit '{}'", value))?);
+            }
+            "--json" => args.json = true,
+            "--bench" => args.bench = Some(iter.next().ok_or("--bench needs a file")?),
+            "--equiv" => args.equiv = Some(iter.next().ok_or("--equiv needs a file")?),
+            "-h" | "--help" => return Err(USAGE.to_string()),
+            _ if arg.starts_with("--") => return Err(format!("unknown option {}", arg)),
+            _ if args.file_path.is_empty() => args.file_path = arg,
+            _ => return Err(format!("unexpected argument {}", arg)),
+        }
+    }
+
+    if args.file_path.is_empty() {
+        return Err(USAGE.to_string());
+    }
+    // options that would otherwise be ignored without a word
+    let run_only = [("--max-ticks", args.max_ticks.is_some()), ("--json", args.json)];
+    if args.equiv.is_some() && args.bench.is_some() {
+        return Err("--equiv and --bench can't be combined".to_string());
+    }
+    if args.equiv.is_some()
+        && let Some((option, _)) = run_only.iter().find(|(_, given)| *given)
+    {
+        return Err(format!("{} can't be combined with --equiv", option));
+    }
+    if args.equiv.is_some() && !args.assignments.is_empty() {
+        return Err("--set and --stimulus can't be combined with --equiv".to_string());
+    }
+    if args.bench.is_some()
+        && let Some((option, _)) = run_only.iter().find(|(_, given)| *given)
+    {
+        return Err(format!("{} can't be combined with --bench", option));
+    }
+    Ok(args)
+}
+
+fn apply_inputs(circuit: &mut Circuit, assignments: &[(String, bool)]) -> Result<(), String> {
+    let inputs = circuit.io_names(GateType::IN);
+    for (name, value) in assignments {
+        let (key, _) = inputs
+            .iter()
+            .find(|(_, input_name)| input_name == name)
+            .ok_or(format!("no IN gate named {}", name))?;
+        circuit.gates[*key].active = *value;
+    }
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    let args = match parse_args() {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("{}", e);
+            return ExitCode::from(1);
+        }
+    };
+
+    if let Some(other) = &args.equiv {
+        return match check_files(&args.file_path, other) {
+            Ok(result) => {
+                print!("{}", result.to_text());
+                match result {
+                    Equivalence::Equivalent => ExitCode::SUCCESS,
+                    Equivalence::Different(_) => ExitCode::from(3),
+                }
+            }
+            Err(e) => {
+                eprintln!("{}", e);
+                ExitCode::from(1)
+            }
+        };
+    }
+
+    let mut circuit = match formats::load(&args.file_path) {
+        Ok(circuit) => circuit,
+        Err(e) => {
+            eprintln!("Error loading: {}", e);
+            return ExitCode::from(1);
+        }
+    };
+
+    if let Err(e) = apply_inputs(&mut circuit, &args.assignments) {
+        eprintln!("{}", e);
+        return ExitCode::from(1);
+    }
+
+    if let Some(path) = &args.bench {
+        let result = fs::read_to_string(path)
+            .map_err(|e| format!("{}: {}", path, e))
+            .and_then(|text| TestBench::parse(&text))
+            .and_then(|bench| bench.run(&circuit));
+        return match result {
+            Ok(report) => {
+                print!("{}", report.to_text());
+                if report.passed() { ExitCode::SUCCESS } else { ExitCode::from(3) }
+            }
+            Err(e) => {
+                eprintln!("{}", e);
+                ExitCode::from(1)
+            }
+        };
+    }
+
+    let ticks = circuit.settle(args.max_ticks.unwrap_or(DEFAULT_MAX_TICKS));
+    let report = Report {
+        settled: circuit.emulation_done,
+        ticks,
+        outputs: circuit
+            .io_names(GateType::OUT)
+            .into_iter()
+            .map(|(key, name)| OutputValue {
+                name,
+                value: circuit.read_input(&circuit.gates[key], 0),
+            })
+            .collect(),
+    };
+
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&report).unwrap());
+    } else {
+        for output in &report.outputs {
+            println!("{}={}", output.name, output.value as u8);
+        }
+        if !report.settled {
+            eprintln!("did not settle after {} ticks", report.ticks);
+        }
+    }
+
+    if report.settled { ExitCode::SUCCESS } else { ExitCode::from(2) }
+}
@@ -0,0 +1,370 @@
+use crate::types::circuit::*;
+use crate::types::gate_type::*;
+use crate::types::keys::*;
+use std::collections::HashMap;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Edge {
+    Rise,
+    Fall,
+    Change,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Compare {
+    Equal,
+    NotEqual,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+}
+
+// bits read as a number, a single signal is bit 0. signals are kept by name and looked up on
+// every check, so rewiring or renaming doesn't leave them watching some other wire. a name that
+// doesn't resolve anymore reads false like an unconnected pin
+#[derive(Clone, Debug)]
+pub enum Operand {
+    Signals(Vec<(u32, String)>),
+    // name0, name1 ... as found when checking
+    Bus(String),
+    Constant(u64),
+}
+
+#[derive(Clone, Debug)]
+pub enum Condition {
+    Edge(Edge, String),
+    Compare(Operand, Compare, Operand),
+    And(Box<Condition>, Box<Condition>),
+    Or(Box<Condition>, Box<Condition>),
+}
+
+// pauses emulation when its condition goes from false to true, conditions with an edge in them
+// fire on every tick they're true
+//
+//   rise clk / fall clk / change clk    edges, also written 'clk rises' ...
+//   bus == 0x3F                         bus0, bus1 ... (or bus[0] ...) read as a number, bit 0 first
+//   OUT a != OUT b                      IN / OUT / probe pick where a name is looked up
+//   {a, b, c} >= 5                      wires listed msb first
+//   rise clk && en == 1                 && binds tighter than ||
+//
+// plain names are looked up in the probes, then the IN and then the OUT gates
+pub struct Breakpoint {
+    pub text: String,
+    condition: Condition,
+    was_true: bool,
+    // value of each edge signal after the last check
+    previous: HashMap<String, bool>,
+}
+
+impl Breakpoint {
+    pub fn parse(text: &str, circuit: &Circuit) -> Result<Breakpoint, String> {
+        Ok(Breakpoint {
+            text: text.trim().to_string(),
+            condition: parse_or(text, circuit)?,
+            was_true: false,
+            previous: HashMap::new(),
+        })
+    }
+
+    // call after every tick, returns the wires that made the condition true when it fires
+    pub fn check(&mut self, circuit: &Circuit) -> Option<Vec<WireKey>> {
+        let mut wires = vec![];
+        let is_true = evaluate(&self.condition, circuit, &self.previous, &mut wires);
+
+        self.previous.clear();
+        collect_edges(&self.condition, &mut |signal| {
+            self.previous.insert(signal.to_string(), read(circuit, lookup(signal, circuit)));
+        });
+
+        let fired = is_true && (!self.was_true || has_edge(&self.condition));
+        self.was_true = is_true;
+        fired.then_some(wires)
+    }
+}
+
+fn read(circuit: &Circuit, wire: Option<WireKey>) -> bool {
+    wire.and_then(|wire| circuit.wires_read.get(wire)).copied().unwrap_or(false)
+}
+
+fn lookup(text: &str, circuit: &Circuit) -> Option<WireKey> {
+    resolve(text, circuit).ok().flatten()
+}
+
+fn bits(operand: &Operand, circuit: &Circuit) -> Vec<(u32, Option<WireKey>)> {
+    match operand {
+        Operand::Constant(_) => vec![],
+        Operand::Signals(signals) => signals.iter().map(|(bit, signal)| (*bit, lookup(signal, circuit))).collect(),
+        Operand::Bus(name) => bus(name, circuit),
+    }
+}
+
+fn value(operand: &Operand, circuit: &Circuit) -> u64 {
+    match operand {
+        Operand::Constant(value) => *value,
+        _ => bits(operand, circuit)
+            .iter()
+            .filter(|(_, wire)| read(circuit, *wire))
+            .fold(0, |value, (bit, _)| value | 1 << bit),
+    }
+}
+
+// wires of the parts that are true go into triggering
+fn evaluate(
+    condition: &Condition,
+    circuit: &Circuit,
+    previous: &HashMap<String, bool>,
+    triggering: &mut Vec<WireKey>,
+) -> bool {
+    match condition {
+        Condition::Edge(edge, signal) => {
+            let Some(wire) = lookup(signal, circuit) else {
+                return false;
+            };
+            let Some(before) = previous.get(signal) else {
+                return false;
+            };
+            let now = read(circuit, Some(wire));
+            let fired = match edge {
+                Edge::Rise => !before && now,
+                Edge::Fall => *before && !now,
+                Edge::Change => *before != now,
+            };
+            if fired {
+                triggering.push(wire);
+            }
+            fired
+        }
+        Condition::Compare(a, compare, b) => {
+            let (a_value, b_value) = (value(a, circuit), value(b, circuit));
+            let is_true = match compare {
+                Compare::Equal => a_value == b_value,
+                Compare::NotEqual => a_value != b_value,
+                Compare::Less => a_value < b_value,
+                Compare::LessEqual => a_value <= b_value,
+                Compare::Greater => a_value > b_value,
+                Compare::GreaterEqual => a_value >= b_value,
+            };
+            if is_true {
+                for operand in [a, b] {
+                    triggering.extend(bits(operand, circuit).into_iter().filter_map(|(_, wire)| wire));
+                }
+            }
+            is_true
+        }
+        Condition::And(a, b) => {
+            let mut wires = vec![];
+            let is_true = evaluate(a, circuit, previous, &mut wires) && evaluate(b, circuit, previous, &mut wires);
+            if is_true {
+                triggering.extend(wires);
+            }
+            is_true
+        }
+        Condition::Or(a, b) => {
+            // both sides run so every true part is highlighted
+            let a_true = evaluate(a, circuit, previous, triggering);
+            let b_true = evaluate(b, circuit, previous, triggering);
+            a_true || b_true
+        }
+    }
+}
+
+fn has_edge(condition: &Condition) -> bool {
+    match condition {
+        Condition::Edge(..) => true,
+        Condition::Compare(..) => false,
+        Condition::And(a, b) | Condition::Or(a, b) => has_edge(a) || has_edge(b),
+    }
+}
+
+fn collect_edges(condition: &Condition, found: &mut impl FnMut(&str)) {
+    match condition {
+        Condition::Edge(_, signal) => found(signal),
+        Condition::Compare(..) => {}
+        Condition::And(a, b) | Condition::Or(a, b) => {
+            collect_edges(a, found);
+            collect_edges(b, found);
+        }
+    }
+}
+
+fn parse_or(text: &str, circuit: &Circuit) -> Result<Condition, String> {
+    let mut parts = text.split("||").map(|part| parse_and(part, circuit));
+    let first = parts.next().unwrap()?;
+    parts.try_fold(first, |a, b| Ok(Condition::Or(Box::new(a), Box::new(b?))))
+}
+
+fn parse_and(text: &str, circuit: &Circuit) -> Result<Condition, String> {
+    let mut parts = text.split("&&").map(|part| parse_term(part, circuit));
+    let first = parts.next().unwrap()?;
+    parts.try_fold(first, |a, b| Ok(Condition::And(Box::new(a), Box::new(b?))))
+}
+
+fn parse_term(text: &str, circuit: &Circuit) -> Result<Condition, String> {
+    let text = text.trim();
+    if text.is_empty() {
+        return Err("empty condition".to_string());
+    }
+
+    let edges = [("rise", Edge::Rise), ("fall", Edge::Fall), ("change", Edge::Change)];
+    for (word, edge) in edges {
+        let signal = text
+            .strip_prefix(word)
+            .filter(|rest| rest.starts_with(char::is_whitespace))
+            .or(text.strip_suffix(&format!("{}s", word)));
+        if let Some(signal) = signal {
+            resolve(signal.trim(), circuit)?;
+            return Ok(Condition::Edge(edge, signal.trim().to_string()));
+        }
+    }
+
+    let compares = [
+        ("==", Compare::Equal),
+        ("!=", Compare::NotEqual),
+        ("<=", Compare::LessEqual),
+        (">=", Compare::GreaterEqual),
+        ("<", Compare::Less),
+        (">", Compare::Greater),
+    ];
+    for (symbol, compare) in compares {
+        if let Some((a, b)) = text.split_once(symbol) {
+            return Ok(Condition::Compare(parse_operand(a, circuit)?, compare, parse_operand(b, circuit)?));
+        }
+    }
+
+    // a lone signal breaks when it's high
+    Ok(Condition::Compare(parse_operand(text, circuit)?, Compare::Equal, Operand::Constant(1)))
+}
+
+fn parse_operand(text: &str, circuit: &Circuit) -> Result<Operand, String> {
+    let text = text.trim();
+    if text.is_empty() {
+        return Err("missing operand".to_string());
+    }
+
+    if text.starts_with(|c: char| c.is_ascii_digit()) {
+        let parsed = if let Some(hex) = text.strip_prefix("0x") {
+            u64::from_str_radix(hex, 16)
+        } else if let Some(binary) = text.strip_prefix("0b") {
+            u64::from_str_radix(binary, 2)
+        } else {
+            text.parse()
+        };
+        return parsed.map(Operand::Constant).map_err(|_| format!("bad number '{}'", text));
+    }
+
+    if let Some(list) = text.strip_prefix('{').and_then(|rest| rest.strip_suffix('}')) {
+        let names: Vec<&str> = list.split(',').map(str::trim).collect();
+        let count = names.len() as u32;
+        return names
+            .iter()
+            .enumerate()
+            .map(|(index, name)| {
+                resolve(name, circuit)?;
+                Ok((count - 1 - index as u32, name.to_string()))
+            })
+            .collect::<Result<_, String>>()
+            .map(Operand::Signals);
+    }
+
+    match resolve(text, circuit) {
+        Ok(_) => Ok(Operand::Signals(vec![(0, text.to_string())])),
+        Err(e) => match bus(text, circuit).is_empty() {
+            true => Err(e),
+            false => Ok(Operand::Bus(text.to_string())),
+        },
+    }
+}
+
+// every signal the name can refer to, in lookup order
+fn signals(circuit: &Circuit, kind: Option<&str>) -> Vec<(String, Option<WireKey>)> {
+    let mut signals = vec![];
+    if kind.is_none() || kind == Some("probe") {
+        signals.extend(circuit.probes.iter().map(|probe| (probe.name.clone(), Some(probe.wire))));
+    }
+    if kind.is_none() || kind == Some("IN") {
+        for (key, name) in circuit.io_names(GateType::IN) {
+            signals.push((name, circuit.gates[key].output[0].wire_index));
+        }
+    }
+    if kind.is_none() || kind == Some("OUT") {
+        for (key, name) in circuit.io_names(GateType::OUT) {
+            signals.push((name, circuit.gates[key].input[0].wire_index));
+        }
+    }
+    signals
+}
+
+fn split_kind(text: &str) -> (Option<&str>, &str) {
+    for kind in ["IN", "OUT", "probe"] {
+        if let Some(name) = text.strip_prefix(kind)
+            && name.starts_with(char::is_whitespace)
+        {
+            return (Some(kind), name.trim());
+        }
+    }
+    (None, text)
+}
+
+fn resolve(text: &str, circuit: &Circuit) -> Result<Option<WireKey>, String> {
+    let (kind, name) = split_kind(text);
+    signals(circuit, kind)
+        .into_iter()
+        .find(|(signal, _)| signal == name)
+        .map(|(_, wire)| wire)
+        .ok_or(format!("no probe, IN or OUT gate named '{}'", name))
+}
+
+// name0, name1 ... or name[0], name[1] ..., the number is the bit
+fn bus(text: &str, circuit: &Circuit) -> Vec<(u32, Option<WireKey>)> {
+    let (kind, name) = split_kind(text);
+    let mut bits: Vec<(u32, Option<WireKey>)> = vec![];
+    for (signal, wire) in signals(circuit, kind) {
+        let Some(rest) = signal.strip_prefix(name) else {
+            continue;
+        };
+        let index = rest.strip_prefix('[').and_then(|rest| rest.strip_suffix(']')).unwrap_or(rest);
+        if let Ok(bit) = index.parse::<u32>()
+            && bit < 64
+            && !bits.iter().any(|(existing, _)| *existing == bit)
+        {
+            bits.push((bit, wire));
+        }
+    }
+    bits
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::gate::*;
+    use crate::types::pin_type::*;
+    use macroquad::prelude::Rect;
+
+    #[test]
+    fn names_follow_rewiring() {
+        let mut circuit = Circuit::new();
+        let mut add = |gate_type: GateType, label: &str, y: f32| {
+            let mut gate = Gate::new(Rect::new(0.0, y * 64.0, 64.0, 64.0), Rotation::Up, gate_type);
+            gate.label = Some(label.to_string());
+            circuit.gates.insert(gate)
+        };
+        let a = add(GateType::IN, "a", 0.0);
+        let y = add(GateType::OUT, "y", 1.0);
+        circuit.connect_wire(a, y, 0, PinType::Output, 0, PinType::Input);
+        circuit.init_wire_buffers();
+        let mut breakpoint = Breakpoint::parse("rise a", &circuit).unwrap();
+        assert!(breakpoint.check(&circuit).is_none());
+
+        // the wire is replaced by a new one with a different key
+        let old = circuit.gates[a].output[0].wire_index.unwrap();
+        circuit.remove_wire(old);
+        circuit.connect_wire(a, y, 0, PinType::Output, 0, PinType::Input);
+        circuit.init_wire_buffers();
+        let new = circuit.gates[a].output[0].wire_index.unwrap();
+        assert_ne!(old, new);
+        circuit.gates[a].active = true;
+        circuit.tick();
+        assert_eq!(breakpoint.check(&circuit), Some(vec![new]));
+    }
+}
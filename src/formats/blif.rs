@@ -0,0 +1,472 @@
+use crate::formats::Names;
+use crate::layout;
+use crate::types::circuit::*;
+use crate::types::gate_type::*;
+use crate::types::keys::*;
+use crate::types::netlist::*;
+use std::collections::{HashMap, HashSet};
+
+// .names covers of the primitive gates, inputs in pin order then the output column
+fn cover(gate_type: &GateType) -> Option<&'static str> {
+    match gate_type {
+        GateType::NOT => Some("0 1\n"),
+        GateType::OR => Some("1- 1\n-1 1\n"),
+        GateType::XOR => Some("01 1\n10 1\n"),
+        GateType::NOR => Some("00 1\n"),
+        GateType::XNOR => Some("00 1\n11 1\n"),
+        GateType::AND => Some("11 1\n"),
+        GateType::NAND => Some("0- 1\n-0 1\n"),
+        _ => None,
+    }
+}
+
+// the functions above as truth tables over (a, b) = 00, 01, 10, 11
+const TWO_INPUT_GATES: &[(GateType, [bool; 4])] = &[
+    (GateType::AND, [false, false, false, true]),
+    (GateType::OR, [false, true, true, true]),
+    (GateType::XOR, [false, true, true, false]),
+    (GateType::NAND, [true, true, true, false]),
+    (GateType::NOR, [true, false, false, false]),
+    (GateType::XNOR, [true, false, false, true]),
+];
+
+// nets of the model, wires get a name the first time they're used
+struct Nets {
+    names: Names,
+    nets: HashMap<WireKey, String>,
+    zero: Option<String>,
+}
+
+impl Nets {
+    fn net(&mut self, wire: Option<WireKey>) -> String {
+        let Some(wire) = wire else {
+            return self.names.unique("nc");
+        };
+        if let Some(name) = self.nets.get(&wire) {
+            return name.clone();
+        }
+        let name = self.names.unique(&format!("w{}", self.nets.len()));
+        self.nets.insert(wire, name.clone());
+        name
+    }
+
+    // an unconnected input reads 0 like in the simulator, from a table without rows
+    fn input(&mut self, wire: Option<WireKey>) -> String {
+        match wire {
+            Some(_) => self.net(wire),
+            None => self.zero.get_or_insert_with(|| self.names.unique("zero")).clone(),
+        }
+    }
+}
+
+// a flat blif model. IN / OUT gates are the .inputs / .outputs, gates become .names tables and
+// flip-flops .latch lines clocked on the rising edge. custom gates are only a grouping of the
+// gates inside them, those are written like any other gate
+pub fn export(circuit: &Circuit, model_name: &str) -> String {
+    let mut nets = Nets {
+        names: Names::words(&[]),
+        nets: HashMap::new(),
+        zero: None,
+    };
+    let mut tables = String::new();
+
+    let mut inputs = vec![];
+    for (key, name) in circuit.io_names(GateType::IN) {
+        let name = nets.names.unique(&name);
+        if let Some(wire) = circuit.gates[key].output[0].wire_index {
+            nets.nets.insert(wire, name.clone());
+        }
+        inputs.push(name);
+    }
+    // an output names the net it reads unless that already has a name, then it gets a buffer
+    let mut outputs = vec![];
+    let mut buffers = vec![];
+    for (key, name) in circuit.io_names(GateType::OUT) {
+        let name = nets.names.unique(&name);
+        match circuit.gates[key].input[0].wire_index {
+            Some(wire) if !nets.nets.contains_key(&wire) => {
+                nets.nets.insert(wire, name.clone());
+            }
+            Some(wire) => buffers.push((wire, name.clone())),
+            None => tables += &format!(".names {}\n", name),
+        }
+        outputs.push(name);
+    }
+
+    let mut latches = String::new();
+    for (_, gate) in &circuit.gates {
+        if gate.gate_type == GateType::DFF {
+            let d = nets.input(gate.input[0].wire_index);
+            let clock = nets.input(gate.input[1].wire_index);
+            let q = nets.net(gate.output[0].wire_index);
+            latches += &format!(".latch {} {} re {} {}\n", d, q, clock, gate.active as u8);
+            continue;
+        }
+        let Some(cover) = cover(&gate.gate_type) else {
+            continue;
+        };
+        let mut terminals: Vec<String> = gate.input.iter().map(|pin| nets.input(pin.wire_index)).collect();
+        terminals.push(nets.net(gate.output[0].wire_index));
+        tables += &format!(".names {}\n{}", terminals.join(" "), cover);
+    }
+    for (wire, name) in buffers {
+        tables += &format!(".names {} {}\n1 1\n", nets.net(Some(wire)), name);
+    }
+    if let Some(zero) = &nets.zero {
+        tables += &format!(".names {}\n", zero);
+    }
+
+    let mut text = format!(".model {}\n", Names::words(&[]).unique(model_name));
+    if !inputs.is_empty() {
+        text += &format!(".inputs {}\n", inputs.join(" "));
+    }
+    if !outputs.is_empty() {
+        text += &format!(".outputs {}\n", outputs.join(" "));
+    }
+    text + &tables + &latches + ".end\n"
+}
+
+pub fn import(text: &str) -> Result<Circuit, String> {
+    Ok(layout::build(&parse(text)?))
+}
+
+struct Table {
+    inputs: Vec<String>,
+    output: String,
+    // input cube and output value
+    rows: Vec<(String, bool)>,
+    line: usize,
+}
+
+impl Table {
+    // the function over every input combination, the first input is the most significant bit
+    fn truth_table(&self) -> Vec<bool> {
+        let count = self.inputs.len();
+        (0..1usize << count)
+            .map(|combination| {
+                let matches = self.rows.iter().any(|(cube, _)| {
+                    cube.chars().enumerate().all(|(index, c)| {
+                        let value = combination >> (count - 1 - index) & 1 == 1;
+                        c == '-' || (c == '1') == value
+                    })
+                });
+                // rows either all list where the output is 1 or all where it's 0
+                match self.rows.first() {
+                    Some((_, true)) => matches,
+                    Some((_, false)) => !matches,
+                    None => false,
+                }
+            })
+            .collect()
+    }
+
+    fn is_buffer(&self) -> bool {
+        self.inputs.len() == 1 && self.truth_table() == [false, true]
+    }
+}
+
+struct Latch {
+    input: String,
+    output: String,
+    // re or fe, None for a latch on the global clock
+    edge: Option<String>,
+    control: Option<String>,
+    init: bool,
+    line: usize,
+}
+
+// the first model of a flat blif file, latches on the global clock share an IN gate called clock
+pub fn parse(text: &str) -> Result<Netlist, String> {
+    let mut inputs: Vec<String> = vec![];
+    let mut outputs: Vec<String> = vec![];
+    let mut tables: Vec<Table> = vec![];
+    let mut latches: Vec<Latch> = vec![];
+
+    // lines ending in '\' go on on the next one
+    let mut lines: Vec<(usize, String)> = vec![];
+    let mut pending: Option<(usize, String)> = None;
+    for (index, raw) in text.lines().enumerate() {
+        let content = raw.split('#').next().unwrap_or("").trim_end();
+        let (line, mut joined) = pending.take().unwrap_or((index + 1, String::new()));
+        joined += " ";
+        match content.strip_suffix('\\') {
+            Some(start) => {
+                joined += start;
+                pending = Some((line, joined));
+            }
+            None => {
+                joined += content;
+                lines.push((line, joined));
+            }
+        }
+    }
+    lines.extend(pending);
+
+    let mut table: Option<Table> = None;
+    let mut models = 0;
+    for (line, content) in lines {
+        let words: Vec<&str> = content.split_whitespace().collect();
+        let Some(first) = words.first() else {
+            continue;
+        };
+        let error = |message: &str| format!("line {}: {}", line, message);
+
+        if !first.starts_with('.') {
+            let Some(table) = table.as_mut() else {
+                return Err(error("cover row outside of a .names table"));
+            };
+            let (cube, value) = match words.as_slice() {
+                [value] if table.inputs.is_empty() => ("", *value),
+                [cube, value] if cube.len() == table.inputs.len() => (*cube, *value),
+                _ => return Err(error(&format!("expected {} inputs and an output", table.inputs.len()))),
+            };
+            if !cube.chars().all(|c| matches!(c, '0' | '1' | '-')) || !matches!(value, "0" | "1") {
+                return Err(error("cover rows use 0, 1 and -"));
+            }
+            let value = value == "1";
+            if table.rows.first().is_some_and(|(_, first)| *first != value) {
+                return Err(error("every row of a table needs the same output value"));
+            }
+            table.rows.push((cube.to_string(), value));
+            continue;
+        }
+
+        tables.extend(table.take());
+        match *first {
+            ".model" => {
+                models += 1;
+                if models > 1 {
+                    break;
+                }
+            }
+            ".inputs" => inputs.extend(words[1..].iter().map(|word| word.to_string())),
+            ".outputs" => outputs.extend(words[1..].iter().map(|word| word.to_string())),
+            ".names" => {
+                let Some((output, names)) = words[1..].split_last() else {
+                    return Err(error(".names needs an output"));
+                };
+                table = Some(Table {
+                    inputs: names.iter().map(|name| name.to_string()).collect(),
+                    output: output.to_string(),
+                    rows: vec![],
+                    line,
+                });
+            }
+            ".latch" => {
+                let (input, output, edge, control, init) = match words[1..] {
+                    [input, output] => (input, output, None, None, "3"),
+                    [input, output, init] => (input, output, None, None, init),
+                    [input, output, edge, control] => (input, output, Some(edge), Some(control), "3"),
+                    [input, output, edge, control, init] => (input, output, Some(edge), Some(control), init),
+                    _ => return Err(error(".latch needs an input and an output")),
+                };
+                if let Some(edge) = edge
+                    && !matches!(edge, "re" | "fe")
+                {
+                    return Err(error(&format!("{} latches aren't supported, only re and fe", edge)));
+                }
+                latches.push(Latch {
+                    input: input.to_string(),
+                    output: output.to_string(),
+                    edge: edge.map(|edge| edge.to_string()),
+                    control: control.filter(|control| *control != "NIL").map(|control| control.to_string()),
+                    // 2 (don't care) and 3 (unknown) start cleared like a new flip-flop
+                    init: init == "1",
+                    line,
+                });
+            }
+            ".end" | ".exdc" if models > 0 => break,
+            ".subckt" | ".gate" | ".mlatch" | ".search" | ".start_kiss" => {
+                return Err(error(&format!("{} isn't supported, only flat models", first)));
+            }
+            // clocks and timing don't change the logic
+            _ => {}
+        }
+    }
+    tables.extend(table);
+
+    // buffers merge their output net into their input net
+    let aliases: HashMap<String, String> = tables
+        .iter()
+        .filter(|table| table.is_buffer())
+        .map(|table| (table.output.clone(), table.inputs[0].clone()))
+        .collect();
+    let root = |name: &str| -> String {
+        let mut name = name.to_string();
+        for _ in 0..=aliases.len() {
+            match aliases.get(&name) {
+                Some(next) => name = next.clone(),
+                None => break,
+            }
+        }
+        name
+    };
+
+    let mut builder = NetlistBuilder::new();
+    let mut driven: HashSet<String> = HashSet::new();
+    let mut drive = |name: &str, line: usize| -> Result<(), String> {
+        match driven.insert(root(name)) {
+            true => Ok(()),
+            false => Err(format!("line {}: {} has more than one driver", line, name)),
+        }
+    };
+
+    for input in &inputs {
+        drive(input, 1)?;
+        let net = builder.named_net(&root(input));
+        builder.gate(GateType::IN, Some(input.clone()), &[], &[net]);
+    }
+
+    let mut inverted: HashMap<NetId, NetId> = HashMap::new();
+    for table in tables.iter().filter(|table| !table.is_buffer()) {
+        drive(&table.output, table.line)?;
+        let output = builder.named_net(&root(&table.output));
+        let pins: Vec<NetId> = table.inputs.iter().map(|input| builder.named_net(&root(input))).collect();
+        // only a table small enough for a single gate is expanded, a wide one would take 2^inputs
+        let truth_table = match pins.len() <= 2 {
+            true => table.truth_table(),
+            false => vec![],
+        };
+
+        if let [a] = pins.as_slice()
+            && truth_table == [true, false]
+        {
+            builder.gate(GateType::NOT, None, &[Some(*a)], &[output]);
+            continue;
+        }
+        if let [a, b] = pins.as_slice()
+            && let Some((gate_type, _)) = TWO_INPUT_GATES.iter().find(|(_, table)| truth_table == table)
+        {
+            builder.gate(gate_type.clone(), None, &[Some(*a), Some(*b)], &[output]);
+            continue;
+        }
+
+        // anything else as a sum of products, an off-set cover is inverted at the end
+        let Some((_, on_set)) = table.rows.first() else {
+            continue;
+        };
+        let mut cubes = vec![];
+        for (cube, _) in &table.rows {
+            let mut literals = vec![];
+            for (c, pin) in cube.chars().zip(&pins) {
+                match c {
+                    '1' => literals.push(*pin),
+                    '0' => literals.push(*inverted.entry(*pin).or_insert_with(|| builder.unary(GateType::NOT, *pin))),
+                    _ => {}
+                }
+            }
+            cubes.push(literals);
+        }
+        match (cubes.as_slice(), on_set) {
+            ([literals], true) => builder.reduce_into(GateType::AND, literals, output),
+            ([literals], false) => builder.reduce_into(GateType::NAND, literals, output),
+            _ => {
+                let products: Vec<NetId> = cubes.iter().map(|literals| builder.reduce(GateType::AND, literals)).collect();
+                let gate_type = if *on_set { GateType::OR } else { GateType::NOR };
+                builder.reduce_into(gate_type, &products, output);
+            }
+        }
+    }
+
+    let mut global_clock = None;
+    for latch in &latches {
+        drive(&latch.output, latch.line)?;
+        let clock = match &latch.control {
+            Some(control) => builder.named_net(&root(control)),
+            None => match global_clock {
+                Some(net) => net,
+                None => {
+                    let net = builder.named_net(&root("clock"));
+                    if !inputs.iter().any(|input| input == "clock") {
+                        drive("clock", latch.line)?;
+                        builder.gate(GateType::IN, Some("clock".to_string()), &[], &[net]);
+                    }
+                    *global_clock.insert(net)
+                }
+            },
+        };
+        let clock = match latch.edge.as_deref() {
+            Some("fe") => builder.unary(GateType::NOT, clock),
+            _ => clock,
+        };
+        let d = builder.named_net(&root(&latch.input));
+        let q = builder.named_net(&root(&latch.output));
+        let node = builder.gate(GateType::DFF, None, &[Some(d), Some(clock)], &[q]);
+        builder.set_active(node, latch.init);
+    }
+
+    for output in &outputs {
+        let net = builder.named_net(&root(output));
+        builder.output(output, net);
+    }
+    Ok(builder.build())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::equivalence::{self, Equivalence, Matching};
+
+    fn output(circuit: &mut Circuit, inputs: &[bool]) -> bool {
+        for ((key, _), value) in circuit.io_names(GateType::IN).into_iter().zip(inputs) {
+            circuit.gates[key].active = *value;
+        }
+        circuit.reset_wires();
+        circuit.settle(1000);
+        let (key, _) = circuit.io_names(GateType::OUT)[0];
+        circuit.read_input(&circuit.gates[key], 0)
+    }
+
+    #[test]
+    fn wide_tables_skip_the_truth_table() {
+        let names: Vec<String> = (0..64).map(|index| format!("i{}", index)).collect();
+        let text = format!(
+            ".model wide\n.inputs {0}\n.outputs y\n.names {0} y\n{1} 1\n.end\n",
+            names.join(" "),
+            "1".repeat(64)
+        );
+        let mut circuit = import(&text).unwrap();
+        assert!(output(&mut circuit, &[true; 64]));
+        let mut inputs = [true; 64];
+        inputs[40] = false;
+        assert!(!output(&mut circuit, &inputs));
+    }
+
+    const MAJORITY: &str = ".model majority\n.inputs a b c\n.outputs y\n.names a b c y\n11- 1\n1-1 1\n-11 1\n.end\n";
+
+    #[test]
+    fn covers_with_dont_cares_import_as_their_function() {
+        let mut circuit = import(MAJORITY).unwrap();
+        for combination in 0..8u8 {
+            let inputs: Vec<bool> = (0..3).map(|bit| combination >> (2 - bit) & 1 == 1).collect();
+            assert_eq!(output(&mut circuit, &inputs), combination.count_ones() >= 2, "{:03b}", combination);
+        }
+    }
+
+    #[test]
+    fn exported_circuits_import_as_the_same_function() {
+        let circuit = import(MAJORITY).unwrap();
+        let text = export(&circuit, "majority");
+        let again = import(&text).unwrap();
+        assert!(matches!(equivalence::check(&circuit, &again, Matching::Names), Ok(Equivalence::Equivalent)), "{}", text);
+    }
+
+    #[test]
+    fn latches_toggle_on_the_rising_edge() {
+        let text = ".model toggle\n.inputs clk\n.outputs q\n.names q t\n0 1\n.latch t q re clk 0\n.end\n";
+        let exported = export(&import(text).unwrap(), "toggle");
+        for mut circuit in [import(text).unwrap(), import(&exported).unwrap()] {
+            let (clock, _) = circuit.io_names(GateType::IN)[0];
+            let (q, _) = circuit.io_names(GateType::OUT)[0];
+            let mut seen = vec![];
+            for _ in 0..4 {
+                for level in [false, true] {
+                    circuit.gates[clock].active = level;
+                    circuit.settle(1000);
+                }
+                seen.push(circuit.read_input(&circuit.gates[q], 0));
+            }
+            assert_eq!(seen, [true, false, true, false]);
+        }
+    }
+}
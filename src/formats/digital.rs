@@ -0,0 +1,187 @@
+use crate::formats::wire_names;
+use crate::layout::GRID_SIZE;
+use crate::types::circuit::*;
+use crate::types::gate_type::*;
+use quick_xml::escape::escape;
+
+// digital units per grid cell, its own grid is 20 and a gate is 60 to 80 wide
+const CELL: f32 = 120.0;
+
+type Point = (i32, i32);
+
+fn add(a: Point, b: Point) -> Point {
+    (a.0 + b.0, a.1 + b.1)
+}
+
+// counterclockwise like digital's rotation attribute, y grows downwards
+fn rotate((x, y): Point, quarter_turns: i32) -> Point {
+    match quarter_turns.rem_euclid(4) {
+        0 => (x, y),
+        1 => (y, -x),
+        2 => (-x, -y),
+        _ => (-y, x),
+    }
+}
+
+// the circuit as an hneemann digital .dig file. gates keep their cell and rotation. wires here
+// are straight lines with no route to keep, so every pin a wire touches gets a tunnel named after
+// the wire, and unconnected inputs get a constant 0. custom gates are only a grouping, the gates
+// inside them are written as usual
+pub fn export(circuit: &Circuit) -> String {
+    let names = wire_names(circuit);
+    let mut elements = String::new();
+    for gate in circuit.gates.values() {
+        let rotation = (4 - (gate.rotation.as_degrees() / 90.0) as i32) % 4;
+        let center = gate.rect.center() / GRID_SIZE * CELL;
+        let center = ((center.x / 20.0).round() as i32 * 20, (center.y / 20.0).round() as i32 * 20);
+
+        // pins are relative to the element's position, which is its first pin
+        let (element, middle, inputs, outputs): (&str, Point, Vec<Point>, Vec<Point>) = match &gate.gate_type {
+            GateType::IN => ("In", (0, 0), vec![], vec![(0, 0)]),
+            GateType::OUT => ("Out", (0, 0), vec![(0, 0)], vec![]),
+            GateType::NOT => ("Not", (20, 0), vec![(0, 0)], vec![(40, 0)]),
+            GateType::DFF => ("D_FF", (20, 0), vec![(0, 0), (0, 20)], vec![(60, 0)]),
+            GateType::CUSTOM { .. } => continue,
+            gate_type => {
+                // a negated output makes the gate wider
+                let (element, width) = match gate_type {
+                    GateType::AND => ("And", 60),
+                    GateType::OR => ("Or", 60),
+                    GateType::XOR => ("XOr", 60),
+                    GateType::NAND => ("NAnd", 80),
+                    GateType::NOR => ("NOr", 80),
+                    _ => ("XNOr", 80),
+                };
+                (element, (40, 20), vec![(0, 0), (0, 40)], vec![(width, 20)])
+            }
+        };
+        let (x, y) = rotate(middle, rotation);
+        let position = (center.0 - x, center.1 - y);
+        let at = |pin: Point| add(position, rotate(pin, rotation));
+
+        let mut attributes = rotation_entry(rotation);
+        if let Some(label) = &gate.label {
+            attributes.push(string_entry("Label", label));
+        }
+        write_element(&mut elements, element, &attributes, position);
+
+        // tunnels are drawn to the right of their pin and constants to the left
+        for (pin, point) in gate.input.iter().zip(inputs) {
+            match pin.wire_index {
+                Some(wire) => {
+                    let mut attributes = rotation_entry(rotation + 2);
+                    attributes.push(string_entry("NetName", &names[&wire]));
+                    write_element(&mut elements, "Tunnel", &attributes, at(point));
+                }
+                None => {
+                    let mut attributes = rotation_entry(rotation);
+                    attributes.push("        <entry>\n          <string>Value</string>\n          <long>0</long>\n        </entry>\n".to_string());
+                    write_element(&mut elements, "Const", &attributes, at(point));
+                }
+            }
+        }
+        for (pin, point) in gate.output.iter().zip(outputs) {
+            if let Some(wire) = pin.wire_index {
+                let mut attributes = rotation_entry(rotation);
+                attributes.push(string_entry("NetName", &names[&wire]));
+                write_element(&mut elements, "Tunnel", &attributes, at(point));
+            }
+        }
+    }
+
+    format!(
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n\
+         <circuit>\n  \
+         <version>2</version>\n  \
+         <attributes/>\n  \
+         <visualElements>\n{}  \
+         </visualElements>\n  \
+         <wires/>\n  \
+         <measurementOrdering/>\n\
+         </circuit>\n",
+        elements
+    )
+}
+
+// none when not turned, the default
+fn rotation_entry(quarter_turns: i32) -> Vec<String> {
+    match quarter_turns % 4 {
+        0 => vec![],
+        quarter_turns => vec![format!(
+            "        <entry>\n          <string>rotation</string>\n          <rotation rotation=\"{}\"/>\n        </entry>\n",
+            quarter_turns
+        )],
+    }
+}
+
+fn string_entry(key: &str, value: &str) -> String {
+    format!(
+        "        <entry>\n          <string>{}</string>\n          <string>{}</string>\n        </entry>\n",
+        key,
+        escape(value)
+    )
+}
+
+fn write_element(out: &mut String, name: &str, attributes: &[String], (x, y): Point) {
+    out.push_str("    <visualElement>\n");
+    out.push_str(&format!("      <elementName>{}</elementName>\n", name));
+    match attributes.is_empty() {
+        true => out.push_str("      <elementAttributes/>\n"),
+        false => {
+            out.push_str("      <elementAttributes>\n");
+            attributes.iter().for_each(|attribute| out.push_str(attribute));
+            out.push_str("      </elementAttributes>\n");
+        }
+    }
+    out.push_str(&format!("      <pos x=\"{}\" y=\"{}\"/>\n", x, y));
+    out.push_str("    </visualElement>\n");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::gate::*;
+    use crate::types::pin_type::*;
+    use macroquad::prelude::Rect;
+    use std::collections::HashMap;
+
+    #[test]
+    fn every_wire_becomes_a_pair_of_tunnels() {
+        let mut circuit = Circuit::new();
+        let mut add = |gate_type: GateType, x: f32, rotation: Rotation| {
+            circuit.gates.insert(Gate::new(Rect::new(x * 64.0, 0.0, 64.0, 64.0), rotation, gate_type))
+        };
+        let a = add(GateType::IN, 0.0, Rotation::Up);
+        let b = add(GateType::IN, 1.0, Rotation::Up);
+        let nand = add(GateType::NAND, 2.0, Rotation::Right);
+        let y = add(GateType::OUT, 3.0, Rotation::Up);
+        // nothing drives its input
+        let not = add(GateType::NOT, 4.0, Rotation::Down);
+        let z = add(GateType::OUT, 5.0, Rotation::Up);
+        circuit.connect_wire(a, nand, 0, PinType::Output, 0, PinType::Input);
+        circuit.connect_wire(b, nand, 0, PinType::Output, 1, PinType::Input);
+        circuit.connect_wire(nand, y, 0, PinType::Output, 0, PinType::Input);
+        circuit.connect_wire(not, z, 0, PinType::Output, 0, PinType::Input);
+
+        let text = export(&circuit);
+        let mut elements: HashMap<&str, usize> = HashMap::new();
+        for line in text.lines() {
+            if let Some(name) = line.trim().strip_prefix("<elementName>").and_then(|rest| rest.strip_suffix("</elementName>")) {
+                *elements.entry(name).or_default() += 1;
+            }
+        }
+        let expected = [("In", 2), ("Out", 2), ("NAnd", 1), ("Not", 1), ("Const", 1), ("Tunnel", 8)];
+        assert_eq!(elements, HashMap::from(expected), "{}", text);
+
+        // a tunnel at the driving pin and one at the reading pin
+        let mut tunnels: HashMap<&str, usize> = HashMap::new();
+        let lines: Vec<&str> = text.lines().map(str::trim).collect();
+        for pair in lines.windows(2) {
+            if pair[0] == "<string>NetName</string>" {
+                *tunnels.entry(pair[1]).or_default() += 1;
+            }
+        }
+        assert_eq!(tunnels.len(), 4, "{}", text);
+        assert!(tunnels.values().all(|count| *count == 2), "{}", text);
+    }
+}
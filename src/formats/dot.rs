@@ -0,0 +1,179 @@
+use crate::types::circuit::*;
+use crate::types::gate_type::*;
+use crate::types::keys::*;
+use std::collections::HashMap;
+
+fn quote(text: &str) -> String {
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+// the gate / wire graph for graphviz. gates are nodes filled with their color, every wire is an
+// edge from its source to each connected gate, labelled with the output and input pin. custom
+// gates are only a grouping, with 'clusters' their gates are drawn in a box named after them
+pub fn export(circuit: &Circuit, clusters: bool) -> String {
+    let ids: HashMap<GateKey, String> = circuit
+        .gates
+        .keys()
+        .enumerate()
+        .map(|(index, key)| (key, format!("g{}", index)))
+        .collect();
+
+    // the smallest custom gate holding a gate is the cluster it goes in
+    let mut owners: HashMap<GateKey, GateKey> = HashMap::new();
+    if clusters {
+        let mut customs: Vec<(GateKey, &Vec<GateKey>)> = circuit
+            .gates
+            .iter()
+            .filter_map(|(key, gate)| match &gate.gate_type {
+                GateType::CUSTOM { gates, .. } => Some((key, gates)),
+                _ => None,
+            })
+            .collect();
+        customs.sort_by_key(|(_, gates)| std::cmp::Reverse(gates.len()));
+        for (custom, gates) in customs {
+            for key in gates {
+                owners.insert(*key, custom);
+            }
+        }
+    }
+    let mut members: HashMap<Option<GateKey>, Vec<GateKey>> = HashMap::new();
+    for key in circuit.gates.keys() {
+        members.entry(owners.get(&key).copied()).or_default().push(key);
+    }
+
+    let mut dot = String::from("digraph circuit {\n    rankdir=LR;\n    node [shape=box, style=filled];\n");
+    write_nodes(circuit, &ids, &members, None, 1, &mut dot);
+
+    for (key, gate) in &circuit.gates {
+        for (pin, output) in gate.output.iter().enumerate() {
+            let Some(wire) = output.wire_index.and_then(|wire| circuit.wires.get(wire)) else {
+                continue;
+            };
+            for connection in &wire.connections {
+                dot.push_str(&format!(
+                    "    {} -> {} [taillabel=\"{}\", headlabel=\"{}\"];\n",
+                    ids[&key], ids[&connection.gate_index], pin, connection.pin_index
+                ));
+            }
+        }
+    }
+    dot.push_str("}\n");
+    dot
+}
+
+// the gates in one cluster, custom gates among them become nested clusters
+fn write_nodes(
+    circuit: &Circuit,
+    ids: &HashMap<GateKey, String>,
+    members: &HashMap<Option<GateKey>, Vec<GateKey>>,
+    cluster: Option<GateKey>,
+    depth: usize,
+    dot: &mut String,
+) {
+    let indent = "    ".repeat(depth);
+    for key in members.get(&cluster).into_iter().flatten() {
+        let gate = &circuit.gates[*key];
+        if let GateType::CUSTOM { .. } = gate.gate_type {
+            // without clusters it has no gates of its own to show
+            if members.contains_key(&Some(*key)) {
+                dot.push_str(&format!("{}subgraph cluster_{} {{\n", indent, ids[key]));
+                let name = match &gate.label {
+                    Some(label) => format!("{} {}", gate.gate_type.text(), label),
+                    None => gate.gate_type.text().to_string(),
+                };
+                dot.push_str(&format!("{}    label={};\n", indent, quote(&name)));
+                write_nodes(circuit, ids, members, Some(*key), depth + 1, dot);
+                dot.push_str(&format!("{}}}\n", indent));
+            }
+            continue;
+        }
+
+        let label = match &gate.label {
+            Some(label) => format!("{}\n{}", gate.gate_type.text(), label),
+            None => gate.gate_type.text().to_string(),
+        };
+        let [r, g, b, _]: [u8; 4] = gate.gate_type.color().into();
+        dot.push_str(&format!(
+            "{}{} [label={}, fillcolor=\"#{:02x}{:02x}{:02x}\"];\n",
+            indent,
+            ids[key],
+            quote(&label).replace('\n', "\\n"),
+            r,
+            g,
+            b
+        ));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::formats::verilog;
+
+    // the nodes and edges read back out of the dot text
+    struct Graph {
+        // id to gate type and label
+        nodes: HashMap<String, (String, Option<String>)>,
+        // (from, to, input pin)
+        edges: Vec<(String, String, usize)>,
+    }
+
+    fn read(dot: &str) -> Graph {
+        let mut graph = Graph { nodes: HashMap::new(), edges: vec![] };
+        for line in dot.lines().map(str::trim) {
+            if let Some((from, rest)) = line.split_once(" -> ") {
+                let (to, rest) = rest.split_once(' ').unwrap();
+                let pin = rest.split("headlabel=\"").nth(1).unwrap().split('"').next().unwrap();
+                graph.edges.push((from.to_string(), to.to_string(), pin.parse().unwrap()));
+            } else if let Some((id, rest)) = line.split_once(" [label=\"") {
+                let label = rest.split_once("\", fillcolor").unwrap().0;
+                let (gate_type, label) = match label.split_once("\\n") {
+                    Some((gate_type, label)) => (gate_type, Some(label.to_string())),
+                    None => (label, None),
+                };
+                graph.nodes.insert(id.to_string(), (gate_type.to_string(), label));
+            }
+        }
+        graph
+    }
+
+    fn value(graph: &Graph, id: &str, inputs: &HashMap<&str, bool>) -> bool {
+        let mut sources: Vec<&(String, String, usize)> = graph.edges.iter().filter(|(_, to, _)| to == id).collect();
+        sources.sort_by_key(|(_, _, pin)| *pin);
+        let values: Vec<bool> = sources.iter().map(|(from, _, _)| value(graph, from, inputs)).collect();
+        let (gate_type, label) = &graph.nodes[id];
+        match gate_type.as_str() {
+            "in" => inputs[label.as_deref().unwrap()],
+            "out" => values[0],
+            "not" => !values[0],
+            "and" => values[0] && values[1],
+            "or" => values[0] || values[1],
+            "xor" => values[0] != values[1],
+            "nand" => !(values[0] && values[1]),
+            "nor" => !(values[0] || values[1]),
+            "xnor" => values[0] == values[1],
+            other => panic!("unexpected node {}", other),
+        }
+    }
+
+    #[test]
+    fn the_graph_computes_what_the_circuit_does() {
+        let circuit = verilog::import(
+            "module adder(input a, b, cin, output sum, cout);\n  wire p, g, c;\n  xor (p, a, b);\n  xor (sum, p, cin);\n  \
+             and (g, a, b);\n  and (c, p, cin);\n  or (cout, g, c);\nendmodule\n",
+        )
+        .unwrap();
+        let dot = export(&circuit, false);
+        let graph = read(&dot);
+        assert_eq!(graph.nodes.len(), circuit.gates.len(), "{}", dot);
+        let output = |name: &str| {
+            graph.nodes.iter().find(|(_, (gate_type, label))| gate_type == "out" && label.as_deref() == Some(name)).unwrap().0
+        };
+        for combination in 0..8u8 {
+            let inputs = HashMap::from([("a", combination & 4 != 0), ("b", combination & 2 != 0), ("cin", combination & 1 != 0)]);
+            let total = combination.count_ones();
+            assert_eq!(value(&graph, output("sum"), &inputs), total % 2 == 1, "{}", dot);
+            assert_eq!(value(&graph, output("cout"), &inputs), total >= 2, "{}", dot);
+        }
+    }
+}
@@ -0,0 +1,147 @@
+use crate::types::gate_type::*;
+use crate::types::netlist::*;
+use std::collections::HashMap;
+
+pub type Net = usize;
+
+struct Cell {
+    gate_type: GateType,
+    // None leaves the pin floating. logic gates can have any number, see reduce
+    inputs: Vec<Option<Net>>,
+    output: Net,
+    active: bool,
+}
+
+// what the importers flatten a design into before it becomes a Netlist: gates driving nets, with
+// buffers and assigns merging nets instead of adding gates (union find)
+#[derive(Default)]
+pub struct NetGraph {
+    parent: Vec<Net>,
+    // hierarchical name of every net for the error messages
+    names: Vec<String>,
+    cells: Vec<Cell>,
+    zero: Option<Net>,
+    one: Option<Net>,
+}
+
+impl NetGraph {
+    pub fn new() -> NetGraph {
+        NetGraph::default()
+    }
+
+    pub fn net(&mut self, name: String) -> Net {
+        self.parent.push(self.parent.len());
+        self.names.push(name);
+        self.parent.len() - 1
+    }
+
+    pub fn find(&mut self, net: Net) -> Net {
+        let mut root = net;
+        while self.parent[root] != root {
+            root = self.parent[root];
+        }
+        let mut net = net;
+        while self.parent[net] != root {
+            let next = self.parent[net];
+            self.parent[net] = root;
+            net = next;
+        }
+        root
+    }
+
+    pub fn name(&self, net: Net) -> &str {
+        &self.names[net]
+    }
+
+    pub fn merge(&mut self, a: Net, b: Net) {
+        let (a, b) = (self.find(a), self.find(b));
+        self.parent[a] = b;
+    }
+
+    // an undriven net reads false, a NOT gate with a floating input is true
+    pub fn constant(&mut self, value: bool) -> Net {
+        if let Some(net) = if value { self.one } else { self.zero } {
+            return net;
+        }
+        let net = self.net(format!("constant {}", value as u8));
+        if value {
+            self.cells.push(Cell { gate_type: GateType::NOT, inputs: vec![None], output: net, active: true });
+            self.one = Some(net);
+        } else {
+            self.zero = Some(net);
+        }
+        net
+    }
+
+    pub fn gate(&mut self, gate_type: GateType, inputs: &[Net], output: Net) {
+        let inputs = inputs.iter().map(|net| Some(*net)).collect();
+        self.cells.push(Cell { gate_type, inputs, output, active: true });
+    }
+
+    pub fn flip_flop(&mut self, d: Net, clock: Net, q: Net, active: bool) {
+        self.cells.push(Cell { gate_type: GateType::DFF, inputs: vec![Some(d), Some(clock)], output: q, active });
+    }
+
+    // a new net driven by a gate, 'name' is what errors call it
+    pub fn output(&mut self, gate_type: GateType, inputs: &[Net], name: &str) -> Net {
+        let output = self.net(name.to_string());
+        self.gate(gate_type, inputs, output);
+        output
+    }
+
+    // a gate with any number of inputs, NetlistBuilder::reduce_into splits it into 2 input gates
+    pub fn reduce(&mut self, gate_type: GateType, inputs: &[Net], output: Net) {
+        self.gate(gate_type, inputs, output);
+    }
+
+    // the ports become IN / OUT gates with their names as labels, a net with two drivers is an error
+    pub fn netlist(mut self, inputs: &[(String, Net)], outputs: &[(String, Net)]) -> Result<Netlist, String> {
+        let mut builder = NetlistBuilder::new();
+        let mut nets = HashMap::new();
+        let mut drivers: HashMap<Net, String> = HashMap::new();
+
+        let mut drive = |root: Net, driver: String, names: &[String]| -> Result<(), String> {
+            match drivers.get(&root) {
+                Some(other) => Err(format!("{} is driven by both {} and {}", names[root], other, driver)),
+                None => {
+                    drivers.insert(root, driver);
+                    Ok(())
+                }
+            }
+        };
+        let mut net_of = |root: Net, builder: &mut NetlistBuilder| *nets.entry(root).or_insert_with(|| builder.net());
+
+        for (label, net) in inputs {
+            let root = self.find(*net);
+            drive(root, format!("input {}", label), &self.names)?;
+            let net = net_of(root, &mut builder);
+            builder.gate(GateType::IN, Some(label.clone()), &[], &[net]);
+        }
+        let cells = std::mem::take(&mut self.cells);
+        for cell in cells {
+            let root = self.find(cell.output);
+            let text = cell.gate_type.text();
+            let article = if text.starts_with(['a', 'o', 'x']) { "an" } else { "a" };
+            drive(root, format!("{} {} gate", article, text), &self.names)?;
+            let inputs: Vec<Option<NetId>> = cell
+                .inputs
+                .iter()
+                .map(|input| input.map(|input| net_of(self.find(input), &mut builder)))
+                .collect();
+            let output = net_of(root, &mut builder);
+            if inputs.len() != cell.gate_type.input_count() {
+                let inputs: Vec<NetId> = inputs.into_iter().flatten().collect();
+                builder.reduce_into(cell.gate_type, &inputs, output);
+                continue;
+            }
+            let node = builder.gate(cell.gate_type, None, &inputs, &[output]);
+            builder.set_active(node, cell.active);
+        }
+        for (label, net) in outputs {
+            let root = self.find(*net);
+            let net = net_of(root, &mut builder);
+            builder.output(label, net);
+        }
+        Ok(builder.build())
+    }
+}
@@ -0,0 +1,996 @@
+use crate::analysis::subcircuit::Boundary;
+use crate::formats::graph::{Net, NetGraph};
+use crate::formats::wire_names;
+use crate::layout::{GRID_SIZE, SPACING};
+use crate::types::circuit::*;
+use crate::types::gate::*;
+use crate::types::gate_type::*;
+use crate::types::keys::*;
+use crate::types::pin_type::*;
+use macroquad::prelude::*;
+use quick_xml::escape::escape;
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::{Reader, XmlVersion};
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+// logisim units in one grid cell, logisim snaps to 10 and a gate is 50 wide
+const CELL: f32 = 40.0;
+
+type Point = (i32, i32);
+
+// the parts of the xml the importer looks at
+struct Element {
+    name: String,
+    attributes: HashMap<String, String>,
+    children: Vec<Element>,
+}
+
+impl Element {
+    fn new(start: &BytesStart) -> Result<Element, String> {
+        let mut attributes = HashMap::new();
+        for attribute in start.attributes() {
+            let attribute = attribute.map_err(|e| e.to_string())?;
+            let value = attribute.normalized_value(XmlVersion::Implicit1_0).map_err(|e| e.to_string())?;
+            attributes.insert(attribute.key.0.to_string(), value.into_owned());
+        }
+        Ok(Element {
+            name: start.name().0.to_string(),
+            attributes,
+            children: vec![],
+        })
+    }
+
+    fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes.get(name).map(|value| value.as_str())
+    }
+
+    fn children<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Element> {
+        self.children.iter().filter(move |child| child.name == name)
+    }
+
+    // circuits and components keep their attributes as <a name=".." val=".."/> children
+    fn setting(&self, name: &str) -> Option<&str> {
+        self.children("a")
+            .find(|a| a.attribute("name") == Some(name))
+            .and_then(|a| a.attribute("val"))
+    }
+}
+
+fn parse_xml(text: &str) -> Result<Element, String> {
+    let mut reader = Reader::from_str(text);
+    let mut stack = vec![Element {
+        name: String::new(),
+        attributes: HashMap::new(),
+        children: vec![],
+    }];
+    loop {
+        match reader.read_event().map_err(|e| format!("not an xml file: {}", e))? {
+            Event::Start(start) => stack.push(Element::new(&start)?),
+            Event::Empty(start) => {
+                let element = Element::new(&start)?;
+                stack.last_mut().unwrap().children.push(element);
+            }
+            Event::End(_) => {
+                let element = stack.pop().unwrap();
+                match stack.last_mut() {
+                    Some(parent) => parent.children.push(element),
+                    None => return Err("not an xml file".to_string()),
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+    let document = stack.pop().unwrap();
+    document
+        .children
+        .into_iter()
+        .find(|element| element.name == "project")
+        .ok_or("not a logisim file, there is no <project>".to_string())
+}
+
+// "(120,40)"
+fn point(text: Option<&str>) -> Result<Point, String> {
+    let text = text.ok_or("a location is missing")?;
+    let parsed = text
+        .trim()
+        .strip_prefix('(')
+        .and_then(|rest| rest.strip_suffix(')'))
+        .and_then(|rest| rest.split_once(','))
+        .and_then(|(x, y)| Some((x.trim().parse().ok()?, y.trim().parse().ok()?)));
+    parsed.ok_or(format!("bad location '{}'", text))
+}
+
+// the grid cell a logisim location falls in
+fn cell_of((x, y): Point) -> Point {
+    ((x as f32 / CELL).round() as i32, (y as f32 / CELL).round() as i32)
+}
+
+fn add(a: Point, b: Point) -> Point {
+    (a.0 + b.0, a.1 + b.1)
+}
+
+// clockwise, y grows downwards
+fn turn((x, y): Point, quarter_turns: i32) -> Point {
+    match quarter_turns.rem_euclid(4) {
+        0 => (x, y),
+        1 => (-y, x),
+        2 => (-x, -y),
+        _ => (y, -x),
+    }
+}
+
+// wires are horizontal or vertical, anything meeting one anywhere along it connects
+fn on_wire(point: Point, (a, b): (Point, Point)) -> bool {
+    let between = |value: i32, a: i32, b: i32| a.min(b) <= value && value <= a.max(b);
+    (a.0 == b.0 && point.0 == a.0 && between(point.1, a.1, b.1))
+        || (a.1 == b.1 && point.1 == a.1 && between(point.0, a.0, b.0))
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Facing {
+    East,
+    South,
+    West,
+    North,
+}
+
+impl Facing {
+    fn parse(text: Option<&str>, default: Facing) -> Facing {
+        match text {
+            Some("east") => Facing::East,
+            Some("south") => Facing::South,
+            Some("west") => Facing::West,
+            Some("north") => Facing::North,
+            _ => default,
+        }
+    }
+
+    // from east
+    fn quarter_turns(self) -> i32 {
+        match self {
+            Facing::East => 0,
+            Facing::South => 1,
+            Facing::West => 2,
+            Facing::North => 3,
+        }
+    }
+
+    fn reverse(self) -> Facing {
+        match self {
+            Facing::East => Facing::West,
+            Facing::South => Facing::North,
+            Facing::West => Facing::East,
+            Facing::North => Facing::South,
+        }
+    }
+
+    // an offset given for a component facing east, for one facing this way
+    fn turn(self, offset: Point) -> Point {
+        turn(offset, self.quarter_turns())
+    }
+
+    fn of(rotation: &Rotation) -> Facing {
+        match rotation {
+            Rotation::Up => Facing::East,
+            Rotation::Right => Facing::South,
+            Rotation::Down => Facing::West,
+            Rotation::Left => Facing::North,
+        }
+    }
+
+    fn text(self) -> &'static str {
+        match self {
+            Facing::East => "east",
+            Facing::South => "south",
+            Facing::West => "west",
+            Facing::North => "north",
+        }
+    }
+
+    // a gate facing east has its inputs on the left and its output on the right, like Rotation::Up
+    fn rotation(self) -> Rotation {
+        match self {
+            Facing::East => Rotation::Up,
+            Facing::South => Rotation::Right,
+            Facing::West => Rotation::Down,
+            Facing::North => Rotation::Left,
+        }
+    }
+}
+
+// what a component becomes, points are absolute
+enum Part {
+    Pin { output: bool, facing: Facing, label: Option<String> },
+    // an IN gate even inside a subcircuit
+    Clock { label: Option<String> },
+    Gate { gate_type: GateType, inputs: Vec<(Point, bool)>, one_hot: bool },
+    Not { input: Point },
+    Buffer { input: Point },
+    Constant(bool),
+    Tunnel(String),
+    FlipFlop { d: Point, clock: Point, q_bar: Point, falling: bool, controls: Vec<Point> },
+    Subcircuit { name: String, ports: Vec<(Point, Point)> },
+    Ignored,
+    Unsupported(String),
+}
+
+impl Part {
+    // where the part connects, besides its location
+    fn inputs(&self) -> Vec<Point> {
+        match self {
+            Part::Gate { inputs, .. } => inputs.iter().map(|(point, _)| *point).collect(),
+            Part::Not { input } | Part::Buffer { input } => vec![*input],
+            Part::FlipFlop { d, clock, q_bar, controls, .. } => [*d, *clock, *q_bar].into_iter().chain(controls.iter().copied()).collect(),
+            Part::Subcircuit { ports, .. } => ports.iter().map(|(point, _)| *point).collect(),
+            _ => vec![],
+        }
+    }
+
+    fn connects_at_location(&self) -> bool {
+        !matches!(self, Part::Subcircuit { .. } | Part::Ignored | Part::Unsupported(_))
+    }
+}
+
+struct Importer<'a> {
+    circuits: HashMap<&'a str, &'a Element>,
+    // library number in the file to its kind, like "#Gates"
+    libraries: HashMap<&'a str, &'a str>,
+    // files from the original logisim, before 2.8, have other defaults
+    legacy: bool,
+    circuit: Circuit,
+    graph: NetGraph,
+    occupied: HashSet<Point>,
+    created: Vec<GateKey>,
+    drivers: Vec<(Net, GateKey, usize)>,
+    sinks: Vec<(Net, GateKey, usize)>,
+    // custom gates and the gates inside them, the wires crossing their edge are known at the end
+    groups: Vec<(GateKey, Vec<GateKey>)>,
+    // logisim circuits being imported, catches one containing itself
+    stack: Vec<&'a str>,
+    report: BTreeMap<String, usize>,
+}
+
+// the circuit marked main, or the first one. subcircuits become custom gates holding the gates
+// they contain, which are placed below the rest. also returns what couldn't be imported
+pub fn import(text: &str) -> Result<(Circuit, Vec<String>), String> {
+    let project = parse_xml(text)?;
+    let version: Vec<u32> = project
+        .attribute("source")
+        .unwrap_or("")
+        .split('.')
+        .map_while(|part| part.parse().ok())
+        .collect();
+
+    let mut importer = Importer {
+        circuits: project
+            .children("circuit")
+            .filter_map(|circuit| Some((circuit.attribute("name")?, circuit)))
+            .collect(),
+        libraries: project
+            .children("lib")
+            .filter_map(|lib| Some((lib.attribute("name")?, lib.attribute("desc")?)))
+            .collect(),
+        legacy: version.len() >= 2 && (version[0], version[1]) < (2, 8),
+        circuit: Circuit::new(),
+        graph: NetGraph::new(),
+        occupied: HashSet::new(),
+        created: vec![],
+        drivers: vec![],
+        sinks: vec![],
+        groups: vec![],
+        stack: vec![],
+        report: BTreeMap::new(),
+    };
+    let main = project
+        .children("main")
+        .find_map(|main| main.attribute("name"))
+        .or_else(|| project.children("circuit").find_map(|circuit| circuit.attribute("name")))
+        .ok_or("the file has no circuits")?;
+    if !importer.circuits.contains_key(main) {
+        return Err(format!("the main circuit {} is missing", main));
+    }
+    importer.sheet(main, (0, 0), None)?;
+    importer.finish()
+}
+
+impl<'a> Importer<'a> {
+    fn skip(&mut self, what: String) {
+        *self.report.entry(what).or_default() += 1;
+    }
+
+    // the cell asked for or the first free one below it
+    fn place(&mut self, gate_type: GateType, mut cell: Point, rotation: Rotation) -> GateKey {
+        while !self.occupied.insert(cell) {
+            cell.1 += 1;
+        }
+        let rect = Rect::new(cell.0 as f32 * GRID_SIZE, cell.1 as f32 * GRID_SIZE, GRID_SIZE, GRID_SIZE);
+        let key = self.circuit.gates.insert(Gate::new(rect, rotation, gate_type));
+        self.created.push(key);
+        key
+    }
+
+    // a gate with any number of inputs out of 2 input gates, as a balanced tree
+    fn reduce(&mut self, gate_type: GateType, inputs: &[Net], output: Net, cell: Point, rotation: Rotation) {
+        let base = match gate_type {
+            GateType::NAND => GateType::AND,
+            GateType::NOR => GateType::OR,
+            GateType::XNOR => GateType::XOR,
+            ref other => other.clone(),
+        };
+        let (left, right) = inputs.split_at(inputs.len() / 2);
+        let partial = |inputs: &[Net], importer: &mut Self| match inputs {
+            [net] => *net,
+            _ => {
+                let net = importer.graph.net("a gate input".to_string());
+                importer.reduce(base.clone(), inputs, net, cell, rotation.clone());
+                net
+            }
+        };
+        let a = partial(left, self);
+        let b = partial(right, self);
+        let key = self.place(gate_type, cell, rotation);
+        self.sinks.push((a, key, 0));
+        self.sinks.push((b, key, 1));
+        self.drivers.push((output, key, 0));
+    }
+
+    fn part(&self, component: &'a Element, location: Point, ends: &HashSet<Point>) -> Part {
+        let library = component.attribute("lib").and_then(|lib| self.libraries.get(lib)).copied();
+        let name = component.attribute("name").unwrap_or("");
+        let facing = |default| Facing::parse(component.setting("facing"), default);
+        let label = component.setting("label").filter(|label| !label.is_empty()).map(str::to_string);
+        let number = |setting: &str, default: i32| {
+            component.setting(setting).and_then(|value| value.parse().ok()).unwrap_or(default)
+        };
+        if number("width", 1) != 1 {
+            return Part::Unsupported(format!("{} wider than 1 bit", name));
+        }
+
+        match (library, name) {
+            (Some("#Wiring"), "Pin") => Part::Pin {
+                output: component.setting("output") == Some("true") || component.setting("type") == Some("output"),
+                facing: facing(Facing::East),
+                label,
+            },
+            (Some("#I/O"), "Button") => Part::Pin { output: false, facing: facing(Facing::East), label },
+            (Some("#I/O"), "LED") => Part::Pin { output: true, facing: facing(Facing::West), label },
+            (Some("#Wiring"), "Clock") => Part::Clock { label },
+            (Some("#Wiring"), "Tunnel") => Part::Tunnel(label.unwrap_or_default()),
+            (Some("#Wiring"), "Power") => Part::Constant(true),
+            (Some("#Wiring"), "Ground") => Part::Constant(false),
+            (Some("#Wiring"), "Constant") => {
+                let value = component.setting("value").unwrap_or("0x1");
+                let value = i64::from_str_radix(value.trim_start_matches("0x"), 16).unwrap_or(1);
+                Part::Constant(value & 1 == 1)
+            }
+            (Some("#Wiring"), "Probe") | (Some("#Base"), "Text") | (Some("#Base"), "Label") => Part::Ignored,
+            (Some("#Gates"), "NOT Gate" | "Buffer") => {
+                let input = add(location, facing(Facing::East).turn((-number("size", 30), 0)));
+                match name {
+                    "Buffer" => Part::Buffer { input },
+                    _ => Part::Not { input },
+                }
+            }
+            (Some("#Gates"), "AND Gate" | "OR Gate" | "XOR Gate" | "NAND Gate" | "NOR Gate" | "XNOR Gate") => {
+                let gate_type = match name {
+                    "AND Gate" => GateType::AND,
+                    "OR Gate" => GateType::OR,
+                    "XOR Gate" => GateType::XOR,
+                    "NAND Gate" => GateType::NAND,
+                    "NOR Gate" => GateType::NOR,
+                    _ => GateType::XNOR,
+                };
+                let count = number("inputs", if self.legacy { 5 } else { 2 }).max(1);
+                let size = number("size", 50);
+                let facing = facing(Facing::East);
+                let inputs = (0..count)
+                    .map(|index| {
+                        let negated = component.setting(&format!("negate{}", index)) == Some("true");
+                        let (x, y) = gate_input(&gate_type, count, index, size);
+                        let offset = (x - if negated { 10 } else { 0 }, y);
+                        (add(location, facing.turn(offset)), negated)
+                    })
+                    .collect();
+                Part::Gate {
+                    gate_type,
+                    inputs,
+                    one_hot: component.setting("xor") != Some("odd"),
+                }
+            }
+            (Some("#Memory"), "D Flip-Flop") => {
+                let appearance = component.setting("appearance");
+                if appearance.unwrap_or(if self.legacy { "classic" } else { "logisim_evolution" }) != "classic" {
+                    return Part::Unsupported("flip-flops not in the classic appearance".to_string());
+                }
+                let trigger = component.setting("trigger").unwrap_or("rising");
+                if trigger != "rising" && trigger != "falling" {
+                    return Part::Unsupported(format!("{} triggered latches", trigger));
+                }
+                // the location is q
+                let at = |offset: Point| add(location, offset);
+                Part::FlipFlop {
+                    d: at((-40, 0)),
+                    clock: at((-40, 20)),
+                    q_bar: at((0, 20)),
+                    falling: trigger == "falling",
+                    controls: vec![at((-10, 30)), at((-20, 30)), at((-30, 30))],
+                }
+            }
+            (None, _) if self.circuits.contains_key(name) => {
+                let ports = self.instance_ports(name, location, facing(Facing::East), ends);
+                Part::Subcircuit { name: name.to_string(), ports }
+            }
+            _ => Part::Unsupported(name.to_string()),
+        }
+    }
+
+    // pairs of where an instance of a subcircuit connects and the location of the Pin inside it.
+    // the port layout depends on the appearance of the subcircuit
+    fn instance_ports(&self, name: &str, location: Point, facing: Facing, ends: &HashSet<Point>) -> Vec<(Point, Point)> {
+        let sheet = self.circuits[name];
+        let mut pins: Vec<(Point, Facing, bool)> = vec![];
+        for component in sheet.children("comp") {
+            let library = component.attribute("lib").and_then(|lib| self.libraries.get(lib)).copied();
+            if library == Some("#Wiring") && component.attribute("name") == Some("Pin")
+                && let Ok(pin) = point(component.attribute("loc"))
+            {
+                let output = component.setting("output") == Some("true") || component.setting("type") == Some("output");
+                pins.push((pin, Facing::parse(component.setting("facing"), Facing::East), output));
+            }
+        }
+
+        // drawn by hand, every port says which pin it is
+        let custom = sheet.children("appear").next();
+        if let Some(appear) = custom.filter(|appear| appear.children("circ-port").next().is_some()) {
+            let (anchor, anchor_facing) = appear
+                .children("circ-anchor")
+                .next()
+                .map(|anchor| {
+                    (point(anchor.attribute("loc")).unwrap_or((0, 0)), Facing::parse(anchor.attribute("facing"), Facing::East))
+                })
+                .unwrap_or(((0, 0), Facing::East));
+            let turns = facing.quarter_turns() - anchor_facing.quarter_turns();
+            return appear
+                .children("circ-port")
+                .filter_map(|port| {
+                    let (x, y) = point(port.attribute("loc")).ok()?;
+                    let pin = point(port.attribute("pin")).ok()?;
+                    Some((add(location, turn((x - anchor.0, y - anchor.1), turns)), pin))
+                })
+                .collect();
+        }
+
+        let appearance = sheet
+            .setting("appearance")
+            .unwrap_or(if self.legacy { "classic" } else { "logisim_evolution" });
+        let offsets = match appearance {
+            "classic" => classic_ports(&pins),
+            _ => {
+                // inputs on the left and outputs on the right 20 apart, the location is the first
+                // output. the width follows the pin labels in logisim's font, the wire ends on the
+                // row of the inputs tell where the left edge is
+                let mut inputs: Vec<Point> = pins.iter().filter(|(_, _, output)| !output).map(|(pin, _, _)| *pin).collect();
+                let mut outputs: Vec<Point> = pins.iter().filter(|(_, _, output)| *output).map(|(pin, _, _)| *pin).collect();
+                inputs.sort_by_key(|(x, y)| (*y, *x));
+                outputs.sort_by_key(|(x, y)| (*y, *x));
+                let width = match outputs.is_empty() {
+                    true => Some(0),
+                    false => (1..100).map(|step| step * 10).find(|width| {
+                        (0..inputs.len() as i32).any(|row| ends.contains(&add(location, facing.turn((-width, row * 20)))))
+                    }),
+                };
+                let rows = |pins: Vec<Point>, x: i32| {
+                    pins.into_iter().enumerate().map(move |(row, pin)| ((x, row as i32 * 20), pin))
+                };
+                let mut offsets: Vec<(Point, Point)> = rows(outputs, 0).collect();
+                if let Some(width) = width {
+                    offsets.extend(rows(inputs, -width));
+                }
+                offsets
+            }
+        };
+        offsets
+            .into_iter()
+            .map(|(offset, pin)| (add(location, facing.turn(offset)), pin))
+            .collect()
+    }
+
+    // a logisim circuit placed with 'origin' as its grid cell (0, 0). for a subcircuit 'ports'
+    // has the net of every Pin inside it that the instance connects. returns the gates made
+    fn sheet(&mut self, name: &'a str, origin: Point, ports: Option<&HashMap<Point, Net>>) -> Result<Vec<GateKey>, String> {
+        if self.stack.contains(&name) {
+            return Err(format!("circuit {} contains itself", name));
+        }
+        self.stack.push(name);
+        let path = self.stack.join("/");
+        let start = self.created.len();
+        let sheet = self.circuits[name];
+
+        let mut wires = vec![];
+        for wire in sheet.children("wire") {
+            wires.push((point(wire.attribute("from"))?, point(wire.attribute("to"))?));
+        }
+        let ends: HashSet<Point> = wires.iter().flat_map(|(a, b)| [*a, *b]).collect();
+        let mut parts = vec![];
+        for component in sheet.children("comp") {
+            let location = point(component.attribute("loc"))?;
+            let part = self.part(component, location, &ends);
+            parts.push((component, location, part));
+        }
+
+        // every point something connects at gets a net, wires join the points along them
+        let mut incidences: HashMap<Point, usize> = HashMap::new();
+        for (a, b) in &wires {
+            *incidences.entry(*a).or_default() += 1;
+            *incidences.entry(*b).or_default() += 1;
+        }
+        for (_, location, part) in &parts {
+            let location = Some(*location).filter(|_| part.connects_at_location());
+            for point in part.inputs().into_iter().chain(location) {
+                *incidences.entry(point).or_default() += 1;
+            }
+        }
+        let mut nets: HashMap<Point, Net> = HashMap::new();
+        for point in incidences.keys() {
+            nets.insert(*point, self.graph.net(format!("the wire at {:?} in {}", point, path)));
+        }
+        for wire in &wires {
+            for point in incidences.keys() {
+                if on_wire(*point, *wire) {
+                    self.graph.merge(nets[point], nets[&wire.0]);
+                }
+            }
+        }
+        let mut tunnels: HashMap<&str, Net> = HashMap::new();
+        for (_, location, part) in &parts {
+            match part {
+                Part::Tunnel(label) => match tunnels.get(label.as_str()) {
+                    Some(net) => self.graph.merge(nets[location], *net),
+                    None => {
+                        tunnels.insert(label, nets[location]);
+                    }
+                },
+                Part::Buffer { input } => self.graph.merge(nets[location], nets[input]),
+                _ => {}
+            }
+        }
+
+        // logisim leaves gate inputs nothing connects to out
+        let mut counts: HashMap<Net, usize> = HashMap::new();
+        for (point, count) in &incidences {
+            *counts.entry(self.graph.find(nets[point])).or_default() += count;
+        }
+        let connected: HashSet<Point> = incidences
+            .keys()
+            .filter(|point| counts[&self.graph.find(nets[point])] > 1)
+            .copied()
+            .collect();
+
+        let cell = |point: Point| add(origin, cell_of(point));
+        let mut subcircuits = vec![];
+        for (component, location, part) in parts {
+            let facing = Facing::parse(component.setting("facing"), Facing::East);
+            let label = component.setting("label").filter(|label| !label.is_empty()).map(str::to_string);
+            match part {
+                // the pins of a subcircuit are where the instance connects
+                Part::Pin { output, facing, label } => match ports {
+                    Some(ports) => {
+                        if let Some(outside) = ports.get(&location) {
+                            self.graph.merge(nets[&location], *outside);
+                        }
+                    }
+                    None => {
+                        // an output pin faces away from what it shows
+                        let (gate_type, rotation) = match output {
+                            true => (GateType::OUT, facing.reverse().rotation()),
+                            false => (GateType::IN, facing.rotation()),
+                        };
+                        let key = self.place(gate_type, cell(location), rotation);
+                        self.circuit.gates[key].label = label;
+                        match output {
+                            true => self.sinks.push((nets[&location], key, 0)),
+                            false => self.drivers.push((nets[&location], key, 0)),
+                        }
+                    }
+                },
+                Part::Clock { label } => {
+                    let key = self.place(GateType::IN, cell(location), facing.rotation());
+                    self.circuit.gates[key].label = Some(label.unwrap_or("clock".to_string()));
+                    self.drivers.push((nets[&location], key, 0));
+                }
+                Part::Gate { gate_type, inputs, one_hot } => {
+                    let rotation = facing.rotation();
+                    let mut input_nets = vec![];
+                    for (point, negated) in inputs.into_iter().filter(|(point, _)| connected.contains(point)) {
+                        let mut input = nets[&point];
+                        if negated {
+                            let not = self.place(GateType::NOT, cell(point), rotation.clone());
+                            self.sinks.push((input, not, 0));
+                            input = self.graph.net(format!("a negated input in {}", path));
+                            self.drivers.push((input, not, 0));
+                        }
+                        input_nets.push(input);
+                    }
+                    let output = nets[&location];
+                    let inverted = matches!(gate_type, GateType::NAND | GateType::NOR | GateType::XNOR);
+                    match input_nets.as_slice() {
+                        [] => self.skip("gates without connected inputs".to_string()),
+                        [input] if inverted => {
+                            let key = self.place(GateType::NOT, cell(location), rotation);
+                            self.sinks.push((*input, key, 0));
+                            self.drivers.push((output, key, 0));
+                        }
+                        [input] => self.graph.merge(output, *input),
+                        [_, _, _, ..] if one_hot && matches!(gate_type, GateType::XOR | GateType::XNOR) => {
+                            self.skip("xor gates with more than 2 inputs that aren't set to odd parity".to_string());
+                        }
+                        _ => self.reduce(gate_type, &input_nets, output, cell(location), rotation),
+                    }
+                }
+                Part::Not { input } => {
+                    let key = self.place(GateType::NOT, cell(location), facing.rotation());
+                    self.sinks.push((nets[&input], key, 0));
+                    self.drivers.push((nets[&location], key, 0));
+                }
+                // a NOT gate with nothing on its input
+                Part::Constant(true) => {
+                    let key = self.place(GateType::NOT, cell(location), facing.rotation());
+                    self.drivers.push((nets[&location], key, 0));
+                }
+                Part::FlipFlop { d, clock, q_bar, falling, controls } => {
+                    let key = self.place(GateType::DFF, cell(location), Rotation::Up);
+                    self.sinks.push((nets[&d], key, 0));
+                    match falling {
+                        true => {
+                            let not = self.place(GateType::NOT, cell(clock), Rotation::Up);
+                            self.sinks.push((nets[&clock], not, 0));
+                            let inverted = self.graph.net(format!("a falling edge clock in {}", path));
+                            self.drivers.push((inverted, not, 0));
+                            self.sinks.push((inverted, key, 1));
+                        }
+                        false => self.sinks.push((nets[&clock], key, 1)),
+                    }
+                    self.drivers.push((nets[&location], key, 0));
+                    if connected.contains(&q_bar) {
+                        let not = self.place(GateType::NOT, cell(q_bar), Rotation::Up);
+                        self.sinks.push((nets[&location], not, 0));
+                        self.drivers.push((nets[&q_bar], not, 0));
+                    }
+                    if controls.iter().any(|point| connected.contains(point)) {
+                        self.skip("flip-flop reset, set and enable pins, they were left out".to_string());
+                    }
+                }
+                Part::Subcircuit { name, ports } => subcircuits.push((name, location, facing, label, ports)),
+                Part::Unsupported(what) => self.skip(what),
+                Part::Constant(false) | Part::Tunnel(_) | Part::Buffer { .. } | Part::Ignored => {}
+            }
+        }
+
+        // each subcircuit gets its own rows below everything placed so far
+        for (name, location, facing, label, ports) in subcircuits {
+            let name = self.circuits.get_key_value(name.as_str()).map(|(name, _)| *name).unwrap();
+            let marker = self.place(
+                GateType::CUSTOM {
+                    gates: vec![],
+                    color: LIGHTGRAY,
+                    text: name.to_string(),
+                    inputs: vec![],
+                    outputs: vec![],
+                },
+                cell(location),
+                facing.rotation(),
+            );
+            self.circuit.gates[marker].label = label;
+
+            let inside: HashMap<Point, Net> = ports.iter().map(|(point, pin)| (*pin, nets[point])).collect();
+            let corner = self.circuits[name]
+                .children("comp")
+                .filter_map(|component| point(component.attribute("loc")).ok())
+                .map(cell_of)
+                .reduce(|a, b| (a.0.min(b.0), a.1.min(b.1)))
+                .unwrap_or((0, 0));
+            let bottom = self.occupied.iter().map(|(_, y)| *y).max().unwrap_or(0);
+            let origin = (-corner.0, bottom + SPACING - corner.1);
+            let members = self.sheet(name, origin, Some(&inside))?;
+            self.groups.push((marker, members));
+        }
+
+        self.stack.pop();
+        Ok(self.created[start..].to_vec())
+    }
+
+    fn finish(mut self) -> Result<(Circuit, Vec<String>), String> {
+        let mut drivers: HashMap<Net, (GateKey, usize)> = HashMap::new();
+        for (net, key, pin) in std::mem::take(&mut self.drivers) {
+            let root = self.graph.find(net);
+            if drivers.insert(root, (key, pin)).is_some() {
+                return Err(format!("{} has more than one driver", self.graph.name(net)));
+            }
+        }
+        for (net, key, pin) in std::mem::take(&mut self.sinks) {
+            if let Some((source, source_pin)) = drivers.get(&self.graph.find(net)) {
+                self.circuit.connect_wire(*source, key, *source_pin, PinType::Output, pin, PinType::Input);
+            }
+        }
+
+        for (marker, members) in self.groups {
+            let boundary = Boundary::of(&self.circuit, &members.iter().copied().collect());
+            let old = &self.circuit.gates[marker];
+            let gate_type = GateType::CUSTOM {
+                gates: members,
+                color: LIGHTGRAY,
+                text: old.gate_type.text().to_string(),
+                inputs: boundary.inputs,
+                outputs: boundary.outputs,
+            };
+            let mut gate = Gate::new(old.rect, old.rotation.clone(), gate_type);
+            gate.label = old.label.clone();
+            self.circuit.gates[marker] = gate;
+        }
+
+        let report = self
+            .report
+            .into_iter()
+            .map(|(what, count)| format!("skipped {}x {}", count, what))
+            .collect();
+        Ok((self.circuit, report))
+    }
+}
+
+// where input 'index' of a gate facing east sits, relative to its output
+fn gate_input(gate_type: &GateType, count: i32, index: i32, size: i32) -> Point {
+    // xor gates are drawn wider, and a negated output adds a bubble
+    let mut length = size;
+    if matches!(gate_type, GateType::XOR | GateType::XNOR) {
+        length += 10;
+    }
+    if matches!(gate_type, GateType::NAND | GateType::NOR | GateType::XNOR) {
+        length += 10;
+    }
+    let (start, step, lower) = match count {
+        1..=3 if size < 40 => (-5, 10, 10),
+        1..=3 if size < 60 || count <= 2 => (-10, 20, 20),
+        1..=3 => (-15, 30, 30),
+        4 if size >= 60 => (-5, 20, 0),
+        _ => (-5, 10, 10),
+    };
+    match count % 2 {
+        1 => (-length, start * (count - 1) + step * index),
+        _ => (-length, start * count + step * index + if index >= count / 2 { lower } else { 0 }),
+    }
+}
+
+// logisim's classic box: pins sit on the edge opposite to where they face, 10 apart, and the
+// location is the first pin on the right, top, left or bottom edge in that order
+fn classic_ports(pins: &[(Point, Facing, bool)]) -> Vec<(Point, Point)> {
+    let mut edges: [Vec<Point>; 4] = Default::default();
+    for (pin, facing, _) in pins {
+        edges[facing.reverse().quarter_turns() as usize].push(*pin);
+    }
+    for (turns, edge) in edges.iter_mut().enumerate() {
+        match turns % 2 {
+            0 => edge.sort_by_key(|(x, y)| (*y, *x)),
+            _ => edge.sort_by_key(|(x, y)| (*x, *y)),
+        }
+    }
+    let [east, south, west, north] = edges;
+    let (vertical, horizontal) = (north.len().max(south.len()) as i32, east.len().max(west.len()) as i32);
+    let offset = |facing: usize, opposite: usize, others: i32| {
+        let most = facing.max(opposite) as i32;
+        let first = match most {
+            0 | 1 if others == 0 => 15,
+            0..=2 => 10,
+            _ if others == 0 => 5,
+            _ => 10,
+        };
+        first + 10 * ((most - facing as i32) / 2)
+    };
+    let dimension = |this: i32, others: i32| match this {
+        0..=2 => 30,
+        _ if others == 0 => 10 * this,
+        _ => 10 * this + 10,
+    };
+    let east_start = offset(east.len(), west.len(), vertical);
+    let west_start = offset(west.len(), east.len(), vertical);
+    let north_start = offset(north.len(), south.len(), horizontal);
+    let south_start = offset(south.len(), north.len(), horizontal);
+    let width = dimension(vertical, horizontal);
+    let height = dimension(horizontal, vertical);
+
+    let anchor = match () {
+        _ if !east.is_empty() => (width, east_start),
+        _ if !north.is_empty() => (north_start, 0),
+        _ if !west.is_empty() => (0, west_start),
+        _ if !south.is_empty() => (south_start, height),
+        _ => (0, 0),
+    };
+    let along = |pins: Vec<Point>, (x, y): Point, (dx, dy): Point| {
+        pins.into_iter()
+            .enumerate()
+            .map(move |(index, pin)| ((x + dx * index as i32 - anchor.0, y + dy * index as i32 - anchor.1), pin))
+    };
+    along(west, (0, west_start), (0, 10))
+        .chain(along(east, (width, east_start), (0, 10)))
+        .chain(along(north, (north_start, 0), (10, 0)))
+        .chain(along(south, (south_start, height), (10, 0)))
+        .collect()
+}
+
+// logisim units per grid cell when exporting, leaves room for the tunnels between gates
+const EXPORT_CELL: f32 = 80.0;
+
+// the circuit as the main circuit of a logisim-evolution project. gates keep their cell and
+// rotation. wires here are straight lines with no route to keep, so every pin a wire touches gets
+// a tunnel named after the wire. unconnected inputs get a constant 0 where logisim would leave
+// them floating. custom gates are only a grouping, the gates inside them are written as usual
+pub fn export(circuit: &Circuit, name: &str) -> String {
+    let names = wire_names(circuit);
+    let mut components = String::new();
+    for gate in circuit.gates.values() {
+        let facing = Facing::of(&gate.rotation);
+        let center = gate.rect.center() / GRID_SIZE * EXPORT_CELL;
+        let center = ((center.x / 10.0).round() as i32 * 10, (center.y / 10.0).round() as i32 * 10);
+        let at = |offset: Point| add(center, facing.turn(offset));
+
+        let (library, component, location, mut settings, inputs, outputs) = match &gate.gate_type {
+            GateType::IN => ("0", "Pin", center, vec![("facing", facing.text())], vec![], vec![center]),
+            // an output pin faces away from what it shows
+            GateType::OUT => (
+                "0",
+                "Pin",
+                center,
+                vec![("facing", facing.reverse().text()), ("output", "true")],
+                vec![center],
+                vec![],
+            ),
+            GateType::NOT => {
+                let location = at((20, 0));
+                let input = add(location, facing.turn((-30, 0)));
+                ("1", "NOT Gate", location, vec![("facing", facing.text())], vec![input], vec![location])
+            }
+            // flip-flops can't be turned in logisim, the location is q
+            GateType::DFF => {
+                let location = add(center, (20, -10));
+                let inputs = vec![add(location, (-40, 0)), add(location, (-40, 20))];
+                ("2", "D Flip-Flop", location, vec![("appearance", "classic")], inputs, vec![location])
+            }
+            GateType::CUSTOM { .. } => continue,
+            gate_type => {
+                let location = at((30, 0));
+                let component = match gate_type {
+                    GateType::AND => "AND Gate",
+                    GateType::OR => "OR Gate",
+                    GateType::XOR => "XOR Gate",
+                    GateType::NAND => "NAND Gate",
+                    GateType::NOR => "NOR Gate",
+                    _ => "XNOR Gate",
+                };
+                let inputs = (0..2)
+                    .map(|index| add(location, facing.turn(gate_input(gate_type, 2, index, 50))))
+                    .collect();
+                let settings = vec![("facing", facing.text()), ("inputs", "2"), ("size", "50")];
+                ("1", component, location, settings, inputs, vec![location])
+            }
+        };
+        if let Some(label) = &gate.label {
+            settings.push(("label", label));
+        }
+        write_component(&mut components, library, component, location, &settings);
+
+        // tunnels and constants point at the pin from outside the gate
+        for (pin, point) in gate.input.iter().zip(inputs) {
+            match pin.wire_index {
+                Some(wire) => {
+                    let settings = [("facing", facing.text()), ("label", names[&wire].as_str())];
+                    write_component(&mut components, "0", "Tunnel", point, &settings);
+                }
+                None => {
+                    let settings = [("facing", facing.text()), ("value", "0x0")];
+                    write_component(&mut components, "0", "Constant", point, &settings);
+                }
+            }
+        }
+        for (pin, point) in gate.output.iter().zip(outputs) {
+            if let Some(wire) = pin.wire_index {
+                let settings = [("facing", facing.reverse().text()), ("label", names[&wire].as_str())];
+                write_component(&mut components, "0", "Tunnel", point, &settings);
+            }
+        }
+    }
+
+    let name = escape(name);
+    format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"no\"?>\n\
+         <project source=\"3.8.0\" version=\"1.0\">\n  \
+         <lib desc=\"#Wiring\" name=\"0\"/>\n  \
+         <lib desc=\"#Gates\" name=\"1\"/>\n  \
+         <lib desc=\"#Memory\" name=\"2\"/>\n  \
+         <main name=\"{}\"/>\n  \
+         <circuit name=\"{}\">\n    \
+         <a name=\"circuit\" val=\"{}\"/>\n{}  \
+         </circuit>\n\
+         </project>\n",
+        name, name, name, components
+    )
+}
+
+fn write_component(out: &mut String, library: &str, name: &str, (x, y): Point, settings: &[(&str, &str)]) {
+    out.push_str(&format!("    <comp lib=\"{}\" loc=\"({},{})\" name=\"{}\">\n", library, x, y, name));
+    for (name, value) in settings {
+        out.push_str(&format!("      <a name=\"{}\" val=\"{}\"/>\n", name, escape(*value)));
+    }
+    out.push_str("    </comp>\n");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::equivalence::{self, Equivalence, Matching};
+    use crate::analysis::truth_table::TruthTable;
+
+    // y = a xor b, z = a and not b through a negated input. a and b branch off to the AND gate
+    const GATES: &str = r##"<?xml version="1.0" encoding="UTF-8" standalone="no"?>
+<project source="3.8.0" version="1.0">
+  <lib desc="#Wiring" name="0"/>
+  <lib desc="#Gates" name="1"/>
+  <main name="main"/>
+  <circuit name="main">
+    <wire from="(100,80)" to="(140,80)"/>
+    <wire from="(120,80)" to="(120,180)"/>
+    <wire from="(120,180)" to="(150,180)"/>
+    <wire from="(100,120)" to="(140,120)"/>
+    <wire from="(110,120)" to="(110,220)"/>
+    <wire from="(110,220)" to="(140,220)"/>
+    <wire from="(200,100)" to="(260,100)"/>
+    <wire from="(200,200)" to="(260,200)"/>
+    <comp lib="0" loc="(100,80)" name="Pin"><a name="label" val="a"/></comp>
+    <comp lib="0" loc="(100,120)" name="Pin"><a name="label" val="b"/></comp>
+    <comp lib="0" loc="(260,100)" name="Pin">
+      <a name="facing" val="west"/><a name="output" val="true"/><a name="label" val="y"/>
+    </comp>
+    <comp lib="0" loc="(260,200)" name="Pin">
+      <a name="facing" val="west"/><a name="output" val="true"/><a name="label" val="z"/>
+    </comp>
+    <comp lib="1" loc="(200,100)" name="XOR Gate"/>
+    <comp lib="1" loc="(200,200)" name="AND Gate"><a name="negate1" val="true"/></comp>
+  </circuit>
+</project>
+"##;
+
+    #[test]
+    fn gates_and_pins_import_as_their_function() {
+        let (circuit, report) = import(GATES).unwrap();
+        assert!(report.is_empty(), "{:?}", report);
+        let table = TruthTable::generate(&circuit).unwrap();
+        let column = |names: &[String], name: &str| names.iter().position(|n| n == name).unwrap();
+        let (a, b) = (column(&table.inputs, "a"), column(&table.inputs, "b"));
+        let (y, z) = (column(&table.outputs, "y"), column(&table.outputs, "z"));
+        assert_eq!(table.rows.len(), 4);
+        for row in &table.rows {
+            let (a, b) = (row.inputs[a], row.inputs[b]);
+            assert!(row.settled);
+            assert_eq!(row.outputs[y], a != b, "a={} b={}", a, b);
+            assert_eq!(row.outputs[z], a && !b, "a={} b={}", a, b);
+        }
+    }
+
+    #[test]
+    fn exported_sheets_import_as_the_same_function() {
+        let (mut circuit, _) = import(GATES).unwrap();
+        // every facing, the tunnels have to land on the turned pins
+        for rotation in [Rotation::Up, Rotation::Right, Rotation::Down, Rotation::Left] {
+            for gate in circuit.gates.values_mut() {
+                gate.rotation = rotation.clone();
+            }
+            let text = export(&circuit, "main");
+            let (again, report) = import(&text).unwrap();
+            assert!(report.is_empty(), "{:?}", report);
+            assert!(
+                matches!(equivalence::check(&circuit, &again, Matching::Names), Ok(Equivalence::Equivalent)),
+                "{}",
+                text
+            );
+        }
+    }
+}
@@ -0,0 +1,105 @@
+pub mod blif;
+pub mod digital;
+pub mod dot;
+pub mod graph;
+pub mod logisim;
+pub mod svg;
+pub mod verilog;
+pub mod yosys;
+
+use crate::types::circuit::*;
+use crate::types::gate_type::*;
+use crate::types::keys::*;
+use crate::utils::load_from_file;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+
+// a save file, or any format that can be imported going by the extension
+pub fn load(path: &str) -> Result<Circuit, String> {
+    let path = path.trim();
+    let import: fn(&str) -> Result<Circuit, String> = match Path::new(path).extension().and_then(|e| e.to_str()) {
+        Some("v") => verilog::import,
+        Some("blif") => blif::import,
+        Some("json") => yosys::import,
+        Some("circ") => import_logisim,
+        _ => return load_from_file(path).map_err(|e| e.to_string()),
+    };
+    let text = fs::read_to_string(path).map_err(|e| e.to_string())?;
+    import(&text)
+}
+
+// what couldn't be imported goes to stderr, the circuit is loaded anyway
+fn import_logisim(text: &str) -> Result<Circuit, String> {
+    let (circuit, report) = logisim::import(text)?;
+    for line in report {
+        eprintln!("{}", line);
+    }
+    Ok(circuit)
+}
+
+// a name for every wire, for formats that connect by name: the label of the IN gate driving it
+// or n0, n1, ...
+pub fn wire_names(circuit: &Circuit) -> HashMap<WireKey, String> {
+    let mut names = Names::words(&[]);
+    let mut wires = HashMap::new();
+    for (key, name) in circuit.io_names(GateType::IN) {
+        if let Some(wire) = circuit.gates[key].output[0].wire_index {
+            wires.insert(wire, names.unique(&name));
+        }
+    }
+    for (index, wire) in circuit.wires.keys().enumerate() {
+        wires.entry(wire).or_insert_with(|| names.unique(&format!("n{}", index)));
+    }
+    wires
+}
+
+// hands out names that are valid in the target format and unique within a scope
+pub struct Names {
+    used: HashSet<String>,
+    reserved: &'static [&'static str],
+    // identifiers are letters, digits and '_' and don't start with a digit
+    identifiers: bool,
+}
+
+impl Names {
+    pub fn new(reserved: &'static [&'static str]) -> Names {
+        Names {
+            used: HashSet::new(),
+            reserved,
+            identifiers: true,
+        }
+    }
+
+    // for formats taking any word without whitespace, like blif
+    pub fn words(reserved: &'static [&'static str]) -> Names {
+        Names {
+            identifiers: false,
+            ..Names::new(reserved)
+        }
+    }
+
+    // invalid characters become '_', a number is added on clashes
+    pub fn unique(&mut self, name: &str) -> String {
+        let valid = |c: char| match self.identifiers {
+            true => c.is_ascii_alphanumeric() || c == '_',
+            false => c.is_ascii_graphic() && !matches!(c, '#' | '=' | '\\'),
+        };
+        let mut base: String = name.chars().map(|c| if valid(c) { c } else { '_' }).collect();
+        if base.is_empty() || (self.identifiers && base.starts_with(|c: char| c.is_ascii_digit())) {
+            base.insert(0, '_');
+        }
+        if self.reserved.contains(&base.as_str()) {
+            base.push('_');
+        }
+
+        let mut name = base.clone();
+        let mut suffix = 1;
+        while self.used.contains(&name) {
+            name = format!("{}_{}", base, suffix);
+            suffix += 1;
+        }
+        self.used.insert(name.clone());
+        name
+    }
+}
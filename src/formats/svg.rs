@@ -0,0 +1,196 @@
+use crate::types::circuit::*;
+use crate::types::keys::*;
+use crate::types::pin_type::*;
+use crate::utils::*;
+use macroquad::prelude::*;
+use quick_xml::escape::escape;
+use std::collections::HashSet;
+
+// room around the gates for the labels drawn above them
+const MARGIN: f32 = 32.0;
+const WIRE_WIDTH: f32 = 3.0;
+const LABEL_FONT_SIZE: u16 = 20;
+
+fn hex(color: Color) -> String {
+    let [r, g, b, _]: [u8; 4] = color.into();
+    format!("#{:02x}{:02x}{:02x}", r, g, b)
+}
+
+// the circuit drawn the way the simulator draws it, gates then wires then pins. with 'selection'
+// only those gates and the wires between them. with 'live' wires and pins carrying a 1 are
+// yellow like on screen, otherwise every wire is black
+pub fn export(circuit: &Circuit, selection: Option<&HashSet<GateKey>>, live: bool) -> String {
+    let included = |key: GateKey| selection.is_none_or(|selection| selection.contains(&key));
+    let gates: Vec<GateKey> = circuit.gates.keys().filter(|key| included(*key)).collect();
+    let bounds = gates
+        .iter()
+        .map(|key| circuit.gates[*key].rect)
+        .reduce(|bounds, rect| bounds.combine_with(rect))
+        .unwrap_or(Rect::new(0.0, 0.0, 0.0, 0.0));
+    let is_live = |wire: Option<WireKey>| {
+        live && wire.and_then(|wire| circuit.wires_read.get(wire)).copied().unwrap_or(false)
+    };
+
+    let mut svg = String::new();
+    svg.push_str(&format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"{} {} {} {}\" width=\"{}\" height=\"{}\">\n",
+        bounds.x - MARGIN,
+        bounds.y - MARGIN,
+        bounds.w + 2.0 * MARGIN,
+        bounds.h + 2.0 * MARGIN,
+        bounds.w + 2.0 * MARGIN,
+        bounds.h + 2.0 * MARGIN
+    ));
+    svg.push_str("  <g font-family=\"sans-serif\" text-anchor=\"middle\">\n");
+    for key in &gates {
+        let gate = &circuit.gates[*key];
+        let rect = gate.rect;
+        svg.push_str(&format!(
+            "    <rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"{}\"/>\n",
+            rect.x,
+            rect.y,
+            rect.w,
+            rect.h,
+            hex(gate.gate_type.color())
+        ));
+        svg.push_str(&format!(
+            "    <text x=\"{}\" y=\"{}\" font-size=\"{}\" dominant-baseline=\"central\">{}</text>\n",
+            rect.center().x,
+            rect.center().y,
+            FONT_SIZE,
+            escape(gate.gate_type.text())
+        ));
+        if let Some(label) = &gate.label {
+            svg.push_str(&format!(
+                "    <text x=\"{}\" y=\"{}\" font-size=\"{}\">{}</text>\n",
+                rect.center().x,
+                rect.y - 4.0,
+                LABEL_FONT_SIZE,
+                escape(label)
+            ));
+        }
+    }
+    svg.push_str("  </g>\n");
+
+    svg.push_str(&format!("  <g stroke-width=\"{}\">\n", WIRE_WIDTH));
+    for (wire_key, wire) in &circuit.wires {
+        if !included(wire.source.gate_index) {
+            continue;
+        }
+        let Some(source) = circuit.gates.get(wire.source.gate_index) else {
+            continue;
+        };
+        let start = source.get_pin_rect(wire.source.pin_index, PinType::Output).center();
+        let color = match is_live(Some(wire_key)) {
+            true => YELLOW,
+            false => BLACK,
+        };
+        for connection in &wire.connections {
+            if !included(connection.gate_index) {
+                continue;
+            }
+            let Some(gate) = circuit.gates.get(connection.gate_index) else {
+                continue;
+            };
+            let end = gate.get_pin_rect(connection.pin_index, PinType::Input).center();
+            svg.push_str(&format!(
+                "    <line x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\" stroke=\"{}\"/>\n",
+                start.x,
+                start.y,
+                end.x,
+                end.y,
+                hex(color)
+            ));
+        }
+    }
+    svg.push_str("  </g>\n");
+
+    svg.push_str("  <g>\n");
+    for key in &gates {
+        let gate = &circuit.gates[*key];
+        for pin in gate.input.iter().chain(&gate.output) {
+            let color = match is_live(pin.wire_index) {
+                true => BLACK.lerp(YELLOW, 0.4),
+                false => BLACK,
+            };
+            svg.push_str(&format!(
+                "    <rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"{}\"/>\n",
+                pin.rect.x,
+                pin.rect.y,
+                pin.rect.w,
+                pin.rect.h,
+                hex(color)
+            ));
+        }
+    }
+    svg.push_str("  </g>\n");
+    svg.push_str("</svg>\n");
+    svg
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::gate::*;
+    use crate::types::gate_type::*;
+
+    // the value of an attribute in one line of the svg
+    fn attribute<'a>(line: &'a str, name: &str) -> &'a str {
+        line.split(&format!(" {}=\"", name)).nth(1).unwrap().split('"').next().unwrap()
+    }
+
+    fn elements<'a>(svg: &'a str, tag: &str) -> Vec<&'a str> {
+        svg.lines().map(str::trim).filter(|line| line.starts_with(&format!("<{} ", tag))).collect()
+    }
+
+    // a -> NOT -> y, with a set
+    fn inverter() -> (Circuit, [GateKey; 3]) {
+        let mut circuit = Circuit::new();
+        let mut add = |gate_type: GateType, x: f32| {
+            circuit.gates.insert(Gate::new(Rect::new(x * 64.0, 0.0, 64.0, 64.0), Rotation::Up, gate_type))
+        };
+        let a = add(GateType::IN, 0.0);
+        let not = add(GateType::NOT, 1.0);
+        let y = add(GateType::OUT, 2.0);
+        circuit.connect_wire(a, not, 0, PinType::Output, 0, PinType::Input);
+        circuit.connect_wire(not, y, 0, PinType::Output, 0, PinType::Input);
+        circuit.gates[a].active = true;
+        circuit.settle(100);
+        (circuit, [a, not, y])
+    }
+
+    #[test]
+    fn wires_run_between_the_pins_drawn() {
+        let (circuit, _) = inverter();
+        let svg = export(&circuit, None, false);
+        let rects = elements(&svg, "rect");
+        // 3 gates and 4 pins
+        assert_eq!(rects.len(), 7, "{}", svg);
+        let centers: Vec<(f32, f32)> = rects
+            .iter()
+            .map(|rect| {
+                let value = |name: &str| attribute(rect, name).parse::<f32>().unwrap();
+                (value("x") + value("width") / 2.0, value("y") + value("height") / 2.0)
+            })
+            .collect();
+        let lines = elements(&svg, "line");
+        assert_eq!(lines.len(), 2, "{}", svg);
+        for line in lines {
+            let value = |name: &str| attribute(line, name).parse::<f32>().unwrap();
+            assert!(centers.contains(&(value("x1"), value("y1"))), "{}", line);
+            assert!(centers.contains(&(value("x2"), value("y2"))), "{}", line);
+            assert_eq!(attribute(line, "stroke"), hex(BLACK));
+        }
+    }
+
+    #[test]
+    fn live_wires_are_yellow_and_the_selection_drops_the_rest() {
+        let (circuit, [a, not, _]) = inverter();
+        let svg = export(&circuit, Some(&HashSet::from([a, not])), true);
+        assert_eq!(elements(&svg, "rect").len(), 2 + 3, "{}", svg);
+        let lines = elements(&svg, "line");
+        // the NOT output goes to a gate left out, and it's 0
+        assert_eq!(lines.len(), 1, "{}", svg);
+        assert_eq!(attribute(lines[0], "stroke"), hex(YELLOW));
+    }
+}
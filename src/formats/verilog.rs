@@ -0,0 +1,1258 @@
+use crate::analysis::subcircuit::Boundary;
+use crate::formats::Names;
+use crate::formats::graph::{Net, NetGraph};
+use crate::layout;
+use crate::types::circuit::*;
+use crate::types::gate_type::*;
+use crate::types::keys::*;
+use crate::types::netlist::*;
+use std::collections::{HashMap, HashSet};
+
+pub const KEYWORDS: &[&str] = &[
+    "module", "endmodule", "input", "output", "inout", "wire", "reg", "assign", "always", "begin", "end",
+    "if", "else", "initial", "parameter", "supply0", "supply1", "buf", "not", "and", "nand", "or", "nor",
+    "xor", "xnor",
+];
+
+fn primitive(gate_type: &GateType) -> Option<&'static str> {
+    match gate_type {
+        GateType::NOT => Some("not"),
+        GateType::OR => Some("or"),
+        GateType::XOR => Some("xor"),
+        GateType::NOR => Some("nor"),
+        GateType::XNOR => Some("xnor"),
+        GateType::AND => Some("and"),
+        GateType::NAND => Some("nand"),
+        _ => None,
+    }
+}
+
+fn primitive_type(name: &str) -> Option<GateType> {
+    [GateType::NOT, GateType::OR, GateType::XOR, GateType::NOR, GateType::XNOR, GateType::AND, GateType::NAND]
+        .into_iter()
+        .find(|gate_type| primitive(gate_type) == Some(name))
+}
+
+// structural verilog of the whole circuit, or of the selected gates when there's a selection.
+// IN / OUT gates become ports, and so do the wires crossing the edge of a selection (i0, i1 ...
+// going in, o0, o1 ... going out). a custom gate's member gates go into a submodule named after
+// its text, with its inputs / outputs wires as the ports. flip-flops use a behavioural dff module
+pub fn export(circuit: &Circuit, selection: Option<&HashSet<GateKey>>, module_name: &str) -> String {
+    let gates: HashSet<GateKey> = match selection {
+        Some(selection) => selection.clone(),
+        None => circuit.gates.keys().collect(),
+    };
+
+    let io_ports = |gate_type: GateType| -> Vec<(String, Option<WireKey>)> {
+        circuit
+            .io_names(gate_type.clone())
+            .into_iter()
+            .filter(|(key, _)| gates.contains(key))
+            .map(|(key, name)| {
+                let gate = &circuit.gates[key];
+                let wire = match gate_type {
+                    GateType::IN => gate.output[0].wire_index,
+                    _ => gate.input[0].wire_index,
+                };
+                (name, wire)
+            })
+            .collect()
+    };
+    let mut inputs = io_ports(GateType::IN);
+    let mut outputs = io_ports(GateType::OUT);
+    if selection.is_some() {
+        let boundary = Boundary::of(circuit, &gates);
+        inputs.extend(boundary.inputs.iter().enumerate().map(|(index, wire)| (format!("i{}", index), Some(*wire))));
+        outputs.extend(boundary.outputs.iter().enumerate().map(|(index, wire)| (format!("o{}", index), Some(*wire))));
+    }
+    let mut port_names = Names::new(KEYWORDS);
+    let mut ports = group_ports("input", inputs, &mut port_names);
+    ports.extend(group_ports("output", outputs, &mut port_names));
+
+    let logic: Vec<GateKey> = circuit
+        .gates
+        .keys()
+        .filter(|key| gates.contains(key))
+        .filter(|key| !matches!(circuit.gates[*key].gate_type, GateType::IN | GateType::OUT))
+        .collect();
+
+    let mut writer = ModuleWriter {
+        circuit,
+        module_names: Names::new(KEYWORDS),
+        modules: vec![],
+        dff: None,
+    };
+    let top = writer.module_names.unique(module_name);
+    writer.write_module(&top, &logic, &ports);
+    writer.modules.join("\n")
+}
+
+struct Port {
+    direction: &'static str,
+    name: String,
+    // (msb, lsb) of a vector port
+    range: Option<(i64, i64)>,
+    // what each bit is called inside the module and the wire it stands for
+    bits: Vec<(String, Option<WireKey>)>,
+}
+
+// IN / OUT gates labelled name[0], name[1] ... (how the importers name the bits of a vector port)
+// go back together into one vector port when the indices have no gaps
+fn group_ports(direction: &'static str, labels: Vec<(String, Option<WireKey>)>, names: &mut Names) -> Vec<Port> {
+    let split = |label: &str| -> Option<(String, i64)> {
+        let (base, index) = label.strip_suffix(']')?.split_once('[')?;
+        Some((base.to_string(), index.parse().ok()?))
+    };
+    let mut buses: HashMap<String, Vec<(i64, Option<WireKey>)>> = HashMap::new();
+    for (label, wire) in &labels {
+        if let Some((base, index)) = split(label) {
+            buses.entry(base).or_default().push((index, *wire));
+        }
+    }
+    buses.retain(|base, bits| {
+        bits.sort_by_key(|(index, _)| *index);
+        let contiguous = bits.windows(2).all(|pair| pair[1].0 == pair[0].0 + 1);
+        contiguous && !labels.iter().any(|(label, _)| label == base)
+    });
+
+    let mut ports = vec![];
+    let mut written = HashSet::new();
+    for (label, wire) in labels {
+        match split(&label).filter(|(base, _)| buses.contains_key(base)) {
+            // the first bit writes the whole vector
+            Some((base, _)) => {
+                if written.insert(base.clone()) {
+                    let bits = &buses[&base];
+                    let name = names.unique(&base);
+                    ports.push(Port {
+                        direction,
+                        range: Some((bits[bits.len() - 1].0, bits[0].0)),
+                        bits: bits.iter().map(|(index, wire)| (format!("{}[{}]", name, index), *wire)).collect(),
+                        name,
+                    });
+                }
+            }
+            None => {
+                let name = names.unique(&label);
+                ports.push(Port { direction, name: name.clone(), range: None, bits: vec![(name, wire)] });
+            }
+        }
+    }
+    ports
+}
+
+struct ModuleWriter<'a> {
+    circuit: &'a Circuit,
+    module_names: Names,
+    // every module written so far, submodules come before the modules using them
+    modules: Vec<String>,
+    // name of the flip-flop module once one was needed
+    dff: Option<String>,
+}
+
+// nets of one module, wires get a name the first time they're used
+struct Nets {
+    names: Names,
+    nets: HashMap<WireKey, String>,
+    declarations: Vec<String>,
+}
+
+impl Nets {
+    fn net(&mut self, wire: Option<WireKey>) -> String {
+        let Some(wire) = wire else {
+            let name = self.names.unique("nc");
+            self.declarations.push(format!("    wire {};\n", name));
+            return name;
+        };
+        if let Some(name) = self.nets.get(&wire) {
+            return name.clone();
+        }
+        let name = self.names.unique(&format!("w{}", self.declarations.len()));
+        self.declarations.push(format!("    wire {};\n", name));
+        self.nets.insert(wire, name.clone());
+        name
+    }
+
+    // an unconnected input reads 0 like in the simulator
+    fn input(&mut self, wire: Option<WireKey>) -> String {
+        match wire {
+            Some(_) => self.net(wire),
+            None => "1'b0".to_string(),
+        }
+    }
+}
+
+impl ModuleWriter<'_> {
+    // flip-flops are instances of a small behavioural module, written the first time it's used
+    fn dff_module(&mut self) -> String {
+        if let Some(name) = &self.dff {
+            return name.clone();
+        }
+        let name = self.module_names.unique("dff");
+        self.modules.push(format!(
+            "module {} (\n    input d,\n    input clk,\n    output reg q\n);\n    always @(posedge clk) q <= d;\nendmodule\n",
+            name
+        ));
+        self.dff = Some(name.clone());
+        name
+    }
+
+    fn write_module(
+        &mut self,
+        name: &str,
+        gates: &[GateKey],
+        ports: &[Port],
+    ) {
+        let mut nets = Nets {
+            names: Names::new(KEYWORDS),
+            nets: HashMap::new(),
+            declarations: vec![],
+        };
+        let mut statements = vec![];
+
+        let mut declarations = vec![];
+        for port in ports {
+            nets.names.unique(&port.name);
+            declarations.push(match port.range {
+                Some((msb, lsb)) => format!("    {} [{}:{}] {}", port.direction, msb, lsb, port.name),
+                None => format!("    {} {}", port.direction, port.name),
+            });
+            if port.direction == "input" {
+                for (bit, wire) in &port.bits {
+                    if let Some(wire) = wire {
+                        nets.nets.entry(*wire).or_insert(bit.clone());
+                    }
+                }
+            }
+        }
+
+        // gates inside a custom gate are written by its submodule
+        let members: HashSet<GateKey> = gates
+            .iter()
+            .filter_map(|key| match &self.circuit.gates[*key].gate_type {
+                GateType::CUSTOM { gates, .. } => Some(gates.iter().copied()),
+                _ => None,
+            })
+            .flatten()
+            .collect();
+
+        // instances share the module's namespace with the ports and nets
+        for (index, key) in gates.iter().filter(|key| !members.contains(key)).enumerate() {
+            let gate = &self.circuit.gates[*key];
+            let instance = match &gate.label {
+                Some(label) => nets.names.unique(label),
+                None => nets.names.unique(&format!("g{}", index)),
+            };
+
+            if let GateType::CUSTOM { gates: custom_gates, text, inputs: custom_inputs, outputs: custom_outputs, .. } = &gate.gate_type {
+                let port = |direction: &'static str, name: String, wire: WireKey| Port {
+                    direction,
+                    name: name.clone(),
+                    range: None,
+                    bits: vec![(name, Some(wire))],
+                };
+                let sub_ports: Vec<Port> = custom_inputs
+                    .iter()
+                    .enumerate()
+                    .map(|(index, wire)| port("input", format!("i{}", index), *wire))
+                    .chain(custom_outputs.iter().enumerate().map(|(index, wire)| port("output", format!("o{}", index), *wire)))
+                    .collect();
+                let sub_gates: Vec<GateKey> = custom_gates
+                    .iter()
+                    .copied()
+                    .filter(|key| {
+                        self.circuit
+                            .gates
+                            .get(*key)
+                            .is_some_and(|gate| !matches!(gate.gate_type, GateType::IN | GateType::OUT))
+                    })
+                    .collect();
+                let sub_name = self.module_names.unique(text);
+                self.write_module(&sub_name, &sub_gates, &sub_ports);
+
+                let mut connections = vec![];
+                for port in &sub_ports {
+                    let wire = port.bits[0].1;
+                    let net = match port.direction {
+                        "input" => nets.input(wire),
+                        _ => nets.net(wire),
+                    };
+                    connections.push(format!(".{}({})", port.name, net));
+                }
+                statements.push(format!("    {} {} ({});\n", sub_name, instance, connections.join(", ")));
+                continue;
+            }
+
+            if gate.gate_type == GateType::DFF {
+                let module = self.dff_module();
+                let d = nets.input(gate.input[0].wire_index);
+                let clock = nets.input(gate.input[1].wire_index);
+                let q = nets.net(gate.output[0].wire_index);
+                statements.push(format!("    {} {} (.d({}), .clk({}), .q({}));\n", module, instance, d, clock, q));
+                continue;
+            }
+
+            let Some(primitive) = primitive(&gate.gate_type) else {
+                continue;
+            };
+            let mut terminals = vec![nets.net(gate.output[0].wire_index)];
+            terminals.extend(gate.input.iter().map(|pin| nets.input(pin.wire_index)));
+            statements.push(format!("    {} {} ({});\n", primitive, instance, terminals.join(", ")));
+        }
+
+        for port in ports.iter().filter(|port| port.direction == "output") {
+            for (bit, wire) in &port.bits {
+                statements.push(format!("    assign {} = {};\n", bit, nets.input(*wire)));
+            }
+        }
+
+        let mut text = match declarations.is_empty() {
+            true => format!("module {} ();\n", name),
+            false => format!("module {} (\n{}\n);\n", name, declarations.join(",\n")),
+        };
+        text += &nets.declarations.concat();
+        if !nets.declarations.is_empty() {
+            text += "\n";
+        }
+        text += &statements.concat();
+        text += "endmodule\n";
+        self.modules.push(text);
+    }
+}
+
+// ---- import ----
+
+// a gate-level subset: modules with scalar or vector ports and wires, the primitives above plus
+// buf, assign with ~ & | ^ expressions, and instances of the other modules in the file, which
+// are flattened into the top module (the one nothing instantiates, the last one if there are
+// several)
+pub fn import(text: &str) -> Result<Circuit, String> {
+    Ok(layout::build(&parse(text)?))
+}
+
+pub fn parse(text: &str) -> Result<Netlist, String> {
+    let mut parser = Parser {
+        tokens: tokenize(text)?,
+        position: 0,
+    };
+    let mut modules: HashMap<String, Module> = HashMap::new();
+    let mut order = vec![];
+    while !parser.at_end() {
+        let module = parser.module()?;
+        if modules.contains_key(&module.name) {
+            return Err(format!("line {}: module {} is defined twice", module.line, module.name));
+        }
+        order.push(module.name.clone());
+        modules.insert(module.name.clone(), module);
+    }
+
+    let instantiated: HashSet<&String> = modules
+        .values()
+        .flat_map(|module| &module.items)
+        .filter_map(|item| match item {
+            Item::Instance { module, .. } => Some(module),
+            _ => None,
+        })
+        .collect();
+    let top = order
+        .iter()
+        .rev()
+        .find(|name| !instantiated.contains(name))
+        .ok_or("no top module, every module is instantiated by another one")?;
+
+    let mut elaborator = Elaborator {
+        modules: &modules,
+        graph: NetGraph::new(),
+        stack: vec![],
+    };
+    let scope = elaborator.module(top, top, &HashMap::new())?;
+    elaborator.netlist(&modules[top], &scope)
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Name(String),
+    Number(String),
+    Symbol(&'static str),
+}
+
+const SYMBOLS: &[&str] = &[
+    "~^", "^~", "~&", "~|", "(", ")", ",", ";", ".", "=", "[", "]", ":", "{", "}", "~", "!", "&", "|", "^", "#",
+];
+
+fn tokenize(text: &str) -> Result<Vec<(Token, usize)>, String> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut tokens = vec![];
+    let mut line = 1;
+    let mut i = 0;
+    let skip_until = |i: &mut usize, line: &mut usize, end: &str| {
+        while *i < chars.len() && !chars[*i..].starts_with(&end.chars().collect::<Vec<_>>()) {
+            if chars[*i] == '\n' {
+                *line += 1;
+            }
+            *i += 1;
+        }
+        *i += end.len();
+    };
+
+    while i < chars.len() {
+        let c = chars[i];
+        let rest: String = chars[i..chars.len().min(i + 2)].iter().collect();
+        if c == '\n' {
+            line += 1;
+            i += 1;
+        } else if c.is_whitespace() {
+            i += 1;
+        } else if rest == "//" {
+            skip_until(&mut i, &mut line, "\n");
+            line += 1;
+        } else if rest == "/*" {
+            skip_until(&mut i, &mut line, "*/");
+        } else if rest == "(*" {
+            // attributes, yosys puts them everywhere
+            skip_until(&mut i, &mut line, "*)");
+        } else if c == '`' {
+            return Err(format!("line {}: compiler directives aren't supported", line));
+        } else if c.is_ascii_alphabetic() || c == '_' || c == '\\' {
+            let start = i;
+            if c == '\\' {
+                // escaped names run until whitespace
+                i += 1;
+                while i < chars.len() && !chars[i].is_whitespace() {
+                    i += 1;
+                }
+                tokens.push((Token::Name(chars[start + 1..i].iter().collect()), line));
+                continue;
+            }
+            while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '_' || chars[i] == '$') {
+                i += 1;
+            }
+            tokens.push((Token::Name(chars[start..i].iter().collect()), line));
+        } else if c.is_ascii_digit() || c == '\'' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '_' || chars[i] == '\'') {
+                i += 1;
+            }
+            tokens.push((Token::Number(chars[start..i].iter().collect()), line));
+        } else if let Some(symbol) = SYMBOLS.iter().find(|symbol| rest.starts_with(**symbol)) {
+            i += symbol.len();
+            tokens.push((Token::Symbol(symbol), line));
+        } else {
+            return Err(format!("line {}: unexpected '{}'", line, c));
+        }
+    }
+    Ok(tokens)
+}
+
+// bits of a number, lsb first. x and z read as 0 like a floating pin
+fn number_bits(text: &str, line: usize) -> Result<Vec<bool>, String> {
+    let error = || format!("line {}: bad number '{}'", line, text);
+    let text = text.replace('_', "");
+    let (width, base, digits) = match text.split_once('\'') {
+        Some((width, rest)) => {
+            let width = match width {
+                "" => None,
+                _ => Some(width.parse::<usize>().map_err(|_| error())?),
+            };
+            let mut rest = rest.chars();
+            let base = match rest.next().map(|c| c.to_ascii_lowercase()) {
+                Some('b') => 2u32,
+                Some('o') => 8,
+                Some('d') => 10,
+                Some('h') => 16,
+                _ => return Err(error()),
+            };
+            (width, base, rest.as_str().to_ascii_lowercase())
+        }
+        None => (None, 10, text.clone()),
+    };
+    if digits.is_empty() {
+        return Err(error());
+    }
+
+    let mut bits = vec![];
+    if base == 10 {
+        let mut value: u64 = digits.parse().map_err(|_| error())?;
+        while value > 0 {
+            bits.push(value & 1 == 1);
+            value >>= 1;
+        }
+    } else {
+        let digit_bits = base.trailing_zeros() as usize;
+        for digit in digits.chars().rev() {
+            let value = match digit {
+                'x' | 'z' | '?' => 0,
+                _ => digit.to_digit(base).ok_or_else(error)?,
+            };
+            bits.extend((0..digit_bits).map(|bit| value >> bit & 1 == 1));
+        }
+    }
+    // unsized numbers are 32 bits wide
+    bits.resize(width.unwrap_or(32), false);
+    Ok(bits)
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Direction {
+    Input,
+    Output,
+    Wire,
+}
+
+struct Declaration {
+    direction: Direction,
+    name: String,
+    // (msb, lsb), None for a single bit
+    range: Option<(i64, i64)>,
+    line: usize,
+}
+
+enum Expr {
+    Name(String),
+    Bit(String, i64, usize),
+    Slice(String, i64, i64, usize),
+    Constant(Vec<bool>),
+    // msb part first, like it's written
+    Concat(Vec<Expr>),
+    Not(Box<Expr>),
+    // AND, OR, XOR or XNOR
+    Binary(GateType, Box<Expr>, Box<Expr>),
+}
+
+enum Connections {
+    Named(Vec<(String, Option<Expr>)>),
+    Ordered(Vec<Option<Expr>>),
+}
+
+enum Item {
+    Primitive { primitive: String, terminals: Vec<Expr>, line: usize },
+    Assign { target: Expr, value: Expr, line: usize },
+    Instance { module: String, name: String, connections: Connections, line: usize },
+}
+
+struct Module {
+    name: String,
+    ports: Vec<String>,
+    declarations: Vec<Declaration>,
+    items: Vec<Item>,
+    line: usize,
+}
+
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    position: usize,
+}
+
+impl Parser {
+    fn at_end(&self) -> bool {
+        self.position >= self.tokens.len()
+    }
+
+    fn line(&self) -> usize {
+        match self.tokens.get(self.position).or(self.tokens.last()) {
+            Some((_, line)) => *line,
+            None => 1,
+        }
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position).map(|(token, _)| token)
+    }
+
+    fn next(&mut self) -> Result<Token, String> {
+        let token = self.peek().cloned().ok_or_else(|| format!("line {}: unexpected end of file", self.line()))?;
+        self.position += 1;
+        Ok(token)
+    }
+
+    fn is_symbol(&self, symbol: &str) -> bool {
+        matches!(self.peek(), Some(Token::Symbol(s)) if *s == symbol)
+    }
+
+    fn is_name(&self, name: &str) -> bool {
+        matches!(self.peek(), Some(Token::Name(n)) if n == name)
+    }
+
+    fn eat(&mut self, symbol: &str) -> bool {
+        let found = self.is_symbol(symbol);
+        if found {
+            self.position += 1;
+        }
+        found
+    }
+
+    fn expect(&mut self, symbol: &str) -> Result<(), String> {
+        match self.eat(symbol) {
+            true => Ok(()),
+            false => Err(format!("line {}: expected '{}'", self.line(), symbol)),
+        }
+    }
+
+    fn name(&mut self) -> Result<String, String> {
+        let line = self.line();
+        match self.next()? {
+            Token::Name(name) => Ok(name),
+            _ => Err(format!("line {}: expected a name", line)),
+        }
+    }
+
+    fn integer(&mut self) -> Result<i64, String> {
+        let line = self.line();
+        match self.next()? {
+            Token::Number(text) => text.parse().map_err(|_| format!("line {}: expected an index", line)),
+            _ => Err(format!("line {}: expected an index", line)),
+        }
+    }
+
+    fn range(&mut self) -> Result<Option<(i64, i64)>, String> {
+        if !self.eat("[") {
+            return Ok(None);
+        }
+        let msb = self.integer()?;
+        self.expect(":")?;
+        let lsb = self.integer()?;
+        self.expect("]")?;
+        Ok(Some((msb, lsb)))
+    }
+
+    fn module(&mut self) -> Result<Module, String> {
+        let line = self.line();
+        if self.name()? != "module" {
+            return Err(format!("line {}: expected 'module'", line));
+        }
+        let mut module = Module {
+            name: self.name()?,
+            ports: vec![],
+            declarations: vec![],
+            items: vec![],
+            line,
+        };
+        if self.is_symbol("#") {
+            return Err(format!("line {}: parameters aren't supported", self.line()));
+        }
+
+        if self.eat("(") && !self.eat(")") {
+            let ansi = self.is_name("input") || self.is_name("output") || self.is_name("inout");
+            let mut direction = None;
+            let mut range = None;
+            loop {
+                if ansi && let Some(new_direction) = self.direction()? {
+                    direction = Some(new_direction);
+                    self.eat_name("wire");
+                    range = self.range()?;
+                }
+                let line = self.line();
+                let name = self.name()?;
+                if let Some(direction) = direction {
+                    module.declarations.push(Declaration { direction, name: name.clone(), range, line });
+                }
+                module.ports.push(name);
+                if !self.eat(",") {
+                    break;
+                }
+            }
+            self.expect(")")?;
+        }
+        self.expect(";")?;
+
+        while !self.is_name("endmodule") {
+            self.statement(&mut module)?;
+        }
+        self.position += 1;
+        Ok(module)
+    }
+
+    fn eat_name(&mut self, name: &str) -> bool {
+        let found = self.is_name(name);
+        if found {
+            self.position += 1;
+        }
+        found
+    }
+
+    fn direction(&mut self) -> Result<Option<Direction>, String> {
+        let direction = match self.peek() {
+            Some(Token::Name(name)) if name == "input" => Direction::Input,
+            Some(Token::Name(name)) if name == "output" => Direction::Output,
+            Some(Token::Name(name)) if name == "inout" => {
+                return Err(format!("line {}: inout ports aren't supported", self.line()));
+            }
+            _ => return Ok(None),
+        };
+        self.position += 1;
+        Ok(Some(direction))
+    }
+
+    fn statement(&mut self, module: &mut Module) -> Result<(), String> {
+        let line = self.line();
+        let keyword = self.name()?;
+        match keyword.as_str() {
+            "input" | "output" | "wire" => {
+                let direction = match keyword.as_str() {
+                    "input" => Direction::Input,
+                    "output" => Direction::Output,
+                    _ => Direction::Wire,
+                };
+                self.eat_name("wire");
+                let range = self.range()?;
+                loop {
+                    let line = self.line();
+                    let name = self.name()?;
+                    module.declarations.push(Declaration { direction, name: name.clone(), range, line });
+                    // wire w = expression;
+                    if self.eat("=") {
+                        let target = match range {
+                            Some((msb, lsb)) => Expr::Slice(name, msb, lsb, line),
+                            None => Expr::Name(name),
+                        };
+                        module.items.push(Item::Assign { target, value: self.expression()?, line });
+                    }
+                    if !self.eat(",") {
+                        break;
+                    }
+                }
+                self.expect(";")
+            }
+            "assign" => {
+                loop {
+                    let line = self.line();
+                    let target = self.primary()?;
+                    self.expect("=")?;
+                    module.items.push(Item::Assign { target, value: self.expression()?, line });
+                    if !self.eat(",") {
+                        break;
+                    }
+                }
+                self.expect(";")
+            }
+            "and" | "or" | "xor" | "nand" | "nor" | "xnor" | "not" | "buf" => {
+                loop {
+                    if let Some(Token::Name(_)) = self.peek() {
+                        self.name()?;
+                    }
+                    self.expect("(")?;
+                    let mut terminals = vec![self.expression()?];
+                    while self.eat(",") {
+                        terminals.push(self.expression()?);
+                    }
+                    self.expect(")")?;
+                    module.items.push(Item::Primitive { primitive: keyword.clone(), terminals, line });
+                    if !self.eat(",") {
+                        break;
+                    }
+                }
+                self.expect(";")
+            }
+            "inout" => Err(format!("line {}: inout ports aren't supported", line)),
+            "reg" | "always" | "initial" | "function" | "task" | "generate" | "parameter" | "localparam" => {
+                Err(format!("line {}: '{}' isn't structural verilog", line, keyword))
+            }
+            _ => {
+                if self.is_symbol("#") {
+                    return Err(format!("line {}: parameters aren't supported", line));
+                }
+                loop {
+                    let line = self.line();
+                    let name = self.name()?;
+                    self.expect("(")?;
+                    let connections = self.connections()?;
+                    self.expect(")")?;
+                    module.items.push(Item::Instance { module: keyword.clone(), name, connections, line });
+                    if !self.eat(",") {
+                        break;
+                    }
+                }
+                self.expect(";")
+            }
+        }
+    }
+
+    fn connections(&mut self) -> Result<Connections, String> {
+        if self.is_symbol(")") {
+            return Ok(Connections::Ordered(vec![]));
+        }
+        if self.is_symbol(".") {
+            let mut connections = vec![];
+            loop {
+                self.expect(".")?;
+                let port = self.name()?;
+                self.expect("(")?;
+                let expr = match self.is_symbol(")") {
+                    true => None,
+                    false => Some(self.expression()?),
+                };
+                self.expect(")")?;
+                connections.push((port, expr));
+                if !self.eat(",") {
+                    return Ok(Connections::Named(connections));
+                }
+            }
+        }
+        let mut connections = vec![];
+        loop {
+            connections.push(match self.is_symbol(",") || self.is_symbol(")") {
+                true => None,
+                false => Some(self.expression()?),
+            });
+            if !self.eat(",") {
+                return Ok(Connections::Ordered(connections));
+            }
+        }
+    }
+
+    // | binds loosest, then ^ and ~^, then &
+    fn expression(&mut self) -> Result<Expr, String> {
+        let mut expr = self.xor_term()?;
+        while self.eat("|") {
+            expr = Expr::Binary(GateType::OR, Box::new(expr), Box::new(self.xor_term()?));
+        }
+        Ok(expr)
+    }
+
+    fn xor_term(&mut self) -> Result<Expr, String> {
+        let mut expr = self.and_term()?;
+        loop {
+            let gate_type = if self.eat("^") {
+                GateType::XOR
+            } else if self.eat("~^") || self.eat("^~") {
+                GateType::XNOR
+            } else {
+                return Ok(expr);
+            };
+            expr = Expr::Binary(gate_type, Box::new(expr), Box::new(self.and_term()?));
+        }
+    }
+
+    fn and_term(&mut self) -> Result<Expr, String> {
+        let mut expr = self.unary()?;
+        while self.eat("&") {
+            expr = Expr::Binary(GateType::AND, Box::new(expr), Box::new(self.unary()?));
+        }
+        Ok(expr)
+    }
+
+    fn unary(&mut self) -> Result<Expr, String> {
+        if self.eat("~") || self.eat("!") {
+            return Ok(Expr::Not(Box::new(self.unary()?)));
+        }
+        if self.is_symbol("~&") || self.is_symbol("~|") || self.is_symbol("&") || self.is_symbol("|") {
+            return Err(format!("line {}: reduction operators aren't supported", self.line()));
+        }
+        self.primary()
+    }
+
+    fn primary(&mut self) -> Result<Expr, String> {
+        let line = self.line();
+        match self.next()? {
+            Token::Symbol("(") => {
+                let expr = self.expression()?;
+                self.expect(")")?;
+                Ok(expr)
+            }
+            Token::Symbol("{") => {
+                let mut parts = vec![self.expression()?];
+                while self.eat(",") {
+                    parts.push(self.expression()?);
+                }
+                self.expect("}")?;
+                Ok(Expr::Concat(parts))
+            }
+            Token::Number(text) => Ok(Expr::Constant(number_bits(&text, line)?)),
+            Token::Name(name) => {
+                if !self.eat("[") {
+                    return Ok(Expr::Name(name));
+                }
+                let msb = self.integer()?;
+                let expr = match self.eat(":") {
+                    true => Expr::Slice(name, msb, self.integer()?, line),
+                    false => Expr::Bit(name, msb, line),
+                };
+                self.expect("]")?;
+                Ok(expr)
+            }
+            _ => Err(format!("line {}: expected an expression", line)),
+        }
+    }
+}
+
+// bits lsb first, with the declared (msb, lsb) to find them by index
+struct Signal {
+    bits: Vec<Net>,
+    msb: i64,
+    lsb: i64,
+}
+
+impl Signal {
+    fn bit(&self, index: i64) -> Option<Net> {
+        let position = match self.msb >= self.lsb {
+            true => index - self.lsb,
+            false => self.lsb - index,
+        };
+        usize::try_from(position).ok().and_then(|position| self.bits.get(position).copied())
+    }
+}
+
+// flattens the module hierarchy into the graph, assign and buf merge nets
+struct Elaborator<'a> {
+    modules: &'a HashMap<String, Module>,
+    graph: NetGraph,
+    // modules being elaborated, catches a module instantiating itself
+    stack: Vec<String>,
+}
+
+impl Elaborator<'_> {
+    fn signal<'s>(&mut self, scope: &'s mut HashMap<String, Signal>, prefix: &str, name: &str) -> &'s Signal {
+        // undeclared names are implicit single bit wires
+        if !scope.contains_key(name) {
+            let net = self.graph.net(format!("{}.{}", prefix, name));
+            scope.insert(name.to_string(), Signal { bits: vec![net], msb: 0, lsb: 0 });
+        }
+        &scope[name]
+    }
+
+    // nets an expression reads, lsb first. operators add gates
+    fn value(&mut self, scope: &mut HashMap<String, Signal>, prefix: &str, expr: &Expr) -> Result<Vec<Net>, String> {
+        match expr {
+            Expr::Constant(bits) => Ok(bits.iter().map(|bit| self.graph.constant(*bit)).collect()),
+            Expr::Concat(parts) => {
+                let mut bits = vec![];
+                for part in parts.iter().rev() {
+                    bits.extend(self.value(scope, prefix, part)?);
+                }
+                Ok(bits)
+            }
+            Expr::Not(inner) => {
+                let bits = self.value(scope, prefix, inner)?;
+                Ok(bits
+                    .into_iter()
+                    .map(|bit| {
+                        let output = self.graph.net(format!("{}.~", prefix));
+                        self.graph.gate(GateType::NOT, &[bit], output);
+                        output
+                    })
+                    .collect())
+            }
+            Expr::Binary(gate_type, a, b) => {
+                let mut a = self.value(scope, prefix, a)?;
+                let mut b = self.value(scope, prefix, b)?;
+                // the narrower side is zero extended
+                let width = a.len().max(b.len());
+                let zero = self.graph.constant(false);
+                a.resize(width, zero);
+                b.resize(width, zero);
+                Ok(a.into_iter()
+                    .zip(b)
+                    .map(|(a, b)| {
+                        let output = self.graph.net(format!("{}.{}", prefix, gate_type.text()));
+                        self.graph.gate(gate_type.clone(), &[a, b], output);
+                        output
+                    })
+                    .collect())
+            }
+            _ => self.target(scope, prefix, expr),
+        }
+    }
+
+    // nets an expression names, what assign and output ports can drive
+    fn target(&mut self, scope: &mut HashMap<String, Signal>, prefix: &str, expr: &Expr) -> Result<Vec<Net>, String> {
+        match expr {
+            Expr::Name(name) => Ok(self.signal(scope, prefix, name).bits.clone()),
+            Expr::Bit(name, index, line) => match self.signal(scope, prefix, name).bit(*index) {
+                Some(net) => Ok(vec![net]),
+                None => Err(format!("line {}: {}[{}] is out of range", line, name, index)),
+            },
+            Expr::Slice(name, msb, lsb, line) => {
+                let signal = self.signal(scope, prefix, name);
+                let (low, high) = (*msb.min(lsb), *msb.max(lsb));
+                let mut bits: Vec<Net> = (low..=high)
+                    .map(|index| signal.bit(index).ok_or(format!("line {}: {}[{}] is out of range", line, name, index)))
+                    .collect::<Result<_, _>>()?;
+                if (msb >= lsb) != (signal.msb >= signal.lsb) {
+                    bits.reverse();
+                }
+                Ok(bits)
+            }
+            Expr::Concat(parts) => {
+                let mut bits = vec![];
+                for part in parts.iter().rev() {
+                    bits.extend(self.target(scope, prefix, part)?);
+                }
+                Ok(bits)
+            }
+            _ => Err("only names can be assigned to".to_string()),
+        }
+    }
+
+    fn single(&mut self, scope: &mut HashMap<String, Signal>, prefix: &str, expr: &Expr, line: usize) -> Result<Net, String> {
+        if let Expr::Constant(bits) = expr {
+            return Ok(self.graph.constant(bits[0]));
+        }
+        match self.value(scope, prefix, expr)?.as_slice() {
+            [net] => Ok(*net),
+            _ => Err(format!("line {}: gate terminals are single bits", line)),
+        }
+    }
+
+    // elaborates a module, ports found in 'bindings' use the nets given there. returns every signal
+    fn module(&mut self, name: &str, prefix: &str, bindings: &HashMap<String, Vec<Net>>) -> Result<HashMap<String, Signal>, String> {
+        let modules = self.modules;
+        let module = &modules[name];
+        if self.stack.iter().any(|other| other == name) {
+            return Err(format!("module {} instantiates itself", name));
+        }
+        self.stack.push(name.to_string());
+
+        let mut scope: HashMap<String, Signal> = HashMap::new();
+        for declaration in &module.declarations {
+            let (msb, lsb) = declaration.range.unwrap_or((0, 0));
+            let width = (msb - lsb).unsigned_abs() as usize + 1;
+            // non ansi ports can be declared again as wires
+            if let Some(signal) = scope.get(&declaration.name) {
+                if signal.bits.len() != width {
+                    return Err(format!("line {}: {} is declared with two widths", declaration.line, declaration.name));
+                }
+                continue;
+            }
+            if declaration.direction != Direction::Wire && !module.ports.contains(&declaration.name) {
+                return Err(format!("line {}: {} isn't in the port list", declaration.line, declaration.name));
+            }
+            let bits = match bindings.get(&declaration.name) {
+                Some(bits) => bits.clone(),
+                None => (0..width)
+                    .map(|bit| match declaration.range {
+                        Some(_) => self.graph.net(format!("{}.{}[{}]", prefix, declaration.name, bit)),
+                        None => self.graph.net(format!("{}.{}", prefix, declaration.name)),
+                    })
+                    .collect(),
+            };
+            scope.insert(declaration.name.clone(), Signal { bits, msb, lsb });
+        }
+        for port in &module.ports {
+            let declared = module.declarations.iter().any(|d| &d.name == port && d.direction != Direction::Wire);
+            if !declared {
+                return Err(format!("line {}: port {} of {} has no direction", module.line, port, name));
+            }
+        }
+
+        for item in &module.items {
+            match item {
+                Item::Primitive { primitive, terminals, line } => {
+                    let nets: Vec<Net> = terminals
+                        .iter()
+                        .map(|terminal| self.single(&mut scope, prefix, terminal, *line))
+                        .collect::<Result<_, _>>()?;
+                    if nets.len() < 2 {
+                        return Err(format!("line {}: {} needs an output and an input", line, primitive));
+                    }
+                    match primitive.as_str() {
+                        // not and buf drive every terminal but the last from the last one
+                        "not" | "buf" => {
+                            let (outputs, input) = nets.split_at(nets.len() - 1);
+                            for output in outputs {
+                                match primitive.as_str() {
+                                    "not" => self.graph.gate(GateType::NOT, input, *output),
+                                    _ => self.graph.merge(*output, input[0]),
+                                }
+                            }
+                        }
+                        _ => {
+                            let gate_type = primitive_type(primitive).unwrap();
+                            self.graph.reduce(gate_type, &nets[1..], nets[0]);
+                        }
+                    }
+                }
+                Item::Assign { target, value, line } => {
+                    let targets = self.target(&mut scope, prefix, target).map_err(|e| format!("line {}: {}", line, e))?;
+                    let mut values = self.value(&mut scope, prefix, value)?;
+                    let zero = self.graph.constant(false);
+                    values.resize(targets.len(), zero);
+                    for (target, value) in targets.into_iter().zip(values) {
+                        self.graph.merge(target, value);
+                    }
+                }
+                Item::Instance { module: sub_name, name: instance, connections, line } => {
+                    let Some(sub) = modules.get(sub_name) else {
+                        return Err(format!("line {}: unknown module or gate '{}'", line, sub_name));
+                    };
+                    let connections: Vec<(&String, &Expr)> = match connections {
+                        Connections::Named(named) => {
+                            let mut connections = vec![];
+                            for (port, expr) in named {
+                                if !sub.ports.contains(port) {
+                                    return Err(format!("line {}: {} has no port {}", line, sub_name, port));
+                                }
+                                if let Some(expr) = expr {
+                                    connections.push((port, expr));
+                                }
+                            }
+                            connections
+                        }
+                        Connections::Ordered(ordered) => {
+                            if ordered.len() > sub.ports.len() {
+                                return Err(format!("line {}: {} has {} ports", line, sub_name, sub.ports.len()));
+                            }
+                            sub.ports
+                                .iter()
+                                .zip(ordered)
+                                .filter_map(|(port, expr)| expr.as_ref().map(|expr| (port, expr)))
+                                .collect()
+                        }
+                    };
+
+                    let mut bindings = HashMap::new();
+                    for (port, expr) in connections {
+                        let declaration = sub.declarations.iter().find(|d| &d.name == port && d.direction != Direction::Wire);
+                        let Some(declaration) = declaration else {
+                            return Err(format!("line {}: port {} of {} has no direction", line, port, sub_name));
+                        };
+                        let (msb, lsb) = declaration.range.unwrap_or((0, 0));
+                        let width = (msb - lsb).unsigned_abs() as usize + 1;
+                        let mut bits = match declaration.direction {
+                            Direction::Input => self.value(&mut scope, prefix, expr)?,
+                            _ => self.target(&mut scope, prefix, expr).map_err(|e| format!("line {}: {}", line, e))?,
+                        };
+                        if bits.len() != width {
+                            if declaration.direction == Direction::Output {
+                                return Err(format!("line {}: port {} of {} is {} bits wide", line, port, sub_name, width));
+                            }
+                            let zero = self.graph.constant(false);
+                            bits.resize(width, zero);
+                        }
+                        bindings.insert(port.clone(), bits);
+                    }
+                    self.module(sub_name, &format!("{}.{}", prefix, instance), &bindings)?;
+                }
+            }
+        }
+
+        self.stack.pop();
+        Ok(scope)
+    }
+
+    // the top module's ports become IN / OUT gates, vectors one per bit named name[index]
+    fn netlist(self, top: &Module, scope: &HashMap<String, Signal>) -> Result<Netlist, String> {
+        let mut inputs = vec![];
+        let mut outputs = vec![];
+        for port in &top.ports {
+            let signal = &scope[port];
+            // non-ansi ports can also be declared as a wire, that one doesn't say which way it goes
+            let declaration = top.declarations.iter().find(|d| &d.name == port && d.direction != Direction::Wire);
+            let Some(declaration) = declaration else {
+                return Err(format!("port {} of {} has no direction", port, top.name));
+            };
+            let direction = declaration.direction;
+            for (position, net) in signal.bits.iter().enumerate() {
+                let label = match signal.bits.len() {
+                    1 => port.clone(),
+                    _ => {
+                        let index = match signal.msb >= signal.lsb {
+                            true => signal.lsb + position as i64,
+                            false => signal.lsb - position as i64,
+                        };
+                        format!("{}[{}]", port, index)
+                    }
+                };
+                match direction {
+                    Direction::Input => inputs.push((label, *net)),
+                    _ => outputs.push((label, *net)),
+                }
+            }
+        }
+        self.graph.netlist(&inputs, &outputs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::equivalence::{self, Equivalence, Matching};
+    use crate::analysis::truth_table::TruthTable;
+    use crate::types::gate::*;
+    use crate::types::pin_type::*;
+    use macroquad::prelude::Rect;
+
+    #[test]
+    fn ports_also_declared_as_wires_keep_their_direction() {
+        let circuit = import("module t(a, y);\n  wire a;\n  input a;\n  output y;\n  not (y, a);\nendmodule\n").unwrap();
+        let names = |gate_type: GateType| -> Vec<String> {
+            circuit.io_names(gate_type).into_iter().map(|(_, name)| name).collect()
+        };
+        assert_eq!(names(GateType::IN), ["a"]);
+        assert_eq!(names(GateType::OUT), ["y"]);
+    }
+
+    #[test]
+    fn instances_dont_reuse_port_or_net_names() {
+        let mut circuit = Circuit::new();
+        let mut add = |gate_type: GateType, label: &str, x: f32| {
+            let mut gate = Gate::new(Rect::new(x * 64.0, 0.0, 64.0, 64.0), Rotation::Up, gate_type);
+            gate.label = Some(label.to_string());
+            circuit.gates.insert(gate)
+        };
+        let a = add(GateType::IN, "a", 0.0);
+        let first = add(GateType::NOT, "a", 1.0);
+        let second = add(GateType::NOT, "w0", 2.0);
+        let y = add(GateType::OUT, "y", 3.0);
+        circuit.connect_wire(a, first, 0, PinType::Output, 0, PinType::Input);
+        circuit.connect_wire(first, second, 0, PinType::Output, 0, PinType::Input);
+        circuit.connect_wire(second, y, 0, PinType::Output, 0, PinType::Input);
+
+        let text = export(&circuit, None, "top");
+        let mut declared: Vec<&str> = text
+            .lines()
+            .filter_map(|line| {
+                let words: Vec<&str> = line.trim().trim_end_matches([',', ';']).split_whitespace().collect();
+                match words.as_slice() {
+                    ["input" | "output" | "wire", name] => Some(*name),
+                    ["not", name, ..] => Some(*name),
+                    _ => None,
+                }
+            })
+            .collect();
+        let count = declared.len();
+        declared.sort();
+        declared.dedup();
+        assert_eq!(declared.len(), count, "{}", text);
+    }
+
+    #[test]
+    fn wide_primitives_are_split_into_two_input_gates() {
+        let wide = import("module t(input a, b, c, d, output y);\n  nand (y, a, b, c, d);\nendmodule\n").unwrap();
+        let two = import("module t(input a, b, c, d, output y);\n  wire l, r;\n  and (l, a, b);\n  and (r, c, d);\n  nand (y, l, r);\nendmodule\n").unwrap();
+        assert!(matches!(equivalence::check(&wide, &two, Matching::Names), Ok(Equivalence::Equivalent)));
+    }
+
+    const FULL_ADDER: &str = "module adder(input a, b, cin, output sum, cout);
+  wire p, g, c;
+  xor (p, a, b);
+  xor (sum, p, cin);
+  and (g, a, b);
+  and (c, p, cin);
+  or (cout, g, c);
+endmodule
+";
+
+    #[test]
+    fn imported_adder_adds() {
+        let circuit = import(FULL_ADDER).unwrap();
+        let table = TruthTable::generate(&circuit).unwrap();
+        let column = |names: &[String], name: &str| names.iter().position(|n| n == name).unwrap();
+        let (a, b, cin) = (column(&table.inputs, "a"), column(&table.inputs, "b"), column(&table.inputs, "cin"));
+        let (sum, cout) = (column(&table.outputs, "sum"), column(&table.outputs, "cout"));
+        assert_eq!(table.rows.len(), 8);
+        for row in &table.rows {
+            let total = row.inputs[a] as u8 + row.inputs[b] as u8 + row.inputs[cin] as u8;
+            assert!(row.settled);
+            assert_eq!(row.outputs[sum], total & 1 == 1);
+            assert_eq!(row.outputs[cout], total >= 2);
+        }
+    }
+
+    #[test]
+    fn exported_adder_imports_as_the_same_function() {
+        let circuit = import(FULL_ADDER).unwrap();
+        let text = export(&circuit, None, "adder");
+        let again = import(&text).unwrap();
+        assert!(matches!(equivalence::check(&circuit, &again, Matching::Names), Ok(Equivalence::Equivalent)), "{}", text);
+    }
+}
@@ -0,0 +1,435 @@
+use crate::formats::graph::{Net, NetGraph};
+use crate::layout;
+use crate::types::circuit::*;
+use crate::types::gate_type::*;
+use crate::types::netlist::*;
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+
+// what `write_json` gives after synthesis down to yosys' internal gate cells ($_AND_, $_DFF_P_, ...)
+#[derive(Deserialize)]
+struct Design {
+    modules: BTreeMap<String, Module>,
+}
+
+#[derive(Deserialize)]
+struct Module {
+    #[serde(default)]
+    attributes: BTreeMap<String, Value>,
+    #[serde(default)]
+    ports: BTreeMap<String, Port>,
+    #[serde(default)]
+    cells: BTreeMap<String, Cell>,
+    #[serde(default)]
+    netnames: BTreeMap<String, NetName>,
+}
+
+#[derive(Deserialize)]
+struct Port {
+    direction: String,
+    bits: Vec<Bit>,
+}
+
+#[derive(Deserialize)]
+struct Cell {
+    #[serde(rename = "type")]
+    cell_type: String,
+    #[serde(default)]
+    connections: BTreeMap<String, Vec<Bit>>,
+}
+
+#[derive(Deserialize)]
+struct NetName {
+    bits: Vec<Bit>,
+    #[serde(default)]
+    attributes: BTreeMap<String, Value>,
+}
+
+// a net number, or a constant "0", "1", "x" or "z"
+#[derive(Deserialize, Clone)]
+#[serde(untagged)]
+enum Bit {
+    Net(usize),
+    Constant(String),
+}
+
+// yosys writes attribute values as binary strings, numbers in older versions
+fn attribute_bits(value: &Value) -> Vec<bool> {
+    match value {
+        Value::String(text) => text.chars().rev().map(|c| c == '1').collect(),
+        Value::Number(number) => {
+            let value = number.as_u64().unwrap_or(0);
+            (0..64).map(|bit| value >> bit & 1 == 1).collect()
+        }
+        _ => vec![],
+    }
+}
+
+pub fn import(text: &str) -> Result<Circuit, String> {
+    Ok(layout::build(&parse(text)?))
+}
+
+// the module with the top attribute, or the only one no other module instantiates. instances of
+// other modules in the design are flattened into it
+pub fn parse(text: &str) -> Result<Netlist, String> {
+    let design: Design = serde_json::from_str(text).map_err(|e| format!("not a yosys json netlist: {}", e))?;
+
+    let marked: Vec<&String> = design
+        .modules
+        .iter()
+        .filter(|(_, module)| module.attributes.get("top").is_some_and(|value| attribute_bits(value).contains(&true)))
+        .map(|(name, _)| name)
+        .collect();
+    let unused: Vec<&String> = design
+        .modules
+        .keys()
+        .filter(|name| {
+            !design.modules.values().any(|module| module.cells.values().any(|cell| &cell.cell_type == *name))
+        })
+        .collect();
+    let top = match (marked.as_slice(), unused.as_slice()) {
+        ([top], _) | ([], [top]) => (*top).clone(),
+        ([], []) => return Err("the design has no modules".to_string()),
+        _ => return Err("can't tell which module is the top one, run yosys' hierarchy -top".to_string()),
+    };
+
+    let mut elaborator = Elaborator {
+        modules: &design.modules,
+        graph: NetGraph::new(),
+        stack: vec![],
+    };
+    let nets = elaborator.module(&top, &top, HashMap::new())?;
+
+    // yosys numbers the port bits in declaration order, the json keeps them sorted by name
+    let module = &design.modules[&top];
+    let mut ports: Vec<(&String, &Port)> = module.ports.iter().collect();
+    ports.sort_by_key(|(_, port)| {
+        port.bits
+            .iter()
+            .filter_map(|bit| match bit {
+                Bit::Net(id) => Some(*id),
+                Bit::Constant(_) => None,
+            })
+            .min()
+            .unwrap_or(usize::MAX)
+    });
+
+    let mut inputs = vec![];
+    let mut outputs = vec![];
+    for (name, port) in ports {
+        for (index, bit) in port.bits.iter().enumerate() {
+            let label = match port.bits.len() {
+                1 => name.clone(),
+                _ => format!("{}[{}]", name, index),
+            };
+            let net = match bit {
+                Bit::Net(id) => nets[id],
+                Bit::Constant(value) => elaborator.graph.constant(value == "1"),
+            };
+            match port.direction.as_str() {
+                "input" => inputs.push((label, net)),
+                "output" => outputs.push((label, net)),
+                _ => return Err(format!("port {} is {}, only input and output are supported", name, port.direction)),
+            }
+        }
+    }
+    elaborator.graph.netlist(&inputs, &outputs)
+}
+
+struct Elaborator<'a> {
+    modules: &'a BTreeMap<String, Module>,
+    graph: NetGraph,
+    // modules being elaborated, catches a module instantiating itself
+    stack: Vec<String>,
+}
+
+impl Elaborator<'_> {
+    // elaborates a module, net numbers found in 'nets' are the ports the instance connected.
+    // returns the net of every net number
+    fn module(&mut self, name: &str, prefix: &str, mut nets: HashMap<usize, Net>) -> Result<HashMap<usize, Net>, String> {
+        let modules = self.modules;
+        let module = &modules[name];
+        if self.stack.iter().any(|other| other == name) {
+            return Err(format!("module {} instantiates itself", name));
+        }
+        self.stack.push(name.to_string());
+
+        // net names for the error messages and the starting values of flip-flops
+        let mut init: HashMap<usize, bool> = HashMap::new();
+        for (net_name, netname) in &module.netnames {
+            let values = netname.attributes.get("init").map(attribute_bits).unwrap_or_default();
+            for (index, bit) in netname.bits.iter().enumerate() {
+                if let Bit::Net(id) = bit {
+                    nets.entry(*id).or_insert_with(|| self.graph.net(format!("{}.{}", prefix, net_name)));
+                    if let Some(value) = values.get(index) {
+                        init.insert(*id, *value);
+                    }
+                }
+            }
+        }
+
+        for (cell_name, cell) in &module.cells {
+            let error = |message: String| format!("{}.{}: {}", prefix, cell_name, message);
+            let mut pins: HashMap<&str, Net> = HashMap::new();
+            let mut vectors: HashMap<&str, Vec<Net>> = HashMap::new();
+            for (pin, bits) in &cell.connections {
+                let bits: Vec<Net> = bits.iter().map(|bit| self.bit(&mut nets, bit, prefix)).collect();
+                if let [net] = bits.as_slice() {
+                    pins.insert(pin, *net);
+                }
+                vectors.insert(pin, bits);
+            }
+            let pin = |name: &str| pins.get(name).copied().ok_or_else(|| error(format!("pin {} isn't connected", name)));
+            let label = format!("{}.{}", prefix, cell_name);
+
+            let cell_type = cell.cell_type.as_str();
+            match cell_type {
+                "$_BUF_" => self.graph.merge(pin("Y")?, pin("A")?),
+                "$_NOT_" => self.graph.gate(GateType::NOT, &[pin("A")?], pin("Y")?),
+                "$_AND_" | "$_NAND_" | "$_OR_" | "$_NOR_" | "$_XOR_" | "$_XNOR_" => {
+                    let gate_type = match cell_type {
+                        "$_AND_" => GateType::AND,
+                        "$_NAND_" => GateType::NAND,
+                        "$_OR_" => GateType::OR,
+                        "$_NOR_" => GateType::NOR,
+                        "$_XOR_" => GateType::XOR,
+                        _ => GateType::XNOR,
+                    };
+                    self.graph.gate(gate_type, &[pin("A")?, pin("B")?], pin("Y")?);
+                }
+                // a and not b, a or not b
+                "$_ANDNOT_" | "$_ORNOT_" => {
+                    let not_b = self.graph.output(GateType::NOT, &[pin("B")?], &label);
+                    let gate_type = if cell_type == "$_ANDNOT_" { GateType::AND } else { GateType::OR };
+                    self.graph.gate(gate_type, &[pin("A")?, not_b], pin("Y")?);
+                }
+                "$_MUX_" | "$_NMUX_" => {
+                    let selected = self.mux(pin("S")?, pin("A")?, pin("B")?, &label);
+                    match cell_type {
+                        "$_MUX_" => self.graph.merge(pin("Y")?, selected),
+                        _ => self.graph.gate(GateType::NOT, &[selected], pin("Y")?),
+                    }
+                }
+                // not ((a and b) or c), not ((a or b) and c), and the same with (c op d)
+                "$_AOI3_" | "$_OAI3_" | "$_AOI4_" | "$_OAI4_" => {
+                    let (inner, outer) = match cell_type {
+                        "$_AOI3_" | "$_AOI4_" => (GateType::AND, GateType::NOR),
+                        _ => (GateType::OR, GateType::NAND),
+                    };
+                    let ab = self.graph.output(inner.clone(), &[pin("A")?, pin("B")?], &label);
+                    let cd = match cell_type {
+                        "$_AOI3_" | "$_OAI3_" => pin("C")?,
+                        _ => self.graph.output(inner, &[pin("C")?, pin("D")?], &label),
+                    };
+                    self.graph.gate(outer, &[ab, cd], pin("Y")?);
+                }
+                _ if cell_type.starts_with("$_DFF") || cell_type.starts_with("$_SDFF") => {
+                    let q = pin("Q")?;
+                    let active = match cell.connections.get("Q").and_then(|bits| bits.first()) {
+                        Some(Bit::Net(id)) => init.get(id).copied().unwrap_or(false),
+                        _ => false,
+                    };
+                    self.flip_flop(cell_type, &pin, q, active, &label).map_err(error)?;
+                }
+                _ if modules.contains_key(cell_type) => {
+                    let sub = &modules[cell_type];
+                    let mut bindings = HashMap::new();
+                    for (port_name, port) in &sub.ports {
+                        let Some(connected) = vectors.get(port_name.as_str()) else {
+                            continue;
+                        };
+                        if connected.len() != port.bits.len() {
+                            return Err(error(format!("port {} is {} bits wide", port_name, port.bits.len())));
+                        }
+                        for (bit, net) in port.bits.iter().zip(connected) {
+                            match bit {
+                                Bit::Net(id) => match bindings.get(id) {
+                                    // the same net on two ports
+                                    Some(other) => self.graph.merge(*net, *other),
+                                    None => {
+                                        bindings.insert(*id, *net);
+                                    }
+                                },
+                                Bit::Constant(value) if port.direction == "output" => {
+                                    let constant = self.graph.constant(value == "1");
+                                    self.graph.merge(*net, constant);
+                                }
+                                Bit::Constant(_) => {}
+                            }
+                        }
+                    }
+                    self.module(cell_type, &label, bindings)?;
+                }
+                // debug info about the hierarchy yosys adds, no logic in it
+                "$scopeinfo" => {}
+                _ if cell_type.starts_with("$_") => {
+                    return Err(error(format!("{} cells aren't supported", cell_type)));
+                }
+                _ if cell_type.starts_with('$') => {
+                    return Err(error(format!("{} isn't a gate level cell, run synth or techmap first", cell_type)));
+                }
+                _ => return Err(error(format!("unknown module {}", cell_type))),
+            }
+        }
+
+        self.stack.pop();
+        Ok(nets)
+    }
+
+    fn bit(&mut self, nets: &mut HashMap<usize, Net>, bit: &Bit, prefix: &str) -> Net {
+        match bit {
+            Bit::Net(id) => *nets.entry(*id).or_insert_with(|| self.graph.net(format!("{}.{}", prefix, id))),
+            // x and z read as 0 like a floating pin
+            Bit::Constant(value) => self.graph.constant(value == "1"),
+        }
+    }
+
+    // select ? b : a
+    fn mux(&mut self, select: Net, a: Net, b: Net, name: &str) -> Net {
+        let not_select = self.graph.output(GateType::NOT, &[select], name);
+        let take_a = self.graph.output(GateType::AND, &[a, not_select], name);
+        let take_b = self.graph.output(GateType::AND, &[b, select], name);
+        self.graph.output(GateType::OR, &[take_a, take_b], name)
+    }
+
+    // $_DFF_[PN]_, $_DFFE_[PN][PN]_, $_SDFF_[PN][PN][01]_, $_SDFFE_ and $_SDFFCE_[PN][PN][01][PN]_.
+    // the letters are the clock, reset and enable polarity and the reset value, enable and reset
+    // become logic in front of d
+    fn flip_flop(
+        &mut self,
+        cell_type: &str,
+        pin: &dyn Fn(&str) -> Result<Net, String>,
+        q: Net,
+        active: bool,
+        name: &str,
+    ) -> Result<(), String> {
+        let (kind, letters) = cell_type
+            .trim_end_matches('_')
+            .split_once('_')
+            .and_then(|(_, rest)| rest.split_once('_'))
+            .ok_or(format!("{} cells aren't supported", cell_type))?;
+        let letters: Vec<char> = letters.chars().collect();
+        let unsupported = || format!("{} cells aren't supported, only synchronous flip-flops", cell_type);
+
+        // a pin that's active low goes through a NOT first
+        let polarity = |pin_name: &str, letter: char, graph: &mut NetGraph| -> Result<Net, String> {
+            let net = pin(pin_name)?;
+            match letter {
+                'P' | '1' => Ok(net),
+                'N' | '0' => Ok(graph.output(GateType::NOT, &[net], name)),
+                _ => Err(unsupported()),
+            }
+        };
+
+        let (clock, d) = match (kind, letters.as_slice()) {
+            ("DFF", [c]) => (polarity("C", *c, &mut self.graph)?, pin("D")?),
+            ("DFFE", [c, e]) => {
+                let enable = polarity("E", *e, &mut self.graph)?;
+                (polarity("C", *c, &mut self.graph)?, self.mux(enable, q, pin("D")?, name))
+            }
+            ("SDFF" | "SDFFE" | "SDFFCE", [c, r, value, rest @ ..]) => {
+                let clock = polarity("C", *c, &mut self.graph)?;
+                let reset = polarity("R", *r, &mut self.graph)?;
+                let value = match value {
+                    '0' => self.graph.constant(false),
+                    '1' => self.graph.constant(true),
+                    _ => return Err(unsupported()),
+                };
+                let d = pin("D")?;
+                let d = match (kind, rest) {
+                    ("SDFF", []) => self.mux(reset, d, value, name),
+                    // reset wins over enable
+                    ("SDFFE", [e]) => {
+                        let enable = polarity("E", *e, &mut self.graph)?;
+                        let enabled = self.mux(enable, q, d, name);
+                        self.mux(reset, enabled, value, name)
+                    }
+                    // reset only while enabled
+                    ("SDFFCE", [e]) => {
+                        let enable = polarity("E", *e, &mut self.graph)?;
+                        let reset = self.mux(reset, d, value, name);
+                        self.mux(enable, q, reset, name)
+                    }
+                    _ => return Err(unsupported()),
+                };
+                (clock, d)
+            }
+            _ => return Err(unsupported()),
+        };
+        self.graph.flip_flop(d, clock, q, active);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::equivalence::{self, Equivalence, Matching};
+    use crate::analysis::truth_table::TruthTable;
+
+    // y = s ? b : a, z = a and not b
+    const CELLS: &str = r#"{"modules": {"top": {
+        "ports": {
+            "a": {"direction": "input", "bits": [2]},
+            "b": {"direction": "input", "bits": [3]},
+            "s": {"direction": "input", "bits": [4]},
+            "y": {"direction": "output", "bits": [5]},
+            "z": {"direction": "output", "bits": [6]}
+        },
+        "cells": {
+            "mux": {"type": "$_MUX_", "connections": {"A": [2], "B": [3], "S": [4], "Y": [5]}},
+            "andnot": {"type": "$_ANDNOT_", "connections": {"A": [2], "B": [3], "Y": [6]}}
+        }
+    }}}"#;
+
+    #[test]
+    fn gate_cells_import_as_their_function() {
+        let table = TruthTable::generate(&import(CELLS).unwrap()).unwrap();
+        // columns go by the layout, not by the port order
+        let column = |names: &[String], name: &str| names.iter().position(|n| n == name).unwrap();
+        let (a, b, s) = (column(&table.inputs, "a"), column(&table.inputs, "b"), column(&table.inputs, "s"));
+        let (y, z) = (column(&table.outputs, "y"), column(&table.outputs, "z"));
+        assert_eq!(table.rows.len(), 8);
+        for row in &table.rows {
+            let (a, b, s) = (row.inputs[a], row.inputs[b], row.inputs[s]);
+            assert!(row.settled);
+            assert_eq!(row.outputs[y], if s { b } else { a }, "a={} b={} s={}", a, b, s);
+            assert_eq!(row.outputs[z], a && !b, "a={} b={} s={}", a, b, s);
+        }
+    }
+
+    #[test]
+    fn instances_are_flattened() {
+        let hierarchy = r#"{"modules": {
+            "top": {
+                "attributes": {"top": "00000000000000000000000000000001"},
+                "ports": {
+                    "a": {"direction": "input", "bits": [2]},
+                    "b": {"direction": "input", "bits": [3]},
+                    "s": {"direction": "input", "bits": [4]},
+                    "y": {"direction": "output", "bits": [5]},
+                    "z": {"direction": "output", "bits": [6]}
+                },
+                "cells": {
+                    "inner": {"type": "pick", "connections": {"p": [2], "q": [3], "sel": [4], "out": [5]}},
+                    "andnot": {"type": "$_ANDNOT_", "connections": {"A": [2], "B": [3], "Y": [6]}}
+                }
+            },
+            "pick": {
+                "ports": {
+                    "p": {"direction": "input", "bits": [2]},
+                    "q": {"direction": "input", "bits": [3]},
+                    "sel": {"direction": "input", "bits": [4]},
+                    "out": {"direction": "output", "bits": [5]}
+                },
+                "cells": {
+                    "mux": {"type": "$_MUX_", "connections": {"A": [2], "B": [3], "S": [4], "Y": [5]}}
+                }
+            }
+        }}"#;
+        let flat = import(CELLS).unwrap();
+        let nested = import(hierarchy).unwrap();
+        assert!(matches!(equivalence::check(&flat, &nested, Matching::Names), Ok(Equivalence::Equivalent)));
+    }
+}
@@ -0,0 +1,197 @@
+use crate::types::circuit::*;
+use crate::types::gate_type::*;
+use crate::types::keys::*;
+use crate::types::probe::*;
+use slotmap::SecondaryMap;
+use std::collections::VecDeque;
+
+// ticks kept, the oldest are dropped first
+pub const HISTORY_LENGTH: usize = 1000;
+
+// what a tick can change, both wire buffers, flip-flop states, the IN gates users flip while
+// it runs and the probe stats. wires_write holds the clock values flip-flops compare against to
+// find a rising edge
+#[derive(Clone)]
+pub struct Snapshot {
+    pub tick: usize,
+    pub wires: SecondaryMap<WireKey, bool>,
+    pub written: SecondaryMap<WireKey, bool>,
+    pub states: Vec<(GateKey, bool)>,
+    pub probes: Vec<Probe>,
+}
+
+impl Snapshot {
+    pub fn of(circuit: &Circuit) -> Snapshot {
+        Snapshot {
+            tick: circuit.tick_count,
+            wires: circuit.wires_read.clone(),
+            written: circuit.wires_write.clone(),
+            states: circuit
+                .gates
+                .iter()
+                .filter(|(_, gate)| matches!(gate.gate_type, GateType::IN | GateType::DFF))
+                .map(|(key, gate)| (key, gate.active))
+                .collect(),
+            probes: circuit.probes.clone(),
+        }
+    }
+
+    // wires and gates added since the snapshot keep their values, removed ones are skipped.
+    // probes added since start counting again
+    pub fn restore(&self, circuit: &mut Circuit) {
+        for (wire_key, value) in &self.wires {
+            if circuit.wires.contains_key(wire_key) {
+                circuit.wires_read.insert(wire_key, *value);
+            }
+        }
+        for (wire_key, value) in &self.written {
+            if circuit.wires.contains_key(wire_key) {
+                circuit.wires_write.insert(wire_key, *value);
+            }
+        }
+        for (gate_key, active) in &self.states {
+            if let Some(gate) = circuit.gates.get_mut(*gate_key) {
+                gate.active = *active;
+            }
+        }
+        for probe in &mut circuit.probes {
+            match self.probes.iter().find(|old| old.wire == probe.wire) {
+                Some(old) => {
+                    probe.last_value = old.last_value;
+                    probe.toggles = old.toggles;
+                    probe.last_change = old.last_change;
+                }
+                None => probe.reset_stats(),
+            }
+        }
+        circuit.tick_count = self.tick;
+        circuit.emulation_done = false;
+    }
+}
+
+// ring buffer of the state after every tick, stepping through it rewinds the circuit
+#[derive(Default)]
+pub struct History {
+    entries: VecDeque<Snapshot>,
+    // index of the shown entry while looking at the past, None when live
+    cursor: Option<usize>,
+}
+
+impl History {
+    pub fn new() -> History {
+        History::default()
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.cursor = None;
+    }
+
+    // recording from an earlier point drops the entries after it, emulation resumed from there
+    pub fn record(&mut self, circuit: &Circuit) {
+        if let Some(cursor) = self.cursor.take() {
+            self.entries.truncate(cursor + 1);
+        }
+        self.entries.push_back(Snapshot::of(circuit));
+        if self.entries.len() > HISTORY_LENGTH {
+            self.entries.pop_front();
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn is_rewound(&self) -> bool {
+        self.cursor.is_some()
+    }
+
+    // (shown entry, entries) counting from 1
+    pub fn position(&self) -> (usize, usize) {
+        let index = self.cursor.unwrap_or(self.entries.len().saturating_sub(1));
+        (index + 1, self.entries.len())
+    }
+
+    pub fn step_back(&mut self, circuit: &mut Circuit) -> bool {
+        let index = match self.cursor {
+            Some(0) => return false,
+            Some(index) => index - 1,
+            None if self.entries.len() < 2 => return false,
+            // the last entry is the live state
+            None => self.entries.len() - 2,
+        };
+        self.cursor = Some(index);
+        self.entries[index].restore(circuit);
+        true
+    }
+
+    pub fn step_forward(&mut self, circuit: &mut Circuit) -> bool {
+        let Some(index) = self.cursor else {
+            return false;
+        };
+        let index = index + 1;
+        self.cursor = if index + 1 == self.entries.len() { None } else { Some(index) };
+        self.entries[index].restore(circuit);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::gate::*;
+    use crate::types::pin_type::*;
+    use macroquad::prelude::Rect;
+
+    // a toggle flip-flop, d is fed its own output through a NOT. the clock is an IN gate that
+    // run() flips every other tick
+    fn flip_flop() -> (Circuit, GateKey, GateKey) {
+        let mut circuit = Circuit::new();
+        let mut add = |gate_type: GateType, y: f32| {
+            circuit.gates.insert(Gate::new(Rect::new(0.0, y * 64.0, 64.0, 64.0), Rotation::Up, gate_type))
+        };
+        let clock = add(GateType::IN, 0.0);
+        let dff = add(GateType::DFF, 1.0);
+        let not = add(GateType::NOT, 2.0);
+        circuit.connect_wire(not, dff, 0, PinType::Output, 0, PinType::Input);
+        circuit.connect_wire(clock, dff, 0, PinType::Output, 1, PinType::Input);
+        circuit.connect_wire(dff, not, 0, PinType::Output, 0, PinType::Input);
+        let q = circuit.gates[dff].output[0].wire_index.unwrap();
+        circuit.probes.push(Probe::new("q".to_string(), q));
+        circuit.gates[clock].active = false;
+        (circuit, clock, dff)
+    }
+
+    fn run(circuit: &mut Circuit, history: &mut History, clock: GateKey, ticks: usize) {
+        for _ in 0..ticks {
+            circuit.gates[clock].active = (circuit.tick_count / 2) % 2 == 1;
+            circuit.tick();
+            history.record(circuit);
+        }
+    }
+
+    #[test]
+    fn resuming_from_every_rewound_tick_repeats_the_run() {
+        let (mut circuit, clock, dff) = flip_flop();
+        let mut history = History::new();
+        history.record(&circuit);
+        run(&mut circuit, &mut history, clock, 24);
+        let stored = |history: &History| -> Vec<bool> {
+            history.entries.iter().map(|entry| entry.states.iter().any(|(key, active)| *key == dff && *active)).collect()
+        };
+        let original = stored(&history);
+        // the toggle flip-flop has to actually count for the test to mean anything
+        assert!(original.contains(&true) && original.contains(&false));
+
+        for back in 1..original.len() - 1 {
+            let mut rewound = circuit.clone();
+            let mut replay = History { entries: history.entries.clone(), cursor: None };
+            for _ in 0..back {
+                assert!(replay.step_back(&mut rewound));
+            }
+            run(&mut rewound, &mut replay, clock, back);
+            assert_eq!(stored(&replay), original, "resumed {} ticks back", back);
+            assert_eq!(rewound.probes[0].toggles, circuit.probes[0].toggles, "resumed {} ticks back", back);
+        }
+    }
+}
@@ -0,0 +1,338 @@
+use crate::types::circuit::*;
+use crate::types::gate::*;
+use crate::types::gate_type::*;
+use crate::types::keys::*;
+use crate::types::netlist::*;
+use crate::types::pin_type::*;
+use macroquad::prelude::*;
+use std::collections::HashMap;
+
+pub const GRID_SIZE: f32 = 64.0;
+// cells between neighbouring gates, leaves room for the wires
+pub const SPACING: i32 = 2;
+// barycenter passes over the layers, alternating down and up
+const SWEEPS: usize = 8;
+
+// grid cell (x, y) of every node: IN gates in the first column, OUT gates in the last one and
+// everything else in the column of its logic depth, ordered to keep the wires from crossing
+pub fn layered(netlist: &Netlist) -> Vec<(i32, i32)> {
+    let edges: Vec<(usize, usize)> = netlist.edges.iter().map(|edge| (edge.from, edge.to)).collect();
+    let outputs: Vec<bool> = netlist.nodes.iter().map(|node| node.gate_type == GateType::OUT).collect();
+    layers(&edges, &outputs)
+        .into_iter()
+        .map(|(layer, row)| (layer as i32 * SPACING, row as i32 * SPACING))
+        .collect()
+}
+
+// grid cell of every gate in 'gates', the same layering as 'layered' with the top left one at
+// (0, 0). signals flow the way most of the gates face, so rotated circuits stay rotated
+pub fn arrange(circuit: &Circuit, gates: &[GateKey]) -> Vec<(i32, i32)> {
+    let nodes: HashMap<GateKey, usize> = gates.iter().enumerate().map(|(index, key)| (*key, index)).collect();
+    let mut edges = vec![];
+    for (from, key) in gates.iter().enumerate() {
+        for output in &circuit.gates[*key].output {
+            let Some(wire) = output.wire_index.and_then(|wire| circuit.wires.get(wire)) else {
+                continue;
+            };
+            for connection in &wire.connections {
+                if let Some(to) = nodes.get(&connection.gate_index) {
+                    edges.push((from, *to));
+                }
+            }
+        }
+    }
+    let outputs: Vec<bool> = gates.iter().map(|key| circuit.gates[*key].gate_type == GateType::OUT).collect();
+
+    let mut counts = [0; 4];
+    for key in gates {
+        counts[circuit.gates[*key].rotation.as_degrees() as usize / 90] += 1;
+    }
+    // ties go to the first rotation, Up
+    let quarter_turns = (0..4).rev().max_by_key(|turns| counts[*turns]).unwrap_or(0);
+
+    let cells: Vec<(i32, i32)> = layers(&edges, &outputs)
+        .into_iter()
+        .map(|(layer, row)| {
+            let (x, y) = (layer as i32 * SPACING, row as i32 * SPACING);
+            // clockwise, layers going right become layers going down
+            match quarter_turns {
+                0 => (x, y),
+                1 => (-y, x),
+                2 => (-x, -y),
+                _ => (y, -x),
+            }
+        })
+        .collect();
+    let left = cells.iter().map(|(x, _)| *x).min().unwrap_or(0);
+    let top = cells.iter().map(|(_, y)| *y).min().unwrap_or(0);
+    cells.into_iter().map(|(x, y)| (x - left, y - top)).collect()
+}
+
+// a new circuit with every node at its layered cell, what the importers hand to the simulator
+pub fn build(netlist: &Netlist) -> Circuit {
+    let mut circuit = Circuit::new();
+    let keys: Vec<GateKey> = layered(netlist)
+        .into_iter()
+        .zip(&netlist.nodes)
+        .map(|((x, y), node)| {
+            let rect = Rect::new(x as f32 * GRID_SIZE, y as f32 * GRID_SIZE, GRID_SIZE, GRID_SIZE);
+            let mut gate = Gate::new(rect, Rotation::Up, node.gate_type.clone());
+            gate.label = node.label.clone();
+            gate.active = node.active;
+            circuit.gates.insert(gate)
+        })
+        .collect();
+
+    for edge in &netlist.edges {
+        circuit.connect_wire(
+            keys[edge.from],
+            keys[edge.to],
+            edge.from_pin,
+            PinType::Output,
+            edge.to_pin,
+            PinType::Input,
+        );
+    }
+    circuit
+}
+
+// (layer, row) of every node, sugiyama style. layers follow logic depth with the 'outputs' all in
+// the last one, edges spanning several layers get a dummy node in each layer they cross and
+// then barycenter sweeps reorder the layers, keeping the order with the fewest crossings
+fn layers(edges: &[(usize, usize)], outputs: &[bool]) -> Vec<(usize, usize)> {
+    let count = outputs.len();
+    let mut fan_in: Vec<Vec<usize>> = vec![vec![]; count];
+    for (from, to) in edges {
+        fan_in[*to].push(*from);
+    }
+
+    let mut columns = depths(&fan_in);
+    let last_column = columns
+        .iter()
+        .zip(outputs)
+        .filter(|(_, output)| !**output)
+        .map(|(column, _)| column + 1)
+        .max()
+        .unwrap_or(0);
+    for (column, output) in columns.iter_mut().zip(outputs) {
+        if *output {
+            *column = last_column;
+        }
+    }
+
+    // edges going back, the ones closing a loop, don't take part in the ordering
+    let mut links: Vec<(usize, usize)> = vec![];
+    for (from, to) in edges {
+        if columns[*from] >= columns[*to] {
+            continue;
+        }
+        let mut previous = *from;
+        for column in columns[*from] + 1..columns[*to] {
+            columns.push(column);
+            links.push((previous, columns.len() - 1));
+            previous = columns.len() - 1;
+        }
+        links.push((previous, *to));
+    }
+
+    let mut order: Vec<Vec<usize>> = vec![vec![]; last_column + 1];
+    for (node, column) in columns.iter().enumerate() {
+        order[*column].push(node);
+    }
+    let mut above: Vec<Vec<usize>> = vec![vec![]; columns.len()];
+    let mut below: Vec<Vec<usize>> = vec![vec![]; columns.len()];
+    for (from, to) in &links {
+        below[*from].push(*to);
+        above[*to].push(*from);
+    }
+
+    let mut best = order.clone();
+    let mut best_crossings = crossings(&order, &links, &columns);
+    for sweep in 0..SWEEPS {
+        if best_crossings == 0 {
+            break;
+        }
+        if sweep % 2 == 0 {
+            for column in 1..order.len() {
+                reorder(&mut order, column - 1, column, &above);
+            }
+        } else {
+            for column in (0..order.len().saturating_sub(1)).rev() {
+                reorder(&mut order, column + 1, column, &below);
+            }
+        }
+        let sweep_crossings = crossings(&order, &links, &columns);
+        if sweep_crossings < best_crossings {
+            best = order.clone();
+            best_crossings = sweep_crossings;
+        }
+    }
+
+    // dummy nodes only steer the order, they don't take up a row
+    let mut cells = vec![(0, 0); count];
+    for (column, nodes) in best.iter().enumerate() {
+        for (row, node) in nodes.iter().filter(|node| **node < count).enumerate() {
+            cells[*node] = (column, row);
+        }
+    }
+    cells
+}
+
+// sorts 'column' by the mean position of each node's neighbours in 'fixed', nodes without any
+// keep their place
+fn reorder(order: &mut [Vec<usize>], fixed: usize, column: usize, neighbours: &[Vec<usize>]) {
+    let positions: HashMap<usize, usize> = order[fixed].iter().enumerate().map(|(row, node)| (*node, row)).collect();
+    let mut keyed: Vec<(f32, usize)> = order[column]
+        .iter()
+        .enumerate()
+        .map(|(row, node)| {
+            let rows: Vec<usize> = neighbours[*node].iter().filter_map(|other| positions.get(other).copied()).collect();
+            match rows.is_empty() {
+                true => (row as f32, *node),
+                false => (rows.iter().sum::<usize>() as f32 / rows.len() as f32, *node),
+            }
+        })
+        .collect();
+    keyed.sort_by(|a, b| a.0.total_cmp(&b.0));
+    order[column] = keyed.into_iter().map(|(_, node)| node).collect();
+}
+
+// pairs of links between neighbouring layers that cross each other. two links only cross when
+// they leave the same layer, sorted by where they start it's the inversions of where they end
+fn crossings(order: &[Vec<usize>], links: &[(usize, usize)], columns: &[usize]) -> usize {
+    let mut rows = vec![0; columns.len()];
+    for nodes in order {
+        for (row, node) in nodes.iter().enumerate() {
+            rows[*node] = row;
+        }
+    }
+    let mut leaving: Vec<Vec<(usize, usize)>> = vec![vec![]; order.len()];
+    for (from, to) in links {
+        leaving[columns[*from]].push((rows[*from], rows[*to]));
+    }
+    let mut result = 0;
+    for mut pairs in leaving {
+        pairs.sort();
+        let mut ends: Vec<usize> = pairs.into_iter().map(|(_, end)| end).collect();
+        result += inversions(&mut ends);
+    }
+    result
+}
+
+// pairs where the larger value comes first, merge sorting 'values' along the way
+fn inversions(values: &mut [usize]) -> usize {
+    if values.len() < 2 {
+        return 0;
+    }
+    let middle = values.len() / 2;
+    let mut result = inversions(&mut values[..middle]) + inversions(&mut values[middle..]);
+    let mut merged = Vec::with_capacity(values.len());
+    let (mut left, mut right) = (0, middle);
+    while left < middle && right < values.len() {
+        if values[right] < values[left] {
+            // everything still waiting on the left is larger
+            result += middle - left;
+            merged.push(values[right]);
+            right += 1;
+        } else {
+            merged.push(values[left]);
+            left += 1;
+        }
+    }
+    merged.extend_from_slice(&values[left..middle]);
+    merged.extend_from_slice(&values[right..]);
+    values.copy_from_slice(&merged);
+    result
+}
+
+// longest path from a node without inputs. a depth first search marks the edges closing a loop,
+// those are ignored and the rest is walked in topological order. no recursion, imported netlists
+// can have chains far deeper than the stack
+fn depths(fan_in: &[Vec<usize>]) -> Vec<usize> {
+    let count = fan_in.len();
+    let mut back: Vec<Vec<bool>> = fan_in.iter().map(|sources| vec![false; sources.len()]).collect();
+    let mut on_stack = vec![false; count];
+    let mut visited = vec![false; count];
+    for root in 0..count {
+        if visited[root] {
+            continue;
+        }
+        visited[root] = true;
+        on_stack[root] = true;
+        let mut stack = vec![(root, 0)];
+        while let Some((node, next)) = stack.last_mut() {
+            let node = *node;
+            let Some(source) = fan_in[node].get(*next).copied() else {
+                on_stack[node] = false;
+                stack.pop();
+                continue;
+            };
+            back[node][*next] = on_stack[source];
+            *next += 1;
+            if !visited[source] {
+                visited[source] = true;
+                on_stack[source] = true;
+                stack.push((source, 0));
+            }
+        }
+    }
+
+    let mut fan_out: Vec<Vec<usize>> = vec![vec![]; count];
+    let mut waiting = vec![0; count];
+    for (node, sources) in fan_in.iter().enumerate() {
+        for (source, back) in sources.iter().zip(&back[node]) {
+            if !back {
+                fan_out[*source].push(node);
+                waiting[node] += 1;
+            }
+        }
+    }
+    let mut depths = vec![0; count];
+    let mut ready: Vec<usize> = (0..count).filter(|node| waiting[*node] == 0).collect();
+    while let Some(node) = ready.pop() {
+        for next in &fan_out[node] {
+            depths[*next] = depths[*next].max(depths[node] + 1);
+            waiting[*next] -= 1;
+            if waiting[*next] == 0 {
+                ready.push(*next);
+            }
+        }
+    }
+    depths
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn long_chains_and_loops_get_their_depth() {
+        let count: usize = 200_000;
+        let mut fan_in: Vec<Vec<usize>> = (0..count).map(|node| node.checked_sub(1).into_iter().collect()).collect();
+        // closing the chain into a ring, one of its edges gets ignored and the rest stay a chain
+        fan_in[0].push(count - 1);
+        let mut depths = depths(&fan_in);
+        depths.sort();
+        assert!(depths.iter().enumerate().all(|(index, depth)| index == *depth));
+    }
+
+    #[test]
+    fn crossings_agree_with_comparing_every_pair() {
+        // two layers of four, most pairs linked and the second layer shuffled
+        let order = vec![vec![0, 1, 2, 3], vec![6, 4, 7, 5]];
+        let columns = vec![0, 0, 0, 0, 1, 1, 1, 1];
+        let links: Vec<(usize, usize)> =
+            (0..4).flat_map(|from| (4..8).filter(move |to| (from + to) % 3 != 0).map(move |to| (from, to))).collect();
+        let row = |node: usize| order[columns[node]].iter().position(|other| *other == node).unwrap() as i64;
+        let mut expected = 0;
+        for (index, (from, to)) in links.iter().enumerate() {
+            for (other_from, other_to) in &links[index + 1..] {
+                if (row(*from) - row(*other_from)) * (row(*to) - row(*other_to)) < 0 {
+                    expected += 1;
+                }
+            }
+        }
+        assert!(expected > 0);
+        assert_eq!(crossings(&order, &links, &columns), expected);
+    }
+}
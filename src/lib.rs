@@ -0,0 +1,11 @@
+pub mod analysis;
+pub mod breakpoint;
+pub mod formats;
+pub mod history;
+pub mod layout;
+pub mod simulator;
+pub mod types;
+pub mod ui;
+pub mod undo;
+pub mod utils;
+pub mod waveform;
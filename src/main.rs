@@ -2,12 +2,7 @@ use std::{time::SystemTime};
 
 use macroquad::prelude::*;
 
-mod simulator;
-mod types;
-mod ui;
-mod utils; // Import the new module
-
-use simulator::Simulator;
+use playground::simulator::Simulator;
 
 #[macroquad::main("Logic Sim")]
 async fn main() {
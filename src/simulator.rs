@@ -1,19 +1,33 @@
+use crate::analysis::equivalence::{self, Matching};
+use crate::breakpoint::Breakpoint;
+use crate::formats::{self, blif, digital, dot, logisim, svg, verilog};
+use crate::history::History;
+use crate::undo::UndoStack;
+use crate::analysis::expression::Expressions;
+use crate::analysis::subcircuit::group_function;
+use crate::analysis::synthesis::FunctionSpec;
+use crate::analysis::test_bench::TestBench;
+use crate::analysis::truth_table::TruthTable;
 use crate::types::circuit::*;
 use crate::types::gate::*;
 use crate::types::gate_type::*;
 use crate::types::keys::*;
+use crate::types::netlist::*;
 use crate::types::pin_type::*;
+use crate::types::probe::*;
 use crate::types::wires::*;
 use crate::utils::*;
-use crate::ui::draw_ui;
+use crate::waveform::{WaveformRecorder, value_char};
+use crate::ui::{WaveformPanel, draw_table_panel, draw_ui};
 use crate::utils::camera_view_rect;
 use crate::utils::draw_grid;
+use crate::layout;
 use macroquad::prelude::*;
 use rstar::{AABB, PointDistance, RTree, RTreeObject};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fmt::Write;
 use std::time::SystemTime;
-use crate::utils::{save_to_file, load_from_file};
+use crate::utils::save_to_file;
 use std::io;
 
 
@@ -149,6 +163,20 @@ pub struct Simulator {
     pub emulate: bool,
     pub log_msg: String,
 
+    // Panels
+    pub truth_table: Option<TruthTable>,
+    pub expressions: Option<Expressions>,
+    pub waveform_panel: WaveformPanel,
+    pub watch_list: bool,
+
+    // Instruments
+    pub recorder: WaveformRecorder,
+    pub breakpoints: Vec<Breakpoint>,
+    pub history: History,
+    pub undo_stack: UndoStack,
+    // (tick, condition, triggering wires) of the breakpoint that paused emulation
+    pub breakpoint_hit: Option<(usize, String, Vec<WireKey>)>,
+
     // State
     tree: RTree<SpatialBlockIndex>,
     state: InputState,
@@ -166,6 +194,15 @@ impl Simulator {
             emulate: false,
             last_tick: SystemTime::now(),
             log_msg: String::new(),
+            truth_table: None,
+            expressions: None,
+            waveform_panel: WaveformPanel::new(),
+            watch_list: false,
+            recorder: WaveformRecorder::new(),
+            breakpoints: vec![],
+            history: History::new(),
+            undo_stack: UndoStack::new(),
+            breakpoint_hit: None,
         }
     }
 
@@ -174,13 +211,22 @@ impl Simulator {
 
         // if self.emulate && (self.last_tick.elapsed().unwrap().as_millis() as i32 > 1000) {
         if self.emulate {
+            // the state before the first tick is worth going back to as well
+            if self.history.is_empty() {
+                self.history.record(&self.circuit);
+            }
             self.circuit.tick();
+            self.history.record(&self.circuit);
+            self.recorder.sample(&self.circuit);
+            self.check_breakpoints();
             self.last_tick = SystemTime::now();
         }
 
         self.handle_keyboard();
-        self.handle_zoom();
-        self.handle_mouse();
+        if !self.waveform_panel.handle_input(&self.recorder) {
+            self.handle_zoom();
+            self.handle_mouse();
+        }
     }
 
     pub fn handle_keyboard(&mut self) {
@@ -231,8 +277,22 @@ impl Simulator {
                 gate_type: GateType::OUT,
                 gate_rotation: Rotation::Up,
             };
+        } else if is_key_pressed(KeyCode::Minus) {
+            self.state = InputState::ChoosingGate {
+                gate_type: GateType::DFF,
+                gate_rotation: Rotation::Up,
+            };
+        } else if is_key_down(KeyCode::LeftControl) && is_key_pressed(KeyCode::Z) {
+            if let Some(circuit) = self.undo_stack.undo(&self.circuit) {
+                self.restore_edit(circuit);
+            }
+        } else if is_key_down(KeyCode::LeftControl) && is_key_pressed(KeyCode::Y) {
+            if let Some(circuit) = self.undo_stack.redo(&self.circuit) {
+                self.restore_edit(circuit);
+            }
         } else if is_key_pressed(KeyCode::R) {
             self.emulate = !self.emulate;
+            self.breakpoint_hit = None;
         } else if is_key_pressed(KeyCode::T) {
             self.circuit.reset_wires();
         } else if is_key_pressed(KeyCode::F) {
@@ -242,10 +302,56 @@ impl Simulator {
                 .read_line(&mut file_name) 
                 .expect("Failed to read line");
 
-            match save_to_file(&self.circuit, file_name.trim().to_string()) {
+            let binary = read_line("save as compressed binary instead of text (y/n): ") == "y";
+            match save_to_file(&self.circuit, file_name.trim().to_string(), binary) {
                 Ok(path) => println!("Saved to {}", path),
                 Err(e) => println!("Error saving: {}", e),
             }
+        } else if is_key_pressed(KeyCode::F1) {
+            self.truth_table = match self.truth_table {
+                Some(_) => None,
+                None => match TruthTable::generate(&self.circuit) {
+                    Ok(table) => Some(table),
+                    Err(e) => {
+                        println!("Error generating truth table: {}", e);
+                        None
+                    }
+                },
+            };
+        } else if is_key_pressed(KeyCode::F2) {
+            self.expressions = match self.expressions {
+                Some(_) => None,
+                None => match Expressions::extract(&self.circuit) {
+                    Ok(expressions) => Some(expressions),
+                    Err(e) => {
+                        println!("Error extracting expressions: {}", e);
+                        None
+                    }
+                },
+            };
+        } else if is_key_pressed(KeyCode::F3) {
+            self.waveform_panel.open = !self.waveform_panel.open;
+        } else if is_key_pressed(KeyCode::F4) {
+            self.watch_list = !self.watch_list;
+        } else if is_key_pressed(KeyCode::G) {
+            self.synthesize();
+        } else if is_key_pressed(KeyCode::E) {
+            self.export();
+        } else if is_key_pressed(KeyCode::Q) {
+            self.check_equivalence();
+        } else if is_key_pressed(KeyCode::B) {
+            self.run_test_bench();
+        } else if is_key_pressed(KeyCode::Comma) {
+            self.emulate = false;
+            self.history.step_back(&mut self.circuit);
+        } else if is_key_pressed(KeyCode::Period) {
+            self.emulate = false;
+            self.history.step_forward(&mut self.circuit);
+        } else if is_key_pressed(KeyCode::K) {
+            self.add_breakpoint();
+        } else if is_key_pressed(KeyCode::O) {
+            let recording = !self.recorder.recording;
+            self.recorder.set_recording(recording, &self.circuit);
         } else if is_key_pressed(KeyCode::L) {
             let mut input = "".to_string();
             println!("enter file name: ");
@@ -256,25 +362,18 @@ impl Simulator {
             let file_path = "tmp/saves/".to_string() + input.trim();
 
             println!("loading from: {}", &file_path);
-            match load_from_file(&file_path) { // Pass &String as &str
-                Ok(mut new_circuit) => {
-
-                    for wire_key in new_circuit.wires.keys() {
-                        new_circuit.wires_read.insert(wire_key, false);
-                        new_circuit.wires_write.insert(wire_key, false);
-                    }
+            match formats::load(&file_path) {
+                Ok(new_circuit) => {
 
                     println!("gates {:?} \nwires {:?} \nwires_r {:?} \nwires_w {:?} \nemulation_done {:?}", new_circuit.gates, new_circuit.wires, new_circuit.wires_read, new_circuit.wires_write, new_circuit.emulation_done);
 
-                    self.circuit = new_circuit;
-                    self.tree = RTree::new();
-                    for (key, gate) in &self.circuit.gates {
-                        self.tree.insert(SpatialBlockIndex {
-                            rect: gate.rect,
-                            index: key,
-                        });
-                    }
-                    // Rebuild RTree here (omitted for brevity)
+                    self.set_circuit(new_circuit);
+                    // the recorded wires, history, edits and breakpoints belonged to the old circuit
+                    self.recorder = WaveformRecorder::new();
+                    self.history.clear();
+                    self.undo_stack.clear();
+                    self.breakpoints.clear();
+                    self.breakpoint_hit = None;
                     println!("Loaded successfully");
                 }
                 Err(e) => println!("Error loading: {}", e),
@@ -319,9 +418,17 @@ impl Simulator {
             }
             InputState::SelectedGates { sp_gates, gates_rect } => {
                 // if something was selected, then when right click or click not on a gate
-                if is_key_down(KeyCode::LeftControl) && is_key_down(KeyCode::C) {
+                if is_key_pressed(KeyCode::M) {
+                    self.minimize_selection(&sp_gates, gates_rect);
+                    self.state = InputState::Idle;
+                } else if is_key_pressed(KeyCode::U) {
+                    self.tidy_selection(&sp_gates, gates_rect);
+                } else if is_key_pressed(KeyCode::V) {
+                    self.toggle_recorded(&sp_gates);
+                } else if is_key_down(KeyCode::LeftControl) && is_key_down(KeyCode::C) {
                     self.state = InputState::PastingGates{ initial_rect: gates_rect, mouse_rect: gates_rect, sp_gates };
                 } else if is_key_down(KeyCode::Escape) {
+                    self.undo_stack.checkpoint(&self.circuit);
                     for key in sp_gates {
                         self.circuit.remove_gate(key.index);
                         // they are not on tree so no need to delete them from tree
@@ -352,10 +459,20 @@ impl Simulator {
 
         match self.state.clone() {
             InputState::Idle => {
-                if is_mouse_button_pressed(MouseButton::Right) {
+                if is_mouse_button_pressed(MouseButton::Right) && is_key_down(KeyCode::LeftShift) {
+                    let wire_key = match hovered_pin {
+                        Some((g_idx, p_idx, p_type)) => self.circuit.gates[g_idx].get_pin(p_idx, p_type).wire_index,
+                        None => self.circuit.wire_at(mouse_world, 6.0),
+                    };
+                    if let Some(wire_key) = wire_key {
+                        self.toggle_probe(wire_key);
+                    }
+                } else if is_mouse_button_pressed(MouseButton::Right) {
                     if let Some((g_idx, p_idx, p_type)) = hovered_pin {
+                        self.undo_stack.checkpoint(&self.circuit);
                         self.delete_wire_at_pin(g_idx, p_idx, p_type);
                     } else if let Some(g_idx) = hovered_gate_key {
+                        self.undo_stack.checkpoint(&self.circuit);
                         self.tree.remove(&SpatialBlockIndex {
                             rect: self.circuit.gates.get(g_idx).as_ref().unwrap().rect,
                             index: g_idx,
@@ -385,6 +502,7 @@ impl Simulator {
                 if is_mouse_button_pressed(MouseButton::Right) {
                     self.state = InputState::Idle;
                 } else if is_mouse_button_pressed(MouseButton::Left) && hovered_gate_key.is_none() {
+                    self.undo_stack.checkpoint(&self.circuit);
                     self.place_gate(mouse_world, gate_type, gate_rotation);
                     self.state = InputState::Idle;
                 }
@@ -411,6 +529,8 @@ impl Simulator {
                     // if distance from initial_click_pos to mouse_world is more than MIN
                     let dist = initial_click_pos.distance(mouse_world);
                     if dist > DRAG_MIN_DIST {
+                        // the drag and any rotation during it undo as one edit
+                        self.undo_stack.begin_drag(&self.circuit);
                         // Remove from tree for the duration of the drag
                         if let Some(gate) = self.circuit.gates.get(gate_key) {
                             self.tree.remove(&SpatialBlockIndex {
@@ -456,6 +576,7 @@ impl Simulator {
                             index: gate_id,
                         });
                         self.state = InputState::Idle;
+                        self.undo_stack.end_drag(&self.circuit);
                     }
                 }
             }
@@ -470,6 +591,7 @@ impl Simulator {
                             || (gate == to_g && pin == to_p)
                             || (p_type.to_string() == to_t.to_string()))
                         {
+                            self.undo_stack.checkpoint(&self.circuit);
                             self.circuit
                                 .connect_wire(gate, to_g, pin, p_type, to_p, to_t);
                             self.state = InputState::Idle;
@@ -564,6 +686,7 @@ impl Simulator {
                     }
 
                     if !sp_gates.is_empty() && !intersection {
+                        self.undo_stack.checkpoint(&self.circuit);
 
                         let dx = mouse_rect.x - initial_rect.x;
                         let dy = mouse_rect.y - initial_rect.y;
@@ -575,7 +698,9 @@ impl Simulator {
                             let rotation = old_gate.rotation.clone();
                             let gate_type = old_gate.gate_type.clone();
                             let new_gate_rect = Rect::new(rect.x + dx, rect.y + dy, rect.w, rect.h);
-                            let new_gate_id = self.circuit.gates.insert(Gate::new(new_gate_rect, rotation, gate_type));
+                            let mut new_gate = Gate::new(new_gate_rect, rotation, gate_type);
+                            new_gate.label = old_gate.label.clone();
+                            let new_gate_id = self.circuit.gates.insert(new_gate);
                             self.tree.insert(SpatialBlockIndex { rect: new_gate_rect, index: new_gate_id });
                             gate_map.insert(*index, new_gate_id);
                         
@@ -657,6 +782,7 @@ impl Simulator {
                     // if distance from initial_click_pos to mouse_world is more than MIN
                     let dist = initial_click_pos.distance(mouse_world);
                     if dist > DRAG_MIN_DIST {
+                        self.undo_stack.begin_drag(&self.circuit);
                         //remove from tree all dragging gates
                         for SpatialBlockIndex{ index: gate_key, .. } in sp_gates.clone() {
                             if let Some(gate) = self.circuit.gates.get(gate_key) {
@@ -716,6 +842,7 @@ impl Simulator {
                         });
                         self.state = InputState::Idle;
                     }
+                    self.undo_stack.end_drag(&self.circuit);
                 }
             }
         }
@@ -845,6 +972,10 @@ impl Simulator {
         crate::utils::draw_gates(&self.circuit, &self.camera);
         crate::utils::draw_wires(&mut self.circuit, &self.camera);
         crate::utils::draw_pins(&self.circuit, &self.camera);
+        crate::utils::draw_probes(&self.circuit, &self.camera);
+        if let Some((_, _, wires)) = &self.breakpoint_hit {
+            crate::utils::draw_highlighted_wires(&self.circuit, &self.camera, wires);
+        }
 
         // draw hover gate
         match self.state.clone() {
@@ -904,12 +1035,468 @@ impl Simulator {
 
         set_default_camera();
 
+        if let Some(table) = &self.truth_table {
+            let rows: Vec<Vec<String>> = table.rows.iter().map(|row| table.cells(row)).collect();
+            draw_table_panel("truth table (F1)", &table.header(), &rows);
+        } else if let Some(expressions) = &self.expressions {
+            let header = ["out", "expression", "sum of products"].map(String::from);
+            let rows: Vec<Vec<String>> = expressions
+                .outputs
+                .iter()
+                .map(|(name, expr, simplified)| {
+                    let simplified = match simplified {
+                        Ok(simplified) => simplified.format(&expressions.inputs),
+                        Err(e) => e.clone(),
+                    };
+                    vec![name.clone(), expr.format(&expressions.inputs), simplified]
+                })
+                .collect();
+            draw_table_panel("expressions (F2)", &header, &rows);
+        } else if self.watch_list {
+            let header = ["probe", "value", "toggles", "last change"].map(String::from);
+            let rows: Vec<Vec<String>> = self
+                .circuit
+                .probes
+                .iter()
+                .map(|probe| {
+                    let value = self.circuit.wires_read.get(probe.wire).copied();
+                    vec![
+                        probe.name.clone(),
+                        value_char(value).to_string(),
+                        probe.toggles.to_string(),
+                        probe.last_change.map_or("-".to_string(), |tick| tick.to_string()),
+                    ]
+                })
+                .collect();
+            draw_table_panel("watch list (F4)", &header, &rows);
+        }
+
+        self.waveform_panel.draw(&self.recorder);
+
         write!(self.log_msg, "input state: {} |", self.state.to_string()).unwrap();
+        if self.history.is_rewound() {
+            let (index, count) = self.history.position();
+            write!(self.log_msg, " rewound to tick {} ({}/{}), R resumes from here |", self.circuit.tick_count, index, count).unwrap();
+        }
+        if let Some((tick, text, _)) = &self.breakpoint_hit {
+            write!(self.log_msg, " breakpoint '{}' hit at tick {} |", text, tick).unwrap();
+        }
+        if self.recorder.recording {
+            write!(self.log_msg, " recording {} wires |", self.recorder.signals.len()).unwrap();
+        }
 
         draw_ui(self.log_msg.clone());
     }
 
-    fn place_gate(&mut self, pos: Vec2, gate_type: GateType, gate_rotation: Rotation) {
+    fn export(&mut self) {
+        let format = read_line("export format (csv, md, expr, vcd, v, blif, circ, dig, dot, svg): ");
+        let file_name = read_line("enter file name: ");
+        let (contents, extension) = match format.as_str() {
+            "csv" | "md" => {
+                let table = match TruthTable::generate(&self.circuit) {
+                    Ok(table) => table,
+                    Err(e) => {
+                        println!("Error generating truth table: {}", e);
+                        return;
+                    }
+                };
+                match format.as_str() {
+                    "csv" => (table.to_csv(), "csv"),
+                    _ => (table.to_markdown(), "md"),
+                }
+            }
+            "vcd" => (self.recorder.to_vcd(), "vcd"),
+            // only the selected gates while there's a selection
+            "v" => {
+                let selection = match &self.state {
+                    InputState::SelectedGates { sp_gates, .. } => Some(sp_gates.iter().map(|sp| sp.index).collect()),
+                    _ => None,
+                };
+                (verilog::export(&self.circuit, selection.as_ref(), &file_name), "v")
+            }
+            "blif" => (blif::export(&self.circuit, &file_name), "blif"),
+            "circ" => (logisim::export(&self.circuit, &file_name), "circ"),
+            "dig" => (digital::export(&self.circuit), "dig"),
+            // the selection too, drawn in its current state if asked
+            "svg" => {
+                let selection: Option<HashSet<GateKey>> = match &self.state {
+                    InputState::SelectedGates { sp_gates, .. } => Some(sp_gates.iter().map(|sp| sp.index).collect()),
+                    _ => None,
+                };
+                let live = read_line("color the wires by their current value (y/n): ") == "y";
+                (svg::export(&self.circuit, selection.as_ref(), live), "svg")
+            }
+            "dot" => {
+                let clusters = read_line("group the gates of custom gates (y/n): ") == "y";
+                (dot::export(&self.circuit, clusters), "dot")
+            }
+            "expr" => match Expressions::extract(&self.circuit) {
+                Ok(expressions) => (expressions.to_text(), "txt"),
+                Err(e) => {
+                    println!("Error extracting expressions: {}", e);
+                    return;
+                }
+            },
+            _ => {
+                println!("unknown export format '{}'", format);
+                return;
+            }
+        };
+
+        if let Err(e) = export_to_file(&contents, file_name, extension) {
+            println!("Error exporting: {}", e);
+        }
+    }
+
+    // compares the circuit on screen against a save file, IN / OUT gates are matched by name
+    // when both use the same names and by position otherwise
+    fn check_equivalence(&mut self) {
+        let file_path = "tmp/saves/".to_string() + &read_line("compare with file: ");
+        let other = match formats::load(&file_path) {
+            Ok(other) => other,
+            Err(e) => {
+                println!("Error loading: {}", e);
+                return;
+            }
+        };
+
+        match equivalence::check(&self.circuit, &other, Matching::guess(&self.circuit, &other)) {
+            Ok(result) => print!("{}", result.to_text()),
+            Err(e) => println!("Error checking equivalence: {}", e),
+        }
+    }
+
+    // swaps in a whole circuit, the RTree is rebuilt to match its gates
+    pub fn set_circuit(&mut self, circuit: Circuit) {
+        self.circuit = circuit;
+        self.tree = RTree::new();
+        for (key, gate) in &self.circuit.gates {
+            self.tree.insert(SpatialBlockIndex {
+                rect: gate.rect,
+                index: key,
+            });
+        }
+        self.state = InputState::Idle;
+    }
+
+    // undo / redo only take back edits, the tick count keeps going so rewind and the
+    // waveforms stay in order
+    fn restore_edit(&mut self, mut circuit: Circuit) {
+        circuit.tick_count = self.circuit.tick_count;
+        self.set_circuit(circuit);
+    }
+
+    // every breakpoint is checked so their edge detection stays up to date, the first one that
+    // fires pauses emulation
+    fn check_breakpoints(&mut self) {
+        for breakpoint in &mut self.breakpoints {
+            if let Some(wires) = breakpoint.check(&self.circuit)
+                && self.breakpoint_hit.is_none()
+            {
+                self.emulate = false;
+                println!("breakpoint '{}' hit at tick {}", breakpoint.text, self.circuit.tick_count);
+                self.breakpoint_hit = Some((self.circuit.tick_count, breakpoint.text.clone(), wires));
+            }
+        }
+    }
+
+    fn add_breakpoint(&mut self) {
+        let text = read_line("break when (e.g. 'rise clk', 'bus == 0x3F', 'OUT a != OUT b'), 'list' or 'clear': ");
+        match text.as_str() {
+            "" => {}
+            "list" => {
+                for breakpoint in &self.breakpoints {
+                    println!("{}", breakpoint.text);
+                }
+            }
+            "clear" => {
+                self.breakpoints.clear();
+                self.breakpoint_hit = None;
+            }
+            _ => match Breakpoint::parse(&text, &self.circuit) {
+                Ok(breakpoint) => self.breakpoints.push(breakpoint),
+                Err(e) => println!("Error adding breakpoint: {}", e),
+            },
+        }
+    }
+
+    // adds the wire to the watch list under a name read from stdin, or takes it off if it's on already
+    fn toggle_probe(&mut self, wire_key: WireKey) {
+        self.undo_stack.checkpoint(&self.circuit);
+        if let Some(index) = self.circuit.probes.iter().position(|probe| probe.wire == wire_key) {
+            let probe = self.circuit.probes.remove(index);
+            println!("removed probe {}", probe.name);
+            return;
+        }
+
+        let mut name = read_line("probe name: ");
+        if name.is_empty() {
+            name = format!("probe{}", self.circuit.probes.len());
+        }
+        self.circuit.probes.push(Probe::new(name, wire_key));
+    }
+
+    // records the output wires of the selected gates, or stops recording them if all already are
+    fn toggle_recorded(&mut self, sp_gates: &[SpatialBlockIndex]) {
+        let mut wires = vec![];
+        for sp_gate in sp_gates {
+            let gate = &self.circuit.gates[sp_gate.index];
+            for (pin_index, pin) in gate.output.iter().enumerate() {
+                if let Some(wire_key) = pin.wire_index {
+                    let name = gate.label.clone().unwrap_or(gate.gate_type.text().to_string());
+                    let name = match gate.output.len() {
+                        1 => name,
+                        _ => format!("{}.{}", name, pin_index),
+                    };
+                    wires.push((wire_key, name));
+                }
+            }
+        }
+
+        if wires.iter().all(|(wire_key, _)| self.recorder.is_recorded(*wire_key)) {
+            for (wire_key, _) in &wires {
+                self.recorder.remove(*wire_key);
+            }
+        } else {
+            for (wire_key, name) in &wires {
+                self.recorder.add(*wire_key, name);
+            }
+        }
+        println!("recording {} wires", self.recorder.signals.len());
+    }
+
+    fn run_test_bench(&mut self) {
+        let file_path = "tmp/benches/".to_string() + &read_line("test bench file: ");
+        let result = std::fs::read_to_string(&file_path)
+            .map_err(|e| format!("{}: {}", file_path, e))
+            .and_then(|text| TestBench::parse(&text))
+            .and_then(|bench| bench.run(&self.circuit));
+        match result {
+            Ok(report) => print!("{}", report.to_text()),
+            Err(e) => println!("Error running test bench: {}", e),
+        }
+    }
+
+    fn synthesize(&mut self) {
+        let text = read_line("expression (e.g. f = (a & b) | !c), or 'table' to paste a truth table: ");
+        let spec = match text.as_str() {
+            "table" => {
+                println!("paste the truth table ('a b | f' header, then rows), end with an empty line: ");
+                let mut table = String::new();
+                loop {
+                    let line = read_line("");
+                    if line.is_empty() {
+                        break;
+                    }
+                    table += &(line + "\n");
+                }
+                FunctionSpec::from_truth_table(&table)
+            }
+            _ => FunctionSpec::from_expression(&text),
+        };
+
+        match spec {
+            Ok(spec) => {
+                self.undo_stack.checkpoint(&self.circuit);
+                let gates = self.place_netlist(&spec.synthesize());
+                println!("synthesized {} gates", gates.len());
+            }
+            Err(e) => println!("Error synthesizing: {}", e),
+        }
+    }
+
+    // drops the netlist to the right of the existing gates, returns the new gates in node order
+    fn place_netlist(&mut self, netlist: &Netlist) -> Vec<GateKey> {
+        let origin = match self.circuit.bounds() {
+            Some(bounds) => vec2(bounds.right() + 2.0 * 64.0, bounds.y),
+            None => self.camera.target,
+        };
+
+        let keys: Vec<GateKey> = self
+            .place_nodes(netlist, origin, |_| true)
+            .into_iter()
+            .map(|key| key.unwrap())
+            .collect();
+
+        for edge in &netlist.edges {
+            self.circuit.connect_wire(
+                keys[edge.from],
+                keys[edge.to],
+                edge.from_pin,
+                PinType::Output,
+                edge.to_pin,
+                PinType::Input,
+            );
+        }
+        keys
+    }
+
+    // places the nodes 'keep' accepts at their layout cell from origin, moving right until they
+    // don't overlap any gate. None for the nodes that were left out
+    fn place_nodes(
+        &mut self,
+        netlist: &Netlist,
+        origin: Vec2,
+        keep: impl Fn(&NetNode) -> bool,
+    ) -> Vec<Option<GateKey>> {
+        let cells = layout::layered(netlist);
+        let kept_cells: Vec<(i32, i32)> = netlist
+            .nodes
+            .iter()
+            .zip(&cells)
+            .filter(|(node, _)| keep(node))
+            .map(|(_, cell)| *cell)
+            .collect();
+
+        let origin = self.free_origin(&kept_cells, origin);
+
+        netlist
+            .nodes
+            .iter()
+            .zip(cells)
+            .map(|(node, (x, y))| {
+                if !keep(node) {
+                    return None;
+                }
+                let pos = origin + vec2(x as f32, y as f32) * 64.0;
+                let key = self.place_gate(pos, node.gate_type.clone(), Rotation::Up);
+                self.circuit.gates[key].label = node.label.clone();
+                self.circuit.gates[key].active = node.active;
+                Some(key)
+            })
+            .collect()
+    }
+
+    // moves origin right until gates at 'cells' from it don't overlap any gate in the tree
+    fn free_origin(&self, cells: &[(i32, i32)], mut origin: Vec2) -> Vec2 {
+        let overlaps = |origin: Vec2| {
+            cells.iter().any(|(x, y)| {
+                let corner = vec2(origin.x.align(64.0), origin.y.align(64.0)) + vec2(*x as f32, *y as f32) * 64.0;
+                let envelope = AABB::from_corners(
+                    [corner.x + 1.0, corner.y + 1.0],
+                    [corner.x + 64.0 - 2.0, corner.y + 64.0 - 2.0],
+                );
+                self.tree.locate_in_envelope_intersecting(&envelope).next().is_some()
+            })
+        };
+        while overlaps(origin) {
+            origin.x += 64.0;
+        }
+        origin
+    }
+
+    // lays the selected gates out again in layers from the corner of the selection, they stay
+    // selected at their new places
+    fn tidy_selection(&mut self, sp_gates: &[SpatialBlockIndex], gates_rect: Rect) {
+        self.undo_stack.checkpoint(&self.circuit);
+        let keys: Vec<GateKey> = sp_gates.iter().map(|sp| sp.index).collect();
+        for key in &keys {
+            self.tree.remove(&SpatialBlockIndex { rect: self.circuit.gates[*key].rect, index: *key });
+        }
+
+        let cells = layout::arrange(&self.circuit, &keys);
+        let origin = self.free_origin(&cells, vec2(gates_rect.x, gates_rect.y));
+        let corner = vec2(origin.x.align(64.0), origin.y.align(64.0));
+        let mut tidied = vec![];
+        for (key, (x, y)) in keys.into_iter().zip(cells) {
+            let gate = &mut self.circuit.gates[key];
+            let pos = corner + vec2(x as f32, y as f32) * 64.0;
+            gate.offset(pos - vec2(gate.rect.x, gate.rect.y));
+            let sp = SpatialBlockIndex { rect: gate.rect, index: key };
+            self.tree.insert(sp);
+            tidied.push(sp);
+        }
+
+        let gates_rect = tidied.iter().skip(1).fold(tidied[0].rect, |rect, sp| rect.combine_with(sp.rect));
+        self.state = InputState::SelectedGates { sp_gates: tidied, gates_rect };
+    }
+
+    // swaps the selected gates for a minimized network with the same boundary outputs
+    fn minimize_selection(&mut self, sp_gates: &[SpatialBlockIndex], gates_rect: Rect) {
+        let gates: HashSet<GateKey> = sp_gates.iter().map(|sp| sp.index).collect();
+        let (boundary, spec) = match group_function(&self.circuit, &gates) {
+            Ok(result) => result,
+            Err(e) => {
+                println!("Error minimizing: {}", e);
+                return;
+            }
+        };
+
+        let netlist = spec.synthesize();
+        let is_port = |node: &NetNode| node.gate_type == GateType::IN || node.gate_type == GateType::OUT;
+        let logic_count = netlist.nodes.iter().filter(|node| !is_port(node)).count();
+        if logic_count >= gates.len() {
+            println!("selection is already minimal ({} gates, minimized {})", gates.len(), logic_count);
+            return;
+        }
+        self.undo_stack.checkpoint(&self.circuit);
+
+        // removing a gate removes every wire touching it, remember where they went outside
+        let sources: Vec<Connection> = boundary
+            .inputs
+            .iter()
+            .map(|wire_key| self.circuit.wires[*wire_key].source.clone())
+            .collect();
+        let sinks: Vec<Vec<Connection>> = boundary
+            .outputs
+            .iter()
+            .map(|wire_key| {
+                self.circuit.wires[*wire_key]
+                    .connections
+                    .iter()
+                    .filter(|connection| !gates.contains(&connection.gate_index))
+                    .cloned()
+                    .collect()
+            })
+            .collect();
+        let mut outside_links: Vec<(Connection, Connection)> = vec![];
+        for wire_key in &boundary.inputs {
+            let wire = &self.circuit.wires[*wire_key];
+            for connection in &wire.connections {
+                if !gates.contains(&connection.gate_index) {
+                    outside_links.push((wire.source.clone(), connection.clone()));
+                }
+            }
+        }
+
+        for sp in sp_gates {
+            self.tree.remove(sp);
+            self.circuit.remove_gate(sp.index);
+        }
+
+        // the IN column of the layout is left out, so the logic starts where the selection was
+        let origin = vec2(gates_rect.x, gates_rect.y) - vec2(layout::SPACING as f32 * 64.0, 0.0);
+        let keys = self.place_nodes(&netlist, origin, |node| !is_port(node));
+
+        let port_index = |label: &Option<String>| -> usize { label.as_ref().unwrap()[1..].parse().unwrap() };
+        for edge in &netlist.edges {
+            let from = match netlist.nodes[edge.from].gate_type {
+                GateType::IN => sources[port_index(&netlist.nodes[edge.from].label)].clone(),
+                _ => Connection { gate_index: keys[edge.from].unwrap(), pin_index: edge.from_pin },
+            };
+            let to = match netlist.nodes[edge.to].gate_type {
+                GateType::OUT => sinks[port_index(&netlist.nodes[edge.to].label)].clone(),
+                _ => vec![Connection { gate_index: keys[edge.to].unwrap(), pin_index: edge.to_pin }],
+            };
+            for to in to {
+                outside_links.push((from.clone(), to));
+            }
+        }
+
+        for (from, to) in outside_links {
+            self.circuit.connect_wire(
+                from.gate_index,
+                to.gate_index,
+                from.pin_index,
+                PinType::Output,
+                to.pin_index,
+                PinType::Input,
+            );
+        }
+        println!("replaced {} gates with {}", gates.len(), logic_count);
+    }
+
+    fn place_gate(&mut self, pos: Vec2, gate_type: GateType, gate_rotation: Rotation) -> GateKey {
         let snap_x = pos.x.align(64.0);
         let snap_y = pos.y.align(64.0);
         let rect = Rect::new(snap_x, snap_y, 64.0, 64.0);
@@ -919,6 +1506,7 @@ impl Simulator {
             .gates
             .insert(Gate::new(rect, gate_rotation, gate_type));
         self.tree.insert(SpatialBlockIndex { rect, index: idx });
+        idx
     }
 
     fn find_hovered_pin(
@@ -2,12 +2,14 @@ use crate::types::gate::*;
 use crate::types::gate_type::*;
 use crate::types::keys::*;
 use crate::types::pin_type::*;
+use crate::types::probe::*;
 use crate::types::wires::*;
 use macroquad::prelude::*;
 use slotmap::{SecondaryMap, SlotMap};
 use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
 
-#[derive(Serialize, Deserialize)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Circuit {
     #[serde(skip)] 
     pub emulation_done: bool,
@@ -17,6 +19,10 @@ pub struct Circuit {
     #[serde(skip)] 
     pub wires_write: SecondaryMap<WireKey, bool>,
     pub gates: SlotMap<GateKey, Gate>,
+    pub probes: Vec<Probe>,
+    // ticks run since the circuit was created or loaded
+    #[serde(skip)]
+    pub tick_count: usize,
 }
 
 // When loading, Serde needs to know how to create the skipped fields.
@@ -28,6 +34,8 @@ impl Default for Circuit {
             wires_read: SecondaryMap::new(),
             wires_write: SecondaryMap::new(),
             gates: SlotMap::with_key(),
+            probes: vec![],
+            tick_count: 0,
         }
     }
 }
@@ -41,18 +49,14 @@ impl Circuit {
             wires_read: SecondaryMap::new(),
             wires_write: SecondaryMap::new(),
             gates: SlotMap::with_key(),
+            probes: vec![],
+            tick_count: 0,
         };
     }
 
     pub fn evaluate(&self, gate: &Gate) -> bool {
         // each wire sample should be false if theres no wire.
-        let get_pin = |index: usize| -> bool {
-            gate.input[index]
-                .wire_index
-                .and_then(|index| self.wires_read.get(index))
-                .copied()
-                .unwrap_or(false)
-        };
+        let get_pin = |index: usize| -> bool { self.read_input(gate, index) };
 
         match (&gate.gate_type, gate.input.len()) {
             (GateType::NOT, 1) => !get_pin(0),
@@ -63,6 +67,9 @@ impl Circuit {
             (GateType::AND, 2) => get_pin(0) & get_pin(1),
             (GateType::NAND, 2) => !(get_pin(0) & get_pin(1)),
             (GateType::IN, 0) => gate.active,
+            (GateType::DFF, 2) => gate.active,
+            // only groups its gates, they are simulated on their own
+            (GateType::CUSTOM { .. }, _) => false,
             // Assuming GND input might be connected or floating, but output is always false
             (GateType::OUT, _) => false,
             _ => panic!("Unsupported gate type or input configuration"),
@@ -232,6 +239,124 @@ impl Circuit {
         for (_, value) in self.wires_write.iter_mut() {
             *value = false;
         }
+        for probe in &mut self.probes {
+            probe.reset_stats();
+        }
+        for (_, gate) in self.gates.iter_mut() {
+            if gate.gate_type == GateType::DFF {
+                gate.active = false;
+            }
+        }
+    }
+
+    // wires_read / wires_write aren't saved, every wire needs an entry after loading
+    pub fn init_wire_buffers(&mut self) {
+        for wire_key in self.wires.keys() {
+            self.wires_read.insert(wire_key, false);
+            self.wires_write.insert(wire_key, false);
+        }
+    }
+
+    // value arriving at an input pin, false if the pin has no wire
+    pub fn read_input(&self, gate: &Gate, index: usize) -> bool {
+        gate.input[index]
+            .wire_index
+            .and_then(|index| self.wires_read.get(index))
+            .copied()
+            .unwrap_or(false)
+    }
+
+    // IN / OUT gates sorted top-down then left-right, this order is what names them (in0, in1, ...)
+    pub fn io_gates(&self, gate_type: GateType) -> Vec<GateKey> {
+        let mut keys: Vec<GateKey> = self
+            .gates
+            .iter()
+            .filter(|(_, gate)| gate.gate_type == gate_type)
+            .map(|(key, _)| key)
+            .collect();
+        keys.sort_by(|a, b| {
+            let (a, b) = (self.gates[*a].rect, self.gates[*b].rect);
+            a.y.total_cmp(&b.y).then(a.x.total_cmp(&b.x))
+        });
+        keys
+    }
+
+    // the label if there is one, otherwise the type and position in io_gates (in0, out2, ...)
+    pub fn io_names(&self, gate_type: GateType) -> Vec<(GateKey, String)> {
+        self.io_gates(gate_type.clone())
+            .into_iter()
+            .enumerate()
+            .map(|(index, key)| match &self.gates[key].label {
+                Some(label) => (key, label.clone()),
+                None => (key, format!("{}{}", gate_type.text(), index)),
+            })
+            .collect()
+    }
+
+    // smallest rect around every gate, None for an empty circuit
+    pub fn bounds(&self) -> Option<Rect> {
+        self.gates
+            .values()
+            .map(|gate| gate.rect)
+            .reduce(|a, b| a.combine_with(b))
+    }
+
+    // wire whose drawn line passes within tolerance of point, wires are straight lines from the
+    // source pin to every connected pin
+    pub fn wire_at(&self, point: Vec2, tolerance: f32) -> Option<WireKey> {
+        let distance = |a: Vec2, b: Vec2| {
+            let segment = b - a;
+            let t = ((point - a).dot(segment) / segment.length_squared().max(f32::EPSILON)).clamp(0.0, 1.0);
+            point.distance(a + segment * t)
+        };
+
+        for (wire_key, wire) in &self.wires {
+            let Some(source_gate) = self.gates.get(wire.source.gate_index) else {
+                continue;
+            };
+            let start = source_gate.get_pin_rect(wire.source.pin_index, PinType::Output).center();
+            for connection in &wire.connections {
+                if let Some(gate) = self.gates.get(connection.gate_index) {
+                    let end = gate.get_pin_rect(connection.pin_index, PinType::Input).center();
+                    if distance(start, end) <= tolerance {
+                        return Some(wire_key);
+                    }
+                }
+            }
+        }
+        None
+    }
+
+    // tick until nothing changes or max_ticks is reached, returns the ticks used
+    pub fn settle(&mut self, max_ticks: usize) -> usize {
+        let mut ticks = 0;
+        self.emulation_done = false;
+        while !self.emulation_done && ticks < max_ticks {
+            self.tick();
+            ticks += 1;
+        }
+        ticks
+    }
+
+    // for when gates were re-inserted under new keys, keys missing from key_map are left alone
+    pub fn remap_gate_keys(&mut self, key_map: &HashMap<GateKey, GateKey>) {
+        let remap = |key: &mut GateKey| {
+            if let Some(new_key) = key_map.get(key) {
+                *key = *new_key;
+            }
+        };
+
+        for (_, wire) in self.wires.iter_mut() {
+            remap(&mut wire.source.gate_index);
+            for connection in &mut wire.connections {
+                remap(&mut connection.gate_index);
+            }
+        }
+        for (_, gate) in self.gates.iter_mut() {
+            if let GateType::CUSTOM { gates, .. } = &mut gate.gate_type {
+                gates.iter_mut().for_each(remap);
+            }
+        }
     }
 
     pub fn new_wire(&mut self, wire: Wire) -> WireKey {
@@ -272,6 +397,7 @@ impl Circuit {
         self.wires.remove(key);
         self.wires_read.remove(key);
         self.wires_write.remove(key);
+        self.probes.retain(|probe| probe.wire != key);
     }
 
     pub fn remove_gate(&mut self, gate_id: GateKey) {
@@ -303,12 +429,213 @@ impl Circuit {
         self.gates.remove(gate_id);
     }
 
+    // what repair would fix, without changing anything
+    pub fn problems(&self) -> Vec<String> {
+        self.clone().repair()
+    }
+
+    // drops every reference between gates and wires that the other side doesn't confirm and
+    // returns what was wrong. a wire has to start at an output pin pointing back at it and each
+    // connection needs an input pin doing the same, anything else would panic when drawing or
+    // ticking. dropping one reference can break another so it runs until nothing changes
+    pub fn repair(&mut self) -> Vec<String> {
+        let mut problems = vec![];
+        loop {
+            let found = problems.len();
+            self.repair_pass(&mut problems);
+            if problems.len() == found {
+                break;
+            }
+        }
+        self.wires_read.retain(|key, _| self.wires.contains_key(key));
+        self.wires_write.retain(|key, _| self.wires.contains_key(key));
+        self.init_wire_buffers();
+        problems
+    }
+
+    fn repair_pass(&mut self, problems: &mut Vec<String>) {
+        // evaluate and tick index pins by gate type, a gate with the wrong count gets fresh pins.
+        // their wires lose the reference back and are dropped below
+        for (gate_key, gate) in self.gates.iter_mut() {
+            let inputs = gate.gate_type.input_count();
+            let outputs = gate.gate_type.output_count();
+            if gate.input.len() != inputs || gate.output.len() != outputs {
+                problems.push(format!(
+                    "gate {:?} has {} inputs and {} outputs instead of {} and {}",
+                    gate_key,
+                    gate.input.len(),
+                    gate.output.len(),
+                    inputs,
+                    outputs
+                ));
+                (gate.input, gate.output) = Gate::get_pins(gate.rect, gate.gate_type.clone(), gate.rotation.clone());
+            }
+        }
+
+        // pins are looked up by position, their index has to match it
+        for (gate_key, gate) in self.gates.iter_mut() {
+            for (index, pin) in gate.input.iter_mut().enumerate().chain(gate.output.iter_mut().enumerate()) {
+                if pin.index != index {
+                    problems.push(format!("pin {} of gate {:?} was numbered {}", index, gate_key, pin.index));
+                    pin.index = index;
+                }
+            }
+        }
+
+        let sourceless: Vec<WireKey> = self
+            .wires
+            .iter()
+            .filter(|(key, wire)| {
+                self.gates
+                    .get(wire.source.gate_index)
+                    .and_then(|gate| gate.output.get(wire.source.pin_index))
+                    .is_none_or(|pin| pin.wire_index != Some(*key))
+            })
+            .map(|(key, _)| key)
+            .collect();
+        for key in sourceless {
+            let source = &self.wires[key].source;
+            problems.push(format!(
+                "wire {:?} starts at output {} of gate {:?}, which isn't connected to it",
+                key, source.pin_index, source.gate_index
+            ));
+            self.wires.remove(key);
+        }
+
+        for (key, wire) in self.wires.iter_mut() {
+            let mut seen = vec![];
+            wire.connections.retain(|connection| {
+                let pin = (connection.gate_index, connection.pin_index);
+                let confirmed = self
+                    .gates
+                    .get(connection.gate_index)
+                    .and_then(|gate| gate.input.get(connection.pin_index))
+                    .is_some_and(|pin| pin.wire_index == Some(key));
+                if !confirmed {
+                    problems.push(format!(
+                        "wire {:?} goes to input {} of gate {:?}, which isn't connected to it",
+                        key, pin.1, pin.0
+                    ));
+                } else if seen.contains(&pin) {
+                    problems.push(format!("wire {:?} goes to input {} of gate {:?} twice", key, pin.1, pin.0));
+                    return false;
+                }
+                seen.push(pin);
+                confirmed
+            });
+        }
+
+        for (gate_key, gate) in self.gates.iter_mut() {
+            for pin in &mut gate.input {
+                if let Some(wire_key) = pin.wire_index
+                    && self
+                        .wires
+                        .get(wire_key)
+                        .is_none_or(|wire| wire.connections.find_pin_index(gate_key, pin.index).is_none())
+                {
+                    match self.wires.contains_key(wire_key) {
+                        true => problems.push(format!("input {} of gate {:?} points at wire {:?}, which doesn't reach it", pin.index, gate_key, wire_key)),
+                        false => problems.push(format!("input {} of gate {:?} points at missing wire {:?}", pin.index, gate_key, wire_key)),
+                    }
+                    pin.wire_index = None;
+                }
+            }
+            for pin in &mut gate.output {
+                if let Some(wire_key) = pin.wire_index
+                    && self
+                        .wires
+                        .get(wire_key)
+                        .is_none_or(|wire| wire.source.gate_index != gate_key || wire.source.pin_index != pin.index)
+                {
+                    match self.wires.contains_key(wire_key) {
+                        true => problems.push(format!("output {} of gate {:?} points at wire {:?}, which doesn't start there", pin.index, gate_key, wire_key)),
+                        false => problems.push(format!("output {} of gate {:?} points at missing wire {:?}", pin.index, gate_key, wire_key)),
+                    }
+                    pin.wire_index = None;
+                }
+            }
+        }
+
+        // everything left is consistent, so remove_wire can clean up after these
+        let unconnected: Vec<WireKey> = self
+            .wires
+            .iter()
+            .filter(|(_, wire)| wire.connections.is_empty())
+            .map(|(key, _)| key)
+            .collect();
+        for key in unconnected {
+            problems.push(format!("wire {:?} isn't connected to any input", key));
+            self.remove_wire(key);
+        }
+
+        let wires = &self.wires;
+        self.probes.retain(|probe| {
+            if !wires.contains_key(probe.wire) {
+                problems.push(format!("probe '{}' watches missing wire {:?}", probe.name, probe.wire));
+            }
+            wires.contains_key(probe.wire)
+        });
+
+        // a custom gate has a pin per boundary wire, dropping wires changes its pins
+        let gate_keys: HashSet<GateKey> = self.gates.keys().collect();
+        for (gate_key, gate) in self.gates.iter_mut() {
+            let GateType::CUSTOM { gates, inputs, outputs, .. } = &mut gate.gate_type else {
+                continue;
+            };
+            gates.retain(|member| {
+                if !gate_keys.contains(member) {
+                    problems.push(format!("custom gate {:?} holds missing gate {:?}", gate_key, member));
+                }
+                gate_keys.contains(member)
+            });
+            let boundary = inputs.len() + outputs.len();
+            for wires in [inputs, outputs] {
+                wires.retain(|wire| {
+                    if !self.wires.contains_key(*wire) {
+                        problems.push(format!("custom gate {:?} has missing boundary wire {:?}", gate_key, wire));
+                    }
+                    self.wires.contains_key(*wire)
+                });
+            }
+
+            if let GateType::CUSTOM { inputs, outputs, .. } = &gate.gate_type
+                && inputs.len() + outputs.len() != boundary
+            {
+                // pins that are gone take their wires along on the next pass
+                let (mut input, mut output) = Gate::get_pins(gate.rect, gate.gate_type.clone(), gate.rotation.clone());
+                for (pin, old) in input.iter_mut().zip(&gate.input) {
+                    pin.wire_index = old.wire_index;
+                }
+                for (pin, old) in output.iter_mut().zip(&gate.output) {
+                    pin.wire_index = old.wire_index;
+                }
+                gate.input = input;
+                gate.output = output;
+            }
+        }
+    }
+
     pub fn tick(&mut self) {
         // check for same length so no problems when swapping
         if self.wires_read.len() != self.wires_write.len() {
             panic!("wire buffers are not of the same length");
         }
 
+        // flip-flops store d on a rising clock, wires_write still holds the clock of the tick before
+        let clocked: Vec<(GateKey, bool)> = self
+            .gates
+            .iter()
+            .filter(|(_, gate)| gate.gate_type == GateType::DFF)
+            .filter(|(_, gate)| {
+                let previous = gate.input[1].wire_index.and_then(|index| self.wires_write.get(index)).copied();
+                self.read_input(gate, 1) && previous == Some(false)
+            })
+            .map(|(key, gate)| (key, self.read_input(gate, 0)))
+            .collect();
+        for (key, value) in clocked {
+            self.gates[key].active = value;
+        }
+
         let mut changed_wires: SecondaryMap<WireKey, bool> = SecondaryMap::new();
         // read
         for (_, gate) in &self.gates {
@@ -334,7 +661,7 @@ impl Circuit {
             }
         }
 
-        // check if emulation is done (add output test in future)
+        // check if emulation is done, expected OUT values are checked by analysis::test_bench
         self.emulation_done = true;
         for (index, value) in self.wires_read.iter() {
             if *value != self.wires_write[index] {
@@ -343,5 +670,64 @@ impl Circuit {
         }
         // write
         std::mem::swap(&mut self.wires_read, &mut self.wires_write);
+        self.tick_count += 1;
+
+        for probe in &mut self.probes {
+            if let Some(value) = self.wires_read.get(probe.wire) {
+                probe.update(*value, self.tick_count);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn add(circuit: &mut Circuit, gate_type: GateType, y: f32) -> GateKey {
+        circuit.gates.insert(Gate::new(Rect::new(0.0, y * 64.0, 64.0, 64.0), Rotation::Up, gate_type))
+    }
+
+    #[test]
+    fn repair_leaves_a_corrupted_circuit_that_ticks() {
+        let mut circuit = Circuit::new();
+        let a = add(&mut circuit, GateType::IN, 0.0);
+        let b = add(&mut circuit, GateType::IN, 1.0);
+        let clock = add(&mut circuit, GateType::IN, 2.0);
+        let and = add(&mut circuit, GateType::AND, 3.0);
+        let dff = add(&mut circuit, GateType::DFF, 4.0);
+        let out = add(&mut circuit, GateType::OUT, 5.0);
+        circuit.connect_wire(a, and, 0, PinType::Output, 0, PinType::Input);
+        circuit.connect_wire(b, and, 0, PinType::Output, 1, PinType::Input);
+        circuit.connect_wire(and, out, 0, PinType::Output, 0, PinType::Input);
+        circuit.connect_wire(and, dff, 0, PinType::Output, 0, PinType::Input);
+        circuit.connect_wire(clock, dff, 0, PinType::Output, 1, PinType::Input);
+        assert!(circuit.problems().is_empty());
+
+        // a flip-flop missing its clock pin, tick would index past the end
+        circuit.gates[dff].input.truncate(1);
+        // a wire gone while its ends still point at it
+        let b_wire = circuit.gates[b].output[0].wire_index.unwrap();
+        circuit.wires.remove(b_wire);
+        // a connection to a pin the gate doesn't have
+        let a_wire = circuit.gates[a].output[0].wire_index.unwrap();
+        circuit.wires[a_wire].connections.push(Connection { pin_index: 7, gate_index: and });
+        circuit.gates[out].input[0].index = 3;
+
+        let problems = circuit.problems();
+        assert!(problems.len() >= 4, "{:?}", problems);
+        assert_eq!(circuit.repair(), problems);
+        assert!(circuit.problems().is_empty());
+
+        assert_eq!(circuit.gates[dff].input.len(), 2);
+        assert!(circuit.gates[dff].input.iter().all(|pin| pin.wire_index.is_none()));
+        assert!(circuit.gates[clock].output[0].wire_index.is_none());
+        assert!(circuit.gates[b].output[0].wire_index.is_none());
+        assert!(circuit.gates[and].input[1].wire_index.is_none());
+        assert_eq!(circuit.wires[a_wire].connections.len(), 1);
+        assert!(circuit.gates[out].input[0].wire_index.is_some());
+        for _ in 0..4 {
+            circuit.tick();
+        }
     }
 }
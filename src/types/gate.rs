@@ -8,6 +8,7 @@ use serde::{Deserialize, Serialize};
 const GATE_SIZE: u16 = 64;
 const PIN_SIZE: u16 = 6;
 const PIN_PIXEL_SIDE_LEN: f32 = PIN_SIZE as f32;
+const LABEL_FONT_SIZE: u16 = 20;
 use crate::utils::rect_serde;
 
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
@@ -51,6 +52,9 @@ pub struct Gate {
     pub output: Pins,
     pub gate_type: GateType,
     pub active: bool,
+    // shown above the gate, IN / OUT gates are named by it
+    #[serde(default)]
+    pub label: Option<String>,
 }
 
 impl Gate {
@@ -63,7 +67,9 @@ impl Gate {
             input: input,
             output: output,
             gate_type: gate_type.clone(),
-            active: true,
+            // flip-flops start cleared
+            active: gate_type != GateType::DFF,
+            label: None,
         };
     }
 
@@ -215,6 +221,20 @@ impl Gate {
                     ..Default::default()
                 },
             );
+
+            if let Some(label) = &self.label {
+                let dims = measure_text(label, None, LABEL_FONT_SIZE, 1.0);
+                draw_text_ex(
+                    label,
+                    self.rect.x + self.rect.w * 0.5 - dims.width * 0.5,
+                    self.rect.y - 4.0,
+                    TextParams {
+                        font_size: LABEL_FONT_SIZE,
+                        color: BLACK,
+                        ..Default::default()
+                    },
+                );
+            }
         }
     }
 
@@ -22,6 +22,8 @@ pub enum GateType {
     inputs: Vec<WireKey>, // all wires that have input / output as source, should be generated top-down
     outputs: Vec<WireKey>
     },
+    // rising edge d flip-flop, input 0 is d and input 1 the clock, the stored bit is gate.active
+    DFF,
 }
 
 impl GateType {
@@ -37,6 +39,7 @@ impl GateType {
             GateType::IN => YELLOW,
             GateType::OUT => DARKGRAY,
             GateType::CUSTOM{color, ..} => *color,
+            GateType::DFF => GREEN,
         };
     }
 
@@ -52,6 +55,7 @@ impl GateType {
             GateType::IN => "in",
             GateType::OUT => "out",
             GateType::CUSTOM {text, .. } => text,
+            GateType::DFF => "dff",
         };
     }
 
@@ -67,6 +71,7 @@ impl GateType {
             GateType::IN => 0,
             GateType::OUT => 1,
             GateType::CUSTOM {inputs, .. } => inputs.len(),
+            GateType::DFF => 2,
         };
     }
 
@@ -82,6 +87,7 @@ impl GateType {
             GateType::IN => 1,
             GateType::OUT => 0,
             GateType::CUSTOM { outputs, .. } => outputs.len(),
+            GateType::DFF => 1,
         };
     }
 }
@@ -0,0 +1,222 @@
+// layouts of saves written by older versions, bincode can't skip missing fields so they are
+// read into these and upgraded one version at a time. changing the saved layout means freezing
+// the current one here as CircuitV{SAVE_VERSION}, giving it an upgrade to the new layout and
+// bumping SAVE_VERSION
+use crate::types::circuit::*;
+use crate::types::gate::*;
+use crate::types::gate_type::*;
+use crate::types::keys::*;
+use crate::types::pins::*;
+use crate::types::wires::*;
+use crate::utils::rect_serde;
+use macroquad::prelude::*;
+use bincode::Options;
+use serde::Deserialize;
+use serde::de::DeserializeOwned;
+use slotmap::SlotMap;
+use std::collections::HashMap;
+
+// before gates had labels
+#[derive(Deserialize)]
+pub struct GateV0 {
+    pub rotation: Rotation,
+    #[serde(with = "rect_serde")]
+    pub rect: Rect,
+    pub input: Pins,
+    pub output: Pins,
+    pub gate_type: GateType,
+    pub active: bool,
+}
+
+#[derive(Deserialize)]
+pub struct CircuitV0 {
+    pub wires: SlotMap<WireKey, Wire>,
+    pub gates: SlotMap<GateKey, GateV0>,
+}
+
+impl CircuitV0 {
+    pub fn upgrade(self) -> CircuitV1 {
+        let mut circuit = Circuit::new();
+        circuit.wires = self.wires;
+
+        // gates get new keys, everything pointing at a gate has to follow
+        let mut key_map: HashMap<GateKey, GateKey> = HashMap::new();
+        for (old_key, gate) in self.gates {
+            let new_key = circuit.gates.insert(Gate {
+                rotation: gate.rotation,
+                rect: gate.rect,
+                input: gate.input,
+                output: gate.output,
+                gate_type: gate.gate_type,
+                active: gate.active,
+                label: None,
+            });
+            key_map.insert(old_key, new_key);
+        }
+        circuit.remap_gate_keys(&key_map);
+        CircuitV1 {
+            wires: circuit.wires,
+            gates: circuit.gates,
+        }
+    }
+}
+
+// before probes were saved
+#[derive(Deserialize)]
+pub struct CircuitV1 {
+    pub wires: SlotMap<WireKey, Wire>,
+    pub gates: SlotMap<GateKey, Gate>,
+}
+
+impl CircuitV1 {
+    pub fn upgrade(self) -> Circuit {
+        let mut circuit = Circuit::new();
+        circuit.wires = self.wires;
+        circuit.gates = self.gates;
+        circuit
+    }
+}
+
+// version of the Circuit layout written after the save header
+pub const SAVE_VERSION: u32 = 2;
+// binary saves start with this and the version as a little endian u32, inside the gzip stream
+pub const SAVE_MAGIC: &[u8; 4] = b"CIRC";
+// saves without the header were all written before it existed, the newest of them is this one
+const HEADERLESS_VERSION: u32 = 2;
+
+// a save body of the given version upgraded to the current Circuit
+pub fn migrate(version: u32, bytes: &[u8]) -> Result<Circuit, String> {
+    let circuit = match version {
+        0 => decode::<CircuitV0>(bytes).map(|legacy| legacy.upgrade().upgrade()),
+        1 => decode::<CircuitV1>(bytes).map(|legacy| legacy.upgrade()),
+        SAVE_VERSION => decode::<Circuit>(bytes),
+        _ => return Err(format!("save version {} is newer than this build reads ({})", version, SAVE_VERSION)),
+    };
+    circuit.map_err(|e| format!("can't read save version {}: {}", version, e))
+}
+
+// a whole decompressed save, the version comes from the header or, for saves older than it, from
+// whichever layout reads the bytes, newest first
+pub fn read_save(bytes: &[u8]) -> Result<Circuit, String> {
+    if let Some(rest) = bytes.strip_prefix(SAVE_MAGIC.as_slice()) {
+        let Some((version, body)) = rest.split_first_chunk::<4>() else {
+            return Err("save header is cut short".to_string());
+        };
+        return migrate(u32::from_le_bytes(*version), body);
+    }
+
+    let mut error = String::new();
+    for version in (0..=HEADERLESS_VERSION).rev() {
+        match migrate(version, bytes) {
+            Ok(circuit) => return Ok(circuit),
+            // the newest layout's error is the one worth showing
+            Err(e) if error.is_empty() => error = e,
+            Err(_) => {}
+        }
+    }
+    Err(error)
+}
+
+// same encoding as bincode::serialize_into, but a save read with the wrong layout shouldn't
+// leave bytes behind
+fn decode<T: DeserializeOwned>(bytes: &[u8]) -> bincode::Result<T> {
+    bincode::DefaultOptions::new()
+        .with_fixint_encoding()
+        .reject_trailing_bytes()
+        .deserialize(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::read::GzDecoder;
+    use std::io::Read;
+
+    // every save in tmp/saves was written before the header existed
+    const SAVES: [&str; 5] = ["clock", "copy_paste", "flag", "nand", "notnand_or"];
+
+    fn unpack(name: &str) -> Vec<u8> {
+        let path = format!("{}/tmp/saves/{}.save", env!("CARGO_MANIFEST_DIR"), name);
+        let mut bytes = vec![];
+        GzDecoder::new(std::fs::File::open(path).unwrap()).read_to_end(&mut bytes).unwrap();
+        bytes
+    }
+
+    fn with_header(version: u32, body: &[u8]) -> Vec<u8> {
+        let mut bytes = SAVE_MAGIC.to_vec();
+        bytes.extend(version.to_le_bytes());
+        bytes.extend(body);
+        bytes
+    }
+
+    #[test]
+    fn headerless_saves_still_load() {
+        for name in SAVES {
+            let circuit = read_save(&unpack(name)).unwrap_or_else(|e| panic!("{}: {}", name, e));
+            assert!(!circuit.gates.is_empty(), "{}", name);
+        }
+    }
+
+    #[test]
+    fn headered_saves_round_trip() {
+        for name in SAVES {
+            let circuit = read_save(&unpack(name)).unwrap();
+            let body = bincode::serialize(&circuit).unwrap();
+            let loaded = read_save(&with_header(SAVE_VERSION, &body)).unwrap();
+            assert_eq!(bincode::serialize(&loaded).unwrap(), body, "{}", name);
+        }
+    }
+
+    #[test]
+    fn older_versions_are_upgraded() {
+        let circuit = read_save(&unpack("nand")).unwrap();
+        let gates: SlotMap<GateKey, GateV0Out> = circuit
+            .gates
+            .iter()
+            .map(|(_, gate)| GateV0Out {
+                rotation: gate.rotation.clone(),
+                rect: RectOut(gate.rect),
+                input: gate.input.clone(),
+                output: gate.output.clone(),
+                gate_type: gate.gate_type.clone(),
+                active: gate.active,
+            })
+            .fold(SlotMap::with_key(), |mut gates, gate| {
+                gates.insert(gate);
+                gates
+            });
+        let body = bincode::serialize(&(&circuit.wires, &gates)).unwrap();
+        let upgraded = read_save(&with_header(0, &body)).unwrap();
+        assert_eq!(upgraded.gates.len(), circuit.gates.len());
+        assert!(upgraded.gates.values().all(|gate| gate.label.is_none()));
+
+        let body = bincode::serialize(&(&circuit.wires, &circuit.gates)).unwrap();
+        let upgraded = read_save(&with_header(1, &body)).unwrap();
+        assert_eq!(upgraded.gates.len(), circuit.gates.len());
+    }
+
+    #[test]
+    fn newer_versions_are_refused() {
+        assert!(read_save(&with_header(SAVE_VERSION + 1, &[])).err().unwrap().contains("newer"));
+        assert!(read_save(SAVE_MAGIC).is_err());
+    }
+
+    // GateV0 as it was written, only Deserialize is derived on the real one
+    #[derive(serde::Serialize)]
+    struct GateV0Out {
+        rotation: Rotation,
+        rect: RectOut,
+        input: Pins,
+        output: Pins,
+        gate_type: GateType,
+        active: bool,
+    }
+
+    struct RectOut(Rect);
+
+    impl serde::Serialize for RectOut {
+        fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            rect_serde::serialize(&self.0, serializer)
+        }
+    }
+}
@@ -2,6 +2,10 @@ pub mod circuit;
 pub mod gate;
 pub mod gate_type;
 pub mod keys;
+pub mod legacy;
+pub mod netlist;
 pub mod pin_type;
 pub mod pins;
+pub mod probe;
+pub mod text_save;
 pub mod wires;
@@ -0,0 +1,215 @@
+use crate::types::gate_type::*;
+use std::collections::HashMap;
+
+// a circuit without positions, what synthesis and the importers produce before layout
+#[derive(Clone, Debug)]
+pub struct NetNode {
+    pub gate_type: GateType,
+    pub label: Option<String>,
+    // starting value of an IN gate or flip-flop
+    pub active: bool,
+}
+
+// output pin 'from_pin' of node 'from' drives input pin 'to_pin' of node 'to'
+#[derive(Clone, Debug, PartialEq)]
+pub struct NetEdge {
+    pub from: usize,
+    pub from_pin: usize,
+    pub to: usize,
+    pub to_pin: usize,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Netlist {
+    pub nodes: Vec<NetNode>,
+    pub edges: Vec<NetEdge>,
+}
+
+impl Netlist {
+    pub fn fan_in(&self, node: usize) -> impl Iterator<Item = &NetEdge> {
+        self.edges.iter().filter(move |edge| edge.to == node)
+    }
+
+    pub fn fan_out(&self, node: usize) -> impl Iterator<Item = &NetEdge> {
+        self.edges.iter().filter(move |edge| edge.from == node)
+    }
+}
+
+pub type NetId = usize;
+
+// builds a Netlist out of nets, each net has at most one driver and any number of sinks
+#[derive(Default)]
+pub struct NetlistBuilder {
+    nodes: Vec<NetNode>,
+    drivers: Vec<Option<(usize, usize)>>,
+    sinks: Vec<Vec<(usize, usize)>>,
+    names: HashMap<String, NetId>,
+}
+
+impl NetlistBuilder {
+    pub fn new() -> NetlistBuilder {
+        NetlistBuilder::default()
+    }
+
+    pub fn net(&mut self) -> NetId {
+        self.drivers.push(None);
+        self.sinks.push(vec![]);
+        self.drivers.len() - 1
+    }
+
+    pub fn named_net(&mut self, name: &str) -> NetId {
+        if let Some(net) = self.names.get(name) {
+            return *net;
+        }
+        let net = self.net();
+        self.names.insert(name.to_string(), net);
+        net
+    }
+
+    // an undriven net reads false, a NOT gate with a floating input reads true
+    pub fn constant(&mut self, value: bool) -> NetId {
+        match value {
+            false => self.net(),
+            true => {
+                let net = self.net();
+                self.gate(GateType::NOT, None, &[None], &[net]);
+                net
+            }
+        }
+    }
+
+    // None leaves an input pin floating
+    pub fn gate(
+        &mut self,
+        gate_type: GateType,
+        label: Option<String>,
+        inputs: &[Option<NetId>],
+        outputs: &[NetId],
+    ) -> usize {
+        let node = self.nodes.len();
+        let active = gate_type != GateType::DFF;
+        self.nodes.push(NetNode { gate_type, label, active });
+        for (pin, net) in inputs.iter().enumerate() {
+            if let Some(net) = net {
+                self.sinks[*net].push((node, pin));
+            }
+        }
+        for (pin, net) in outputs.iter().enumerate() {
+            self.drivers[*net] = Some((node, pin));
+        }
+        node
+    }
+
+    pub fn set_active(&mut self, node: usize, active: bool) {
+        self.nodes[node].active = active;
+    }
+
+    pub fn input(&mut self, name: &str) -> NetId {
+        let net = self.named_net(name);
+        self.gate(GateType::IN, Some(name.to_string()), &[], &[net]);
+        net
+    }
+
+    pub fn output(&mut self, name: &str, net: NetId) {
+        self.gate(GateType::OUT, Some(name.to_string()), &[Some(net)], &[]);
+    }
+
+    pub fn unary(&mut self, gate_type: GateType, input: NetId) -> NetId {
+        let net = self.net();
+        self.gate(gate_type, None, &[Some(input)], &[net]);
+        net
+    }
+
+    pub fn binary(&mut self, gate_type: GateType, a: NetId, b: NetId) -> NetId {
+        let net = self.net();
+        self.gate(gate_type, None, &[Some(a), Some(b)], &[net]);
+        net
+    }
+
+    // any number of inputs out of 2 input gates, as a balanced tree to keep the depth down
+    pub fn reduce(&mut self, gate_type: GateType, inputs: &[NetId]) -> NetId {
+        let (base, inverted) = match gate_type {
+            GateType::NAND => (GateType::AND, true),
+            GateType::NOR => (GateType::OR, true),
+            GateType::XNOR => (GateType::XOR, true),
+            ref other => (other.clone(), false),
+        };
+
+        if inputs.is_empty() {
+            // empty and is true, empty or / xor is false
+            let value = (base == GateType::AND) != inverted;
+            return self.constant(value);
+        }
+
+        let mut level: Vec<NetId> = inputs.to_vec();
+        while level.len() > 2 || (level.len() == 2 && !inverted) {
+            let mut next = vec![];
+            for pair in level.chunks(2) {
+                next.push(match pair {
+                    [a, b] => self.binary(base.clone(), *a, *b),
+                    [a] => *a,
+                    _ => unreachable!(),
+                });
+            }
+            level = next;
+        }
+
+        match (level.as_slice(), inverted) {
+            ([a, b], true) => self.binary(gate_type, *a, *b),
+            ([a], true) => self.unary(GateType::NOT, *a),
+            ([a], false) => *a,
+            _ => unreachable!(),
+        }
+    }
+
+    // like reduce but the last gate drives 'output', for importers whose nets are named up front.
+    // there's no buffer gate, a lone input goes through an OR with a floating pin
+    pub fn reduce_into(&mut self, gate_type: GateType, inputs: &[NetId], output: NetId) {
+        let (base, inverted) = match gate_type {
+            GateType::NAND => (GateType::AND, true),
+            GateType::NOR => (GateType::OR, true),
+            GateType::XNOR => (GateType::XOR, true),
+            ref other => (other.clone(), false),
+        };
+        match inputs {
+            [] => {
+                // an undriven net already reads false
+                if (base == GateType::AND) != inverted {
+                    self.gate(GateType::NOT, None, &[None], &[output]);
+                }
+            }
+            [a] if inverted => {
+                self.gate(GateType::NOT, None, &[Some(*a)], &[output]);
+            }
+            [a] => {
+                self.gate(GateType::OR, None, &[Some(*a), None], &[output]);
+            }
+            _ => {
+                let (left, right) = inputs.split_at(inputs.len() / 2);
+                let a = self.reduce(base.clone(), left);
+                let b = self.reduce(base, right);
+                self.gate(gate_type, None, &[Some(a), Some(b)], &[output]);
+            }
+        }
+    }
+
+    pub fn build(self) -> Netlist {
+        let mut edges = vec![];
+        for (net, driver) in self.drivers.iter().enumerate() {
+            if let Some((from, from_pin)) = driver {
+                for (to, to_pin) in &self.sinks[net] {
+                    edges.push(NetEdge {
+                        from: *from,
+                        from_pin: *from_pin,
+                        to: *to,
+                        to_pin: *to_pin,
+                    });
+                }
+            }
+        }
+        Netlist {
+            nodes: self.nodes,
+            edges,
+        }
+    }
+}
@@ -0,0 +1,44 @@
+use crate::types::keys::*;
+use serde::{Deserialize, Serialize};
+
+// a named wire on the watch list, only the name and wire are saved
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Probe {
+    pub name: String,
+    pub wire: WireKey,
+    #[serde(skip)]
+    pub last_value: Option<bool>,
+    #[serde(skip)]
+    pub toggles: usize,
+    #[serde(skip)]
+    pub last_change: Option<usize>,
+}
+
+impl Probe {
+    pub fn new(name: String, wire: WireKey) -> Probe {
+        Probe {
+            name,
+            wire,
+            last_value: None,
+            toggles: 0,
+            last_change: None,
+        }
+    }
+
+    // the first value seen isn't a toggle
+    pub fn update(&mut self, value: bool, tick: usize) {
+        if let Some(last_value) = self.last_value
+            && last_value != value
+        {
+            self.toggles += 1;
+            self.last_change = Some(tick);
+        }
+        self.last_value = Some(value);
+    }
+
+    pub fn reset_stats(&mut self) {
+        self.last_value = None;
+        self.toggles = 0;
+        self.last_change = None;
+    }
+}
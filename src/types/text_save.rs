@@ -0,0 +1,342 @@
+// the plain text save layout, meant to be read and diffed. a gate's id is the slot of its key,
+// which deleting or adding other gates doesn't change and loading gives back. gates sit on whole
+// grid cells and list where each of their inputs comes from, wires and pin rects are rebuilt on
+// load
+use crate::layout::GRID_SIZE;
+use crate::types::circuit::*;
+use crate::types::gate::*;
+use crate::types::gate_type::*;
+use crate::types::keys::*;
+use crate::types::pin_type::*;
+use crate::types::probe::*;
+use macroquad::prelude::*;
+use serde::{Deserialize, Serialize};
+use slotmap::Key;
+use std::collections::HashMap;
+
+// empty slots filled in on load to give a gate back its id, ids further out than this get the
+// next free slot instead of a pile of placeholders
+const MAX_GAP: usize = 1 << 16;
+
+// index of the key's slot, the low half of its ffi form. the version in the high half changes
+// when a slot is reused so it isn't part of the id
+fn slot(key: GateKey) -> usize {
+    (key.data().as_ffi() & 0xffff_ffff) as usize
+}
+
+// output pin 'pin' of gate 'gate', how wires are referred to
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct Source {
+    pub gate: usize,
+    pub pin: usize,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SavedCustom {
+    pub text: String,
+    pub color: [f32; 4],
+    pub gates: Vec<usize>,
+    pub inputs: Vec<Source>,
+    pub outputs: Vec<Source>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SavedGate {
+    pub id: usize,
+    // the gate text, "custom" for custom gates
+    #[serde(rename = "type")]
+    pub gate_type: String,
+    pub x: i32,
+    pub y: i32,
+    pub rotation: Rotation,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    pub active: bool,
+    // the source of every input pin, null when it isn't wired
+    pub inputs: Vec<Option<Source>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub custom: Option<SavedCustom>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SavedProbe {
+    pub name: String,
+    pub wire: Source,
+}
+
+// bumped when the layout changes in a way serde defaults can't cover, to_circuit upgrades
+// anything older. text saves written before the field are version 1
+pub const TEXT_SAVE_VERSION: u32 = 1;
+
+fn first_version() -> u32 {
+    1
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct TextSave {
+    #[serde(default = "first_version")]
+    pub version: u32,
+    pub gates: Vec<SavedGate>,
+    #[serde(default)]
+    pub probes: Vec<SavedProbe>,
+}
+
+fn gate_type_of(name: &str) -> Option<GateType> {
+    let gate_type = match name {
+        "not" => GateType::NOT,
+        "or" => GateType::OR,
+        "xor" => GateType::XOR,
+        "nor" => GateType::NOR,
+        "xnor" => GateType::XNOR,
+        "and" => GateType::AND,
+        "nand" => GateType::NAND,
+        "in" => GateType::IN,
+        "out" => GateType::OUT,
+        "dff" => GateType::DFF,
+        _ => return None,
+    };
+    Some(gate_type)
+}
+
+impl TextSave {
+    pub fn from_circuit(circuit: &Circuit) -> TextSave {
+        let ids: HashMap<GateKey, usize> = circuit.gates.keys().map(|key| (key, slot(key))).collect();
+        let source_of = |wire: WireKey| {
+            circuit.wires.get(wire).map(|wire| Source {
+                gate: ids[&wire.source.gate_index],
+                pin: wire.source.pin_index,
+            })
+        };
+
+        let gates = circuit
+            .gates
+            .iter()
+            .map(|(key, gate)| {
+                let custom = match &gate.gate_type {
+                    GateType::CUSTOM { gates, color, text, inputs, outputs } => Some(SavedCustom {
+                        text: text.clone(),
+                        color: [color.r, color.g, color.b, color.a],
+                        gates: gates.iter().filter_map(|key| ids.get(key).copied()).collect(),
+                        inputs: inputs.iter().filter_map(|wire| source_of(*wire)).collect(),
+                        outputs: outputs.iter().filter_map(|wire| source_of(*wire)).collect(),
+                    }),
+                    _ => None,
+                };
+                SavedGate {
+                    id: ids[&key],
+                    gate_type: match custom {
+                        Some(_) => "custom".to_string(),
+                        None => gate.gate_type.text().to_string(),
+                    },
+                    x: (gate.rect.x / GRID_SIZE).round() as i32,
+                    y: (gate.rect.y / GRID_SIZE).round() as i32,
+                    rotation: gate.rotation.clone(),
+                    label: gate.label.clone(),
+                    active: gate.active,
+                    inputs: gate.input.iter().map(|pin| pin.wire_index.and_then(source_of)).collect(),
+                    custom,
+                }
+            })
+            .collect();
+
+        let probes = circuit
+            .probes
+            .iter()
+            .filter_map(|probe| {
+                Some(SavedProbe {
+                    name: probe.name.clone(),
+                    wire: source_of(probe.wire)?,
+                })
+            })
+            .collect();
+        TextSave {
+            version: TEXT_SAVE_VERSION,
+            gates,
+            probes,
+        }
+    }
+
+    pub fn to_circuit(&self) -> Result<Circuit, String> {
+        if self.version > TEXT_SAVE_VERSION {
+            return Err(format!("save version {} is newer than this build reads ({})", self.version, TEXT_SAVE_VERSION));
+        }
+        let mut circuit = Circuit::new();
+        let mut gates = self.gates.iter().collect::<Vec<_>>();
+        gates.sort_by_key(|gate| gate.id);
+
+        // custom gates point at gates and wires that don't exist yet, they get their pins now
+        // and the keys once everything is in
+        let mut keys: HashMap<usize, GateKey> = HashMap::new();
+        let mut placeholders = vec![];
+        for saved in &gates {
+            let gate_type = match (&saved.custom, gate_type_of(&saved.gate_type)) {
+                (Some(custom), _) => GateType::CUSTOM {
+                    gates: vec![],
+                    color: Color::from(custom.color),
+                    text: custom.text.clone(),
+                    inputs: vec![WireKey::default(); custom.inputs.len()],
+                    outputs: vec![WireKey::default(); custom.outputs.len()],
+                },
+                (None, Some(gate_type)) => gate_type,
+                (None, None) => return Err(format!("gate {} has unknown type '{}'", saved.id, saved.gate_type)),
+            };
+            let rect = Rect::new(saved.x as f32 * GRID_SIZE, saved.y as f32 * GRID_SIZE, GRID_SIZE, GRID_SIZE);
+            let mut gate = Gate::new(rect, saved.rotation.clone(), gate_type);
+            gate.label = saved.label.clone();
+            gate.active = saved.active;
+
+            // a fresh slotmap hands out slots in order, placeholders take the ones between ids
+            let mut key = circuit.gates.insert(gate.clone());
+            while slot(key) < saved.id && saved.id - slot(key) <= MAX_GAP {
+                placeholders.push(key);
+                key = circuit.gates.insert(gate.clone());
+            }
+            if keys.insert(saved.id, key).is_some() {
+                return Err(format!("gate id {} is used twice", saved.id));
+            }
+        }
+        for key in placeholders {
+            circuit.gates.remove(key);
+        }
+
+        let output = |circuit: &Circuit, source: &Source| -> Result<(GateKey, usize), String> {
+            match keys.get(&source.gate) {
+                Some(key) if source.pin < circuit.gates[*key].output.len() => Ok((*key, source.pin)),
+                _ => Err(format!("no output pin {} on gate {}", source.pin, source.gate)),
+            }
+        };
+        for saved in &gates {
+            let key = keys[&saved.id];
+            if saved.inputs.len() != circuit.gates[key].input.len() {
+                return Err(format!("gate {} has {} inputs, not {}", saved.id, circuit.gates[key].input.len(), saved.inputs.len()));
+            }
+            for (pin, source) in saved.inputs.iter().enumerate() {
+                if let Some(source) = source {
+                    let (from, from_pin) = output(&circuit, source)?;
+                    circuit.connect_wire(from, key, from_pin, PinType::Output, pin, PinType::Input);
+                }
+            }
+        }
+
+        let wire = |circuit: &Circuit, source: &Source| -> Result<WireKey, String> {
+            let (gate, pin) = output(circuit, source)?;
+            circuit.gates[gate].output[pin]
+                .wire_index
+                .ok_or(format!("output pin {} of gate {} isn't wired", source.pin, source.gate))
+        };
+        for saved in &gates {
+            let Some(custom) = &saved.custom else {
+                continue;
+            };
+            let members = custom
+                .gates
+                .iter()
+                .map(|id| keys.get(id).copied().ok_or(format!("custom gate {} holds missing gate {}", saved.id, id)))
+                .collect::<Result<Vec<GateKey>, String>>()?;
+            let boundary_inputs = custom.inputs.iter().map(|source| wire(&circuit, source)).collect::<Result<Vec<_>, _>>()?;
+            let boundary_outputs = custom.outputs.iter().map(|source| wire(&circuit, source)).collect::<Result<Vec<_>, _>>()?;
+            if let GateType::CUSTOM { gates, inputs, outputs, .. } = &mut circuit.gates[keys[&saved.id]].gate_type {
+                *gates = members;
+                *inputs = boundary_inputs;
+                *outputs = boundary_outputs;
+            }
+        }
+
+        for probe in &self.probes {
+            let wire = wire(&circuit, &probe.wire)?;
+            circuit.probes.push(Probe::new(probe.name.clone(), wire));
+        }
+        Ok(circuit)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::equivalence::{self, Equivalence, Matching};
+    use crate::formats::{blif, verilog};
+
+    const ADDER: &str = "
+        module half (input a, b, output s, c);
+          xor (s, a, b);
+          and (c, a, b);
+        endmodule
+        module top (input a, b, cin, output sum, cout);
+          wire s1, c1, c2;
+          half h1 (a, b, s1, c1);
+          half h2 (s1, cin, sum, c2);
+          or (cout, c1, c2);
+        endmodule";
+
+    const COUNTER: &str = "
+        .model counter
+        .inputs en
+        .outputs q0 q1
+        .latch n0 q0 0
+        .latch n1 q1 0
+        .names en q0 n0
+        01 1
+        10 1
+        .names en q0 q1 n1
+        0-1 1
+        -01 1
+        110 1
+        .end";
+
+    fn text(circuit: &Circuit) -> String {
+        serde_json::to_string_pretty(&TextSave::from_circuit(circuit)).unwrap()
+    }
+
+    fn load(text: &str) -> Circuit {
+        serde_json::from_str::<TextSave>(text).unwrap().to_circuit().unwrap()
+    }
+
+    #[test]
+    fn round_trip_keeps_the_circuit_and_the_text() {
+        let mut circuit = verilog::import(ADDER).unwrap();
+        let wire = circuit.wires.keys().next().unwrap();
+        circuit.probes.push(Probe::new("first".to_string(), wire));
+
+        let saved = text(&circuit);
+        let loaded = load(&saved);
+        assert_eq!(text(&loaded), saved);
+        assert_eq!(loaded.probes.len(), 1);
+        assert!(matches!(equivalence::check(&circuit, &loaded, Matching::Names), Ok(Equivalence::Equivalent)));
+    }
+
+    #[test]
+    fn deleting_a_gate_keeps_the_other_ids() {
+        let mut circuit = blif::import(COUNTER).unwrap();
+        let before = TextSave::from_circuit(&circuit);
+        let removed = circuit.gates.keys().nth(3).unwrap();
+        circuit.remove_gate(removed);
+        let after = TextSave::from_circuit(&circuit);
+
+        assert_eq!(after.gates.len() + 1, before.gates.len());
+        for gate in &after.gates {
+            let old = before.gates.iter().find(|old| old.id == gate.id).unwrap();
+            assert_eq!((&old.gate_type, old.x, old.y), (&gate.gate_type, gate.x, gate.y));
+        }
+
+        // loading gives the gates their ids back, saving again changes nothing
+        let saved = serde_json::to_string_pretty(&after).unwrap();
+        let mut loaded = load(&saved);
+        assert_eq!(text(&loaded), saved);
+
+        // and a gate added afterwards doesn't move the others either
+        let gate = Gate::new(Rect::new(0.0, -64.0, 64.0, 64.0), Rotation::Up, GateType::NOT);
+        loaded.gates.insert(gate);
+        let extended = TextSave::from_circuit(&loaded);
+        for gate in &after.gates {
+            assert!(extended.gates.iter().any(|new| new.id == gate.id && new.gate_type == gate.gate_type));
+        }
+    }
+
+    #[test]
+    fn bad_references_are_errors() {
+        let saved = r#"{"gates": [{"id": 1, "type": "and", "x": 0, "y": 0, "rotation": "Up", "active": false,
+            "inputs": [{"gate": 7, "pin": 0}, null]}]}"#;
+        let save: TextSave = serde_json::from_str(saved).unwrap();
+        assert!(save.to_circuit().is_err());
+    }
+}
@@ -7,7 +7,7 @@ pub struct Connection {
     pub gate_index: GateKey, // all connections are input types
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Wire {
     pub source: Connection, // output type connection
     pub connections: Vec<Connection>,
@@ -1,4 +1,5 @@
 use crate::types::gate_type::*;
+use crate::waveform::{Signal, WaveformRecorder, value_char};
 use macroquad::prelude::*;
 
 pub fn draw_ui_gate(current_selection: GateType) {
@@ -37,3 +38,307 @@ pub fn draw_ui(log_msg: String) {
     draw_rectangle(x, y, screen_width() - x, screen_height() - y, WHITE);
     draw_text(log_msg.as_str(), x, y + 50.0, 16 as f32, BLACK);
 }
+
+// table drawn in the top right corner, columns are sized to their widest cell
+pub fn draw_table_panel(title: &str, header: &[String], rows: &[Vec<String>]) {
+    set_default_camera();
+
+    let font_size = 16;
+    let line_height = 20.0;
+    let padding = 10.0;
+    let max_rows = ((screen_height() - 200.0) / line_height).max(1.0) as usize;
+
+    let mut column_widths: Vec<f32> = header
+        .iter()
+        .map(|cell| measure_text(cell, None, font_size, 1.0).width)
+        .collect();
+    for row in rows.iter().take(max_rows) {
+        for (index, cell) in row.iter().enumerate() {
+            column_widths[index] = column_widths[index].max(measure_text(cell, None, font_size, 1.0).width);
+        }
+    }
+
+    let title_width = measure_text(title, None, font_size, 1.0).width;
+    let table_width = column_widths.iter().map(|w| w + padding).sum::<f32>();
+    let width = title_width.max(table_width) + padding * 2.0;
+    let shown_rows = rows.len().min(max_rows) + if rows.len() > max_rows { 1 } else { 0 };
+    let height = line_height * (shown_rows + 2) as f32 + padding * 2.0;
+
+    let x = screen_width() - width - padding;
+    let y = padding;
+    draw_rectangle(x, y, width, height, WHITE);
+    draw_rectangle_lines(x, y, width, height, 2.0, BLACK);
+    draw_text(title, x + padding, y + padding + line_height * 0.75, font_size as f32, BLACK);
+
+    let lines = std::iter::once(header).chain(rows.iter().take(max_rows).map(|row| row.as_slice()));
+    for (line_index, line) in lines.enumerate() {
+        let ty = y + padding + line_height * (line_index as f32 + 1.75);
+        let mut tx = x + padding;
+        for (index, cell) in line.iter().enumerate() {
+            let color = if line_index == 0 { DARKBLUE } else { BLACK };
+            draw_text(cell, tx, ty, font_size as f32, color);
+            tx += column_widths[index] + padding;
+        }
+    }
+
+    if rows.len() > max_rows {
+        let ty = y + height - padding * 0.5;
+        draw_text(&format!("... {} more rows", rows.len() - max_rows), x + padding, ty, font_size as f32, GRAY);
+    }
+}
+
+// timing diagram of the recorded wires, sits on top of the bottom strip drawn by draw_ui
+pub struct WaveformPanel {
+    pub open: bool,
+    pub height: f32,
+    pub pixels_per_tick: f32,
+    // leftmost visible tick
+    pub start_tick: f32,
+    // keep the latest tick in view while recording
+    pub follow: bool,
+    // placed with left / right click
+    pub cursors: [Option<usize>; 2],
+    resizing: bool,
+    panning_from: Option<(f32, f32)>,
+}
+
+const WAVE_STRIP_HEIGHT: f32 = 100.0;
+const WAVE_NAME_WIDTH: f32 = 140.0;
+const WAVE_ROW_HEIGHT: f32 = 24.0;
+const WAVE_HEADER_HEIGHT: f32 = 22.0;
+const WAVE_MIN_HEIGHT: f32 = 80.0;
+
+impl Default for WaveformPanel {
+    fn default() -> Self {
+        WaveformPanel::new()
+    }
+}
+
+impl WaveformPanel {
+    pub fn new() -> WaveformPanel {
+        WaveformPanel {
+            open: false,
+            height: 200.0,
+            pixels_per_tick: 8.0,
+            start_tick: 0.0,
+            follow: true,
+            cursors: [None, None],
+            resizing: false,
+            panning_from: None,
+        }
+    }
+
+    fn rect(&self) -> Rect {
+        let bottom = screen_height() - WAVE_STRIP_HEIGHT;
+        Rect::new(0.0, bottom - self.height, screen_width(), self.height)
+    }
+
+    fn visible_ticks(&self) -> f32 {
+        (screen_width() - WAVE_NAME_WIDTH) / self.pixels_per_tick
+    }
+
+    fn tick_to_x(&self, tick: f32) -> f32 {
+        WAVE_NAME_WIDTH + (tick - self.start_tick) * self.pixels_per_tick
+    }
+
+    fn x_to_tick(&self, x: f32) -> f32 {
+        self.start_tick + (x - WAVE_NAME_WIDTH) / self.pixels_per_tick
+    }
+
+    // returns true when the panel took the mouse, the circuit shouldn't see it then
+    //
+    // drag the top edge to resize, wheel zooms around the mouse, shift + wheel or middle drag
+    // scrolls, left / right click places the two cursors
+    pub fn handle_input(&mut self, recorder: &WaveformRecorder) -> bool {
+        if !self.open {
+            return false;
+        }
+
+        let (mouse_x, mouse_y) = mouse_position();
+        let rect = self.rect();
+        let on_edge = (mouse_y - rect.y).abs() < 6.0;
+        let inside = rect.contains(vec2(mouse_x, mouse_y));
+
+        if is_mouse_button_pressed(MouseButton::Left) && on_edge {
+            self.resizing = true;
+        }
+        if self.resizing {
+            if is_mouse_button_down(MouseButton::Left) {
+                let bottom = screen_height() - WAVE_STRIP_HEIGHT;
+                self.height = (bottom - mouse_y).clamp(WAVE_MIN_HEIGHT, (bottom - 50.0).max(WAVE_MIN_HEIGHT));
+            } else {
+                self.resizing = false;
+            }
+            return true;
+        }
+
+        if self.follow {
+            self.start_tick = (recorder.last_tick as f32 + 1.0 - self.visible_ticks()).max(0.0);
+        }
+
+        if let Some((from_x, from_tick)) = self.panning_from {
+            if is_mouse_button_down(MouseButton::Middle) {
+                self.start_tick = (from_tick - (mouse_x - from_x) / self.pixels_per_tick).max(0.0);
+                self.follow = false;
+            } else {
+                self.panning_from = None;
+            }
+            return true;
+        }
+
+        if !inside {
+            return false;
+        }
+
+        let (_, wheel) = mouse_wheel();
+        if wheel != 0.0 {
+            if is_key_down(KeyCode::LeftShift) {
+                let step = self.visible_ticks() * 0.1 * wheel.signum();
+                self.start_tick = (self.start_tick - step).max(0.0);
+                self.follow = false;
+            } else {
+                let anchor = self.x_to_tick(mouse_x.max(WAVE_NAME_WIDTH));
+                let factor = if wheel > 0.0 { 1.25 } else { 0.8 };
+                self.pixels_per_tick = (self.pixels_per_tick * factor).clamp(0.05, 64.0);
+                if !self.follow {
+                    self.start_tick = (anchor - (mouse_x.max(WAVE_NAME_WIDTH) - WAVE_NAME_WIDTH) / self.pixels_per_tick).max(0.0);
+                }
+            }
+        }
+
+        if is_mouse_button_pressed(MouseButton::Middle) {
+            self.panning_from = Some((mouse_x, self.start_tick));
+        }
+        if mouse_x > WAVE_NAME_WIDTH && mouse_y > rect.y + WAVE_HEADER_HEIGHT {
+            let tick = self.x_to_tick(mouse_x).max(0.0).round() as usize;
+            if is_mouse_button_pressed(MouseButton::Left) {
+                self.cursors[0] = Some(tick);
+            } else if is_mouse_button_pressed(MouseButton::Right) {
+                self.cursors[1] = Some(tick);
+            }
+        }
+        // scrolling back to the latest tick picks following up again
+        if self.start_tick + self.visible_ticks() >= recorder.last_tick as f32 + 1.0 {
+            self.follow = true;
+        }
+        true
+    }
+
+    pub fn draw(&self, recorder: &WaveformRecorder) {
+        if !self.open {
+            return;
+        }
+        set_default_camera();
+
+        let rect = self.rect();
+        let font_size = 16.0;
+        draw_rectangle(rect.x, rect.y, rect.w, rect.h, WHITE);
+        draw_line(rect.x, rect.y, rect.right(), rect.y, 3.0, DARKGRAY);
+
+        let mut title = format!("waveforms (F3) {} ", if recorder.recording { "recording" } else { "paused (O)" });
+        for (index, cursor) in self.cursors.iter().enumerate() {
+            if let Some(tick) = cursor {
+                title += &format!("| {}: tick {} ", ["A", "B"][index], tick);
+            }
+        }
+        if let [Some(a), Some(b)] = self.cursors {
+            title += &format!("| B - A: {} ticks", b as i64 - a as i64);
+        }
+        draw_text(&title, rect.x + 6.0, rect.y + 16.0, font_size, BLACK);
+
+        let left = self.start_tick.floor() as usize;
+        let right = (self.start_tick + self.visible_ticks()).ceil() as usize;
+        self.draw_tick_axis(rect, left, right);
+
+        let top = rect.y + WAVE_HEADER_HEIGHT;
+        let max_rows = ((rect.bottom() - top) / WAVE_ROW_HEIGHT).floor() as usize;
+        for (row, signal) in recorder.signals.iter().take(max_rows).enumerate() {
+            let y = top + row as f32 * WAVE_ROW_HEIGHT;
+            let mut name = signal.name.clone();
+            for tick in self.cursors.iter().flatten() {
+                name += &format!(" {}", value_char(signal.value_at(*tick)));
+            }
+            draw_text(&name, rect.x + 6.0, y + WAVE_ROW_HEIGHT * 0.7, font_size, BLACK);
+            self.draw_signal(signal, y, left, right, recorder.last_tick);
+        }
+        if recorder.signals.len() > max_rows {
+            let more = format!("... {} more", recorder.signals.len() - max_rows);
+            draw_text(&more, rect.x + 6.0, rect.bottom() - 4.0, font_size, GRAY);
+        }
+        if recorder.signals.is_empty() {
+            let hint = "select gates and press V to record their output wires";
+            draw_text(hint, WAVE_NAME_WIDTH, top + WAVE_ROW_HEIGHT * 0.7, font_size, GRAY);
+        }
+
+        for (cursor, color) in self.cursors.iter().zip([RED, DARKGREEN]) {
+            if let Some(tick) = cursor {
+                let x = self.tick_to_x(*tick as f32);
+                if x >= WAVE_NAME_WIDTH && x <= rect.right() {
+                    draw_line(x, top, x, rect.bottom(), 1.0, color);
+                }
+            }
+        }
+        draw_line(WAVE_NAME_WIDTH, top, WAVE_NAME_WIDTH, rect.bottom(), 1.0, LIGHTGRAY);
+    }
+
+    // labels every 1, 2 or 5 * 10^n ticks, whichever keeps them at least 60 pixels apart
+    fn draw_tick_axis(&self, rect: Rect, left: usize, right: usize) {
+        let mut step = 1;
+        'search: for scale in [1, 10, 100, 1000, 10_000, 100_000, 1_000_000] {
+            for base in [1, 2, 5] {
+                step = base * scale;
+                if step as f32 * self.pixels_per_tick >= 60.0 {
+                    break 'search;
+                }
+            }
+        }
+
+        let y = rect.y + WAVE_HEADER_HEIGHT;
+        let mut tick = left.div_ceil(step) * step;
+        while tick <= right {
+            let x = self.tick_to_x(tick as f32);
+            draw_line(x, y, x, rect.bottom(), 1.0, Color::new(0.9, 0.9, 0.9, 1.0));
+            draw_text(&tick.to_string(), x + 2.0, y - 2.0, 14.0, GRAY);
+            tick += step;
+        }
+    }
+
+    fn draw_signal(&self, signal: &Signal, y: f32, left: usize, right: usize, last_tick: usize) {
+        let high = y + 4.0;
+        let low = y + WAVE_ROW_HEIGHT - 4.0;
+        let x_min = WAVE_NAME_WIDTH;
+        let x_max = screen_width();
+
+        // starts at the change in effect at the left edge, a value holds until the next change
+        // and the last one until the last sampled tick
+        let first = signal.changes.partition_point(|(tick, _)| *tick <= left).saturating_sub(1);
+        let mut changes = signal.changes.range(first..).peekable();
+        let mut last_transition = f32::MIN;
+        while let Some((tick, value)) = changes.next() {
+            if *tick > right {
+                break;
+            }
+            let end = changes.peek().map(|(next, _)| *next).unwrap_or(last_tick + 1);
+            let from = self.tick_to_x(*tick as f32).clamp(x_min, x_max);
+            let to = self.tick_to_x(end as f32).clamp(x_min, x_max);
+            draw_level(from, to, high, low, *value);
+
+            // zoomed far out changes pile up on the same pixel, one line is enough for them
+            if from > x_min && from - last_transition >= 1.0 {
+                draw_line(from, high, from, low, 1.0, DARKGREEN);
+                last_transition = from;
+            }
+        }
+    }
+}
+
+fn draw_level(from: f32, to: f32, high: f32, low: f32, value: Option<bool>) {
+    if to <= from {
+        return;
+    }
+    match value {
+        Some(true) => draw_line(from, high, to, high, 2.0, DARKGREEN),
+        Some(false) => draw_line(from, low, to, low, 2.0, DARKGREEN),
+        None => draw_rectangle(from, high, to - from, low - high, Color::new(0.8, 0.2, 0.2, 0.3)),
+    }
+}
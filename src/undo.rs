@@ -0,0 +1,92 @@
+use crate::types::circuit::*;
+
+// edits kept, the oldest are dropped first
+pub const MAX_UNDO: usize = 100;
+
+// whole circuit copies taken before every edit, the RTree is rebuilt from the gates on restore
+#[derive(Default)]
+pub struct UndoStack {
+    undo: Vec<Circuit>,
+    redo: Vec<Circuit>,
+    // copy from the start of a drag, pushed when it ends if a gate moved
+    pending: Option<Circuit>,
+}
+
+impl UndoStack {
+    pub fn new() -> UndoStack {
+        UndoStack::default()
+    }
+
+    pub fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+        self.pending = None;
+    }
+
+    // call right before changing the circuit, a new edit can't be redone past
+    pub fn checkpoint(&mut self, circuit: &Circuit) {
+        self.undo.push(circuit.clone());
+        if self.undo.len() > MAX_UNDO {
+            self.undo.remove(0);
+        }
+        self.redo.clear();
+    }
+
+    // call when a drag starts, the gates move every frame but it undoes as one edit
+    pub fn begin_drag(&mut self, circuit: &Circuit) {
+        self.pending = Some(circuit.clone());
+    }
+
+    // dropping gates back where they were isn't an edit
+    pub fn end_drag(&mut self, circuit: &Circuit) {
+        let Some(before) = self.pending.take() else {
+            return;
+        };
+        let moved = before.gates.len() != circuit.gates.len()
+            || circuit.gates.iter().any(|(key, gate)| {
+                before.gates.get(key).is_none_or(|old| old.rect != gate.rect || old.rotation != gate.rotation)
+            });
+        if moved {
+            self.checkpoint(&before);
+        }
+    }
+
+    pub fn undo(&mut self, current: &Circuit) -> Option<Circuit> {
+        self.pending = None;
+        let previous = self.undo.pop()?;
+        self.redo.push(current.clone());
+        Some(previous)
+    }
+
+    pub fn redo(&mut self, current: &Circuit) -> Option<Circuit> {
+        self.pending = None;
+        let next = self.redo.pop()?;
+        self.undo.push(current.clone());
+        Some(next)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::gate::*;
+    use crate::types::gate_type::*;
+    use macroquad::prelude::*;
+
+    #[test]
+    fn drags_that_end_in_place_leave_no_undo_step() {
+        let mut circuit = Circuit::new();
+        let key = circuit.gates.insert(Gate::new(Rect::new(0.0, 0.0, 64.0, 64.0), Rotation::Up, GateType::NOT));
+        let mut undo_stack = UndoStack::new();
+
+        undo_stack.begin_drag(&circuit);
+        undo_stack.end_drag(&circuit);
+        assert!(undo_stack.undo(&circuit).is_none());
+
+        undo_stack.begin_drag(&circuit);
+        circuit.gates[key].offset(vec2(64.0, 0.0));
+        undo_stack.end_drag(&circuit);
+        let before = undo_stack.undo(&circuit).unwrap();
+        assert_eq!(before.gates[key].rect.x, 0.0);
+    }
+}
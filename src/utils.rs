@@ -9,7 +9,9 @@ use macroquad::prelude::Rect;
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::fs;
 use std::fs::{File};
-use std::io::{BufReader, BufWriter};
+use std::io::{BufWriter, Read, Write};
+use crate::types::legacy::{SAVE_MAGIC, SAVE_VERSION, read_save};
+use crate::types::text_save::TextSave;
 
 use flate2::read::GzDecoder;
 use flate2::write::GzEncoder;
@@ -143,6 +145,41 @@ pub fn draw_pins(circuit: &Circuit, camera: &Camera2D) {
     }
 }
 
+// wires drawn again thicker on top, e.g. the ones that made a breakpoint fire
+pub fn draw_highlighted_wires(circuit: &Circuit, camera: &Camera2D, wires: &[WireKey]) {
+    set_camera(camera);
+    for wire_key in wires {
+        let Some(wire) = circuit.wires.get(*wire_key) else {
+            continue;
+        };
+        let Some(source_gate) = circuit.gates.get(wire.source.gate_index) else {
+            continue;
+        };
+        let start = source_gate.get_pin_rect(wire.source.pin_index, PinType::Output).center();
+        for connection in &wire.connections {
+            if let Some(gate) = circuit.gates.get(connection.gate_index) {
+                let end = gate.get_pin_rect(connection.pin_index, PinType::Input).center();
+                draw_line(start.x, start.y, end.x, end.y, 7.0, RED);
+            }
+        }
+    }
+}
+
+// probe names next to the source pin of their wire
+pub fn draw_probes(circuit: &Circuit, camera: &Camera2D) {
+    set_camera(camera);
+    for probe in &circuit.probes {
+        let Some(wire) = circuit.wires.get(probe.wire) else {
+            continue;
+        };
+        if let Some(gate) = circuit.gates.get(wire.source.gate_index) {
+            let center = gate.get_pin_rect(wire.source.pin_index, PinType::Output).center();
+            draw_circle(center.x, center.y, 6.0, MAGENTA);
+            draw_text(&probe.name, center.x + 8.0, center.y - 8.0, 18.0, MAGENTA);
+        }
+    }
+}
+
 pub fn draw_mouse_wire(
     circuit: &Circuit,
     camera: &Camera2D,
@@ -262,7 +299,9 @@ pub mod color_serde {
     }
 }
 
-pub fn save_to_file(circuit: &Circuit, file_name: String) -> std::io::Result<String> {
+// text saves are pretty printed json, binary ones gzip-compressed bincode. both use .save,
+// loading tells them apart
+pub fn save_to_file(circuit: &Circuit, file_name: String, binary: bool) -> std::io::Result<String> {
     // 1. Define the directory path relative to project root
     let save_dir = "tmp/saves";
 
@@ -271,11 +310,21 @@ pub fn save_to_file(circuit: &Circuit, file_name: String) -> std::io::Result<Str
     fs::create_dir_all(save_dir)?;
 
     let file_path = format!("{}/{}.save", save_dir, file_name);
+    if !binary {
+        let mut text = serde_json::to_string_pretty(&TextSave::from_circuit(circuit)).map_err(std::io::Error::other)?;
+        text.push('\n');
+        fs::write(&file_path, text)?;
+        println!("Success: saved circuit to {}", file_path);
+        return Ok(file_path);
+    }
+
     let file = File::create(&file_path)?;
     let writer = BufWriter::new(file);
 
     let mut encoder = GzEncoder::new(writer, Compression::best());
 
+    encoder.write_all(SAVE_MAGIC)?;
+    encoder.write_all(&SAVE_VERSION.to_le_bytes())?;
     bincode::serialize_into(&mut encoder, circuit)
         .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
     encoder.finish()?;
@@ -284,21 +333,58 @@ pub fn save_to_file(circuit: &Circuit, file_name: String) -> std::io::Result<Str
     Ok(file_path)
 }
 
+// the game loop blocks while typing, same as when saving / loading
+pub fn read_line(prompt: &str) -> String {
+    println!("{}", prompt);
+    let mut input = String::new();
+    std::io::stdin()
+        .read_line(&mut input)
+        .expect("Failed to read line");
+    input.trim().to_string()
+}
+
+// plain text exports (truth tables, netlists, ...) go next to the saves
+pub fn export_to_file(contents: &str, file_name: String, extension: &str) -> std::io::Result<String> {
+    let export_dir = "tmp/exports";
+    fs::create_dir_all(export_dir)?;
+
+    let file_path = format!("{}/{}.{}", export_dir, file_name, extension);
+    fs::write(&file_path, contents)?;
+
+    println!("Success: exported to {}", file_path);
+    Ok(file_path)
+}
+
 pub fn load_from_file(file_path: &str) -> std::io::Result<Circuit> {
     let clean_path = file_path.trim();
     
     // 1. Open File
-    let file = File::open(clean_path)?;
-    let reader = BufReader::new(file);
+    let contents = fs::read(clean_path)?;
+
+    // text saves don't start with the gzip magic number
+    if !contents.starts_with(&[0x1f, 0x8b]) {
+        let save: TextSave = serde_json::from_slice(&contents).map_err(std::io::Error::other)?;
+        let mut circuit = save.to_circuit().map_err(std::io::Error::other)?;
+        circuit.init_wire_buffers();
+        eprintln!("Loaded circuit from {}", clean_path);
+        return Ok(circuit);
+    }
 
-    // 2. Create Decompressor
-    let mut decoder = GzDecoder::new(reader);
+    // 2. Decompress
+    let mut decoder = GzDecoder::new(contents.as_slice());
+    let mut bytes = vec![];
+    decoder.read_to_end(&mut bytes)?;
 
-    // 3. Deserialize Binary Data
-    let circuit: Circuit = bincode::deserialize_from(&mut decoder)
-        .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
+    // 3. Read the header and upgrade older saves to the current layout
+    let mut circuit = read_save(&bytes).map_err(std::io::Error::other)?;
+
+    // 4. Drop references that point nowhere, they would panic later. text saves are rebuilt
+    // through connect_wire and can't have any
+    for problem in circuit.repair() {
+        eprintln!("repaired: {}", problem);
+    }
 
-    println!("Loaded compressed circuit from {}", clean_path);
+    eprintln!("Loaded compressed circuit from {}", clean_path);
     Ok(circuit)
 }
 
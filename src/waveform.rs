@@ -0,0 +1,187 @@
+use crate::types::circuit::*;
+use crate::types::keys::*;
+use std::collections::{BTreeMap, VecDeque};
+
+// changes kept per signal, the oldest are dropped so long runs don't grow without bound
+pub const MAX_CHANGES: usize = 100_000;
+
+pub struct Signal {
+    pub wire: WireKey,
+    pub name: String,
+    // (tick, value) every time the value changed, None while it wasn't recorded
+    pub changes: VecDeque<(usize, Option<bool>)>,
+}
+
+impl Signal {
+    // None before the first kept change or while recording was off
+    pub fn value_at(&self, tick: usize) -> Option<bool> {
+        let index = self.changes.partition_point(|(change_tick, _)| *change_tick <= tick);
+        match index {
+            0 => None,
+            _ => self.changes[index - 1].1,
+        }
+    }
+
+    fn push(&mut self, tick: usize, value: Option<bool>) {
+        // after a rewind the ticks from there on are recorded again
+        while self.changes.back().is_some_and(|(last_tick, _)| *last_tick >= tick) {
+            self.changes.pop_back();
+        }
+        if self.changes.back().map(|(_, last)| *last) == Some(value) {
+            return;
+        }
+        self.changes.push_back((tick, value));
+        if self.changes.len() > MAX_CHANGES {
+            self.changes.pop_front();
+        }
+    }
+}
+
+// samples chosen wires from wires_read after every tick, only value changes are stored
+#[derive(Default)]
+pub struct WaveformRecorder {
+    pub recording: bool,
+    pub signals: Vec<Signal>,
+    // last tick that was sampled
+    pub last_tick: usize,
+}
+
+impl WaveformRecorder {
+    pub fn new() -> WaveformRecorder {
+        WaveformRecorder::default()
+    }
+
+    pub fn is_recorded(&self, wire: WireKey) -> bool {
+        self.signals.iter().any(|signal| signal.wire == wire)
+    }
+
+    // names are made unique and stripped of whitespace, vcd uses it as a separator
+    pub fn add(&mut self, wire: WireKey, name: &str) {
+        if self.is_recorded(wire) {
+            return;
+        }
+        let base: String = name.split_whitespace().collect::<Vec<_>>().join("_");
+        let mut name = base.clone();
+        let mut suffix = 1;
+        while self.signals.iter().any(|signal| signal.name == name) {
+            name = format!("{}_{}", base, suffix);
+            suffix += 1;
+        }
+        self.signals.push(Signal {
+            wire,
+            name,
+            changes: VecDeque::new(),
+        });
+    }
+
+    pub fn remove(&mut self, wire: WireKey) {
+        self.signals.retain(|signal| signal.wire != wire);
+    }
+
+    pub fn clear(&mut self) {
+        for signal in &mut self.signals {
+            signal.changes.clear();
+        }
+    }
+
+    // starting samples the circuit right away, stopping marks every signal unknown from the next tick
+    pub fn set_recording(&mut self, recording: bool, circuit: &Circuit) {
+        if recording == self.recording {
+            return;
+        }
+        self.recording = recording;
+        match recording {
+            true => self.sample(circuit),
+            false => {
+                for signal in &mut self.signals {
+                    signal.push(circuit.tick_count + 1, None);
+                }
+            }
+        }
+    }
+
+    pub fn sample(&mut self, circuit: &Circuit) {
+        if !self.recording {
+            return;
+        }
+        let tick = circuit.tick_count;
+        for signal in &mut self.signals {
+            let value = circuit.wires_read.get(signal.wire).copied();
+            signal.push(tick, value);
+        }
+        self.last_tick = tick;
+    }
+
+    // earliest tick any signal has a value for
+    pub fn first_tick(&self) -> Option<usize> {
+        self.signals
+            .iter()
+            .filter_map(|signal| signal.changes.front().map(|(tick, _)| *tick))
+            .min()
+    }
+
+    // Value Change Dump, one time unit per tick
+    pub fn to_vcd(&self) -> String {
+        let mut text = String::new();
+        text += "$version playground $end\n";
+        text += "$timescale 1ns $end\n";
+        text += "$scope module circuit $end\n";
+        for (index, signal) in self.signals.iter().enumerate() {
+            text += &format!("$var wire 1 {} {} $end\n", identifier(index), signal.name);
+        }
+        text += "$upscope $end\n";
+        text += "$enddefinitions $end\n";
+
+        let Some(first_tick) = self.first_tick() else {
+            return text;
+        };
+
+        text += &format!("#{}\n$dumpvars\n", first_tick);
+        for (index, signal) in self.signals.iter().enumerate() {
+            text += &format!("{}{}\n", value_char(signal.value_at(first_tick)), identifier(index));
+        }
+        text += "$end\n";
+
+        let mut changes: BTreeMap<usize, Vec<(usize, Option<bool>)>> = BTreeMap::new();
+        for (index, signal) in self.signals.iter().enumerate() {
+            for (tick, value) in &signal.changes {
+                if *tick > first_tick {
+                    changes.entry(*tick).or_default().push((index, *value));
+                }
+            }
+        }
+        for (tick, values) in &changes {
+            text += &format!("#{}\n", tick);
+            for (index, value) in values {
+                text += &format!("{}{}\n", value_char(*value), identifier(*index));
+            }
+        }
+
+        // so viewers show the whole run even if nothing changed at the end
+        if changes.keys().next_back().is_none_or(|tick| *tick < self.last_tick) && self.last_tick > first_tick {
+            text += &format!("#{}\n", self.last_tick);
+        }
+        text
+    }
+}
+
+pub fn value_char(value: Option<bool>) -> char {
+    match value {
+        Some(true) => '1',
+        Some(false) => '0',
+        None => 'x',
+    }
+}
+
+// short identifier codes out of the printable ascii range vcd allows, '!' '"' ... '~' '!!' ...
+fn identifier(mut index: usize) -> String {
+    let mut code = String::new();
+    loop {
+        code.push((b'!' + (index % 94) as u8) as char);
+        index /= 94;
+        if index == 0 {
+            return code;
+        }
+        index -= 1;
+    }
+}